@@ -0,0 +1,95 @@
+use bfv::{
+    BfvParameters, Encoding, EvaluationKey, Evaluator, PolyCache, PolyType, Representation,
+    SecretKey,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use itertools::Itertools;
+use rand::thread_rng;
+
+fn bench_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batch");
+    group.sample_size(10);
+
+    for degree in [1 << 15] {
+        for m_size in [12] {
+            for batch_size in [1, 8, 32] {
+                let mut rng = thread_rng();
+                let params = BfvParameters::default(m_size, degree);
+                let logq = params.poly_ctx(&PolyType::Q, 0).big_q().bits();
+                let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+                let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+                let evaluator = Evaluator::new(params);
+
+                let ms = (0..batch_size)
+                    .map(|_| {
+                        evaluator
+                            .params()
+                            .plaintext_modulus_op
+                            .random_vec(degree, &mut rng)
+                    })
+                    .collect_vec();
+                let pts = ms
+                    .iter()
+                    .map(|m| evaluator.plaintext_encode(m, Encoding::default()))
+                    .collect_vec();
+                let mul_pts = ms
+                    .iter()
+                    .map(|m| {
+                        evaluator
+                            .plaintext_encode(m, Encoding::simd(0, PolyCache::Mul(PolyType::Q)))
+                    })
+                    .collect_vec();
+                let cts = evaluator.encrypt_many(&sk, &pts, &mut rng);
+                let products = evaluator.mul_many(&cts, &cts);
+                let mut cts_eval = cts.clone();
+                cts_eval.iter_mut().for_each(|ct| {
+                    evaluator.ciphertext_change_representation(ct, Representation::Evaluation)
+                });
+
+                group.throughput(Throughput::Elements(batch_size as u64));
+                let id = |name| {
+                    BenchmarkId::new(name, format!("n={degree}/logq={logq}/batch={batch_size}"))
+                };
+
+                group.bench_function(id("encrypt_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.encrypt_many(&sk, &pts, &mut rng);
+                    });
+                });
+
+                group.bench_function(id("decrypt_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.decrypt_many(&sk, &cts);
+                    });
+                });
+
+                group.bench_function(id("mul_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.mul_many(&cts, &cts);
+                    });
+                });
+
+                group.bench_function(id("relinearize_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.relinearize_many(&products, &ek);
+                    });
+                });
+
+                group.bench_function(id("rotate_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.rotate_many(&cts, 1, &ek);
+                    });
+                });
+
+                group.bench_function(id("mul_plaintext_many"), |b| {
+                    b.iter(|| {
+                        let _ = evaluator.mul_plaintext_many(&cts_eval, &mul_pts);
+                    });
+                });
+            }
+        }
+    }
+}
+
+criterion_group!(batch, bench_batch);
+criterion_main!(batch);
@@ -82,6 +82,24 @@ fn bench_bfv(c: &mut Criterion) {
                 },
             );
 
+            let rotations = (1..=8).collect_vec();
+            let ek = EvaluationKey::new(
+                evaluator.params(),
+                &sk,
+                &[],
+                &vec![0; rotations.len()],
+                &rotations,
+                &mut rng,
+            );
+            group.bench_function(
+                BenchmarkId::new("rotate_hoisted_8", format!("n={degree}/logq={logq}")),
+                |b| {
+                    b.iter(|| {
+                        let _ = evaluator.rotate_hoisted(&c0, &rotations, &ek);
+                    });
+                },
+            );
+
             let mut c0_clone = c0.clone();
             group.bench_function(
                 BenchmarkId::new("add_assign", format!("n={degree}/logq={logq}")),
@@ -0,0 +1,249 @@
+use crate::error::BfvError;
+use crate::{Ciphertext, EvaluationKey, Evaluator, Plaintext, SecretKey, Workspace};
+use itertools::Itertools;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Batch variants of `Evaluator` operations for many independent ciphertexts. With `parallel`
+/// feature, ciphertexts are processed in parallel on rayon's global thread pool. Otherwise they
+/// are processed sequentially. Multiplications, relinearizations and rotations reuse temporary
+/// polynomials of a `Workspace` per task.
+impl Evaluator {
+    /// Multiplies `lhs[i]` with `rhs[i]`
+    pub fn mul_many(&self, lhs: &[Ciphertext], rhs: &[Ciphertext]) -> Vec<Ciphertext> {
+        self.try_mul_many(lhs, rhs).unwrap()
+    }
+
+    pub fn try_mul_many(
+        &self,
+        lhs: &[Ciphertext],
+        rhs: &[Ciphertext],
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        check_batch_len(lhs.len(), rhs.len())?;
+        try_batch_with_workspace(lhs.len(), |i, ws| {
+            let mut out = Ciphertext::placeholder();
+            self.try_mul_into(&lhs[i], &rhs[i], &mut out, ws)?;
+            Ok(out)
+        })
+    }
+
+    pub fn relinearize_many(&self, cts: &[Ciphertext], ek: &EvaluationKey) -> Vec<Ciphertext> {
+        self.try_relinearize_many(cts, ek).unwrap()
+    }
+
+    pub fn try_relinearize_many(
+        &self,
+        cts: &[Ciphertext],
+        ek: &EvaluationKey,
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        try_batch_with_workspace(cts.len(), |i, ws| {
+            let mut out = Ciphertext::placeholder();
+            self.try_relinearize_into(&cts[i], ek, &mut out, ws)?;
+            Ok(out)
+        })
+    }
+
+    /// Rotates each ciphertext by `rotate_by`
+    pub fn rotate_many(
+        &self,
+        cts: &[Ciphertext],
+        rotate_by: isize,
+        ek: &EvaluationKey,
+    ) -> Vec<Ciphertext> {
+        self.try_rotate_many(cts, rotate_by, ek).unwrap()
+    }
+
+    pub fn try_rotate_many(
+        &self,
+        cts: &[Ciphertext],
+        rotate_by: isize,
+        ek: &EvaluationKey,
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        try_batch_with_workspace(cts.len(), |i, ws| {
+            let mut out = Ciphertext::placeholder();
+            self.try_rotate_into(&cts[i], rotate_by, ek, &mut out, ws)?;
+            Ok(out)
+        })
+    }
+
+    /// Multiplies `cts[i]` with `pts[i]`. Same requirements as `mul_plaintext` apply.
+    pub fn mul_plaintext_many(&self, cts: &[Ciphertext], pts: &[Plaintext]) -> Vec<Ciphertext> {
+        self.try_mul_plaintext_many(cts, pts).unwrap()
+    }
+
+    pub fn try_mul_plaintext_many(
+        &self,
+        cts: &[Ciphertext],
+        pts: &[Plaintext],
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        check_batch_len(cts.len(), pts.len())?;
+        try_batch(cts.len(), |i| self.try_mul_plaintext(&cts[i], &pts[i]))
+    }
+
+    /// Encrypts each plaintext. Randomness of each encryption is sampled from a separate
+    /// ChaCha8 rng seeded from `rng`, thus the output is deterministic for a given `rng`
+    /// irrespective of the no. of threads.
+    pub fn encrypt_many<R: RngCore + CryptoRng>(
+        &self,
+        sk: &SecretKey,
+        pts: &[Plaintext],
+        rng: &mut R,
+    ) -> Vec<Ciphertext> {
+        self.try_encrypt_many(sk, pts, rng).unwrap()
+    }
+
+    pub fn try_encrypt_many<R: RngCore + CryptoRng>(
+        &self,
+        sk: &SecretKey,
+        pts: &[Plaintext],
+        rng: &mut R,
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        let seeds = (0..pts.len())
+            .map(|_| {
+                let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+                rng.fill_bytes(&mut seed);
+                seed
+            })
+            .collect_vec();
+
+        try_batch(pts.len(), |i| {
+            self.try_encrypt(sk, &pts[i], &mut ChaCha8Rng::from_seed(seeds[i]))
+        })
+    }
+
+    pub fn decrypt_many(&self, sk: &SecretKey, cts: &[Ciphertext]) -> Vec<Plaintext> {
+        self.try_decrypt_many(sk, cts).unwrap()
+    }
+
+    pub fn try_decrypt_many(
+        &self,
+        sk: &SecretKey,
+        cts: &[Ciphertext],
+    ) -> Result<Vec<Plaintext>, BfvError> {
+        try_batch(cts.len(), |i| self.try_decrypt(sk, &cts[i]))
+    }
+}
+
+/// Collects `f(i)` for 0 <= i < `count`, in parallel with `parallel` feature. Returns the first
+/// error if any.
+fn try_batch<O, F>(count: usize, f: F) -> Result<Vec<O>, BfvError>
+where
+    O: Send,
+    F: Fn(usize) -> Result<O, BfvError> + Send + Sync,
+{
+    #[cfg(not(feature = "parallel"))]
+    return (0..count).map(f).collect();
+    #[cfg(feature = "parallel")]
+    return (0..count).into_par_iter().map(f).collect();
+}
+
+/// Same as `try_batch` but also passes a workspace to `f`. Workspaces are reused across calls
+/// of the same thread (or rayon task).
+fn try_batch_with_workspace<O, F>(count: usize, f: F) -> Result<Vec<O>, BfvError>
+where
+    O: Send,
+    F: Fn(usize, &mut Workspace) -> Result<O, BfvError> + Send + Sync,
+{
+    #[cfg(not(feature = "parallel"))]
+    let outputs = {
+        let mut ws = Workspace::new();
+        (0..count).map(|i| f(i, &mut ws)).collect()
+    };
+    #[cfg(feature = "parallel")]
+    let outputs = (0..count)
+        .into_par_iter()
+        .map_init(Workspace::new, |ws, i| f(i, ws))
+        .collect();
+    outputs
+}
+
+fn check_batch_len(expected: usize, found: usize) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::BatchLengthMismatch { expected, found });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BfvParameters, Encoding, PolyCache, PolyType, Representation};
+    use itertools::izip;
+    use rand::thread_rng;
+
+    #[test]
+    fn batch_ops_match_single_ops() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+        let evaluator = Evaluator::new(params);
+        let modt = evaluator.params().plaintext_modulus_op.clone();
+
+        let ms = (0..8)
+            .map(|_| modt.random_vec(evaluator.params().degree, &mut rng))
+            .collect_vec();
+        let pts = ms
+            .iter()
+            .map(|m| evaluator.plaintext_encode(m, Encoding::default()))
+            .collect_vec();
+        let cts = evaluator.encrypt_many(&sk, &pts, &mut rng);
+
+        let decode = |cts: &[Ciphertext]| {
+            evaluator
+                .decrypt_many(&sk, cts)
+                .iter()
+                .map(|pt| evaluator.plaintext_decode(pt, Encoding::default()))
+                .collect_vec()
+        };
+        assert_eq!(decode(&cts), ms);
+
+        // mul and relinearize
+        let products = evaluator.relinearize_many(&evaluator.mul_many(&cts, &cts), &ek);
+        let expected = ms
+            .iter()
+            .map(|m| {
+                let mut m2 = m.clone();
+                modt.mul_mod_fast_vec(&mut m2, m);
+                m2
+            })
+            .collect_vec();
+        assert_eq!(decode(&products), expected);
+        izip!(products.iter(), cts.iter()).for_each(|(product, ct)| {
+            let single = evaluator.relinearize(&evaluator.mul(ct, ct), &ek);
+            assert_eq!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, product), Encoding::default()),
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &single), Encoding::default())
+            );
+        });
+
+        // rotate
+        let rotated = evaluator.rotate_many(&cts, 1, &ek);
+        izip!(rotated.iter(), cts.iter()).for_each(|(rotated, ct)| {
+            let single = evaluator.rotate(ct, 1, &ek);
+            assert_eq!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, rotated), Encoding::default()),
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &single), Encoding::default())
+            );
+        });
+
+        // plaintext mul
+        let mul_pts = ms
+            .iter()
+            .map(|m| evaluator.plaintext_encode(m, Encoding::simd(0, PolyCache::Mul(PolyType::Q))))
+            .collect_vec();
+        let mut cts_eval = cts.clone();
+        cts_eval.iter_mut().for_each(|ct| {
+            evaluator.ciphertext_change_representation(ct, Representation::Evaluation)
+        });
+        assert_eq!(
+            decode(&evaluator.mul_plaintext_many(&cts_eval, &mul_pts)),
+            expected
+        );
+
+        assert!(evaluator.try_mul_many(&cts, &cts[1..]).is_err());
+        assert!(evaluator.try_mul_plaintext_many(&cts, &[]).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use crate::error::{check_poly_count, BfvError};
+use crate::nb_theory::prime_power;
+use crate::{
+    BfvParameters, Ciphertext, Encoding, EvaluationKey, Evaluator, LinearTransform, Modulus,
+    Plaintext, PolyCache, PolyType, Representation, SecretKey,
+};
+use itertools::Itertools;
+use rand::{CryptoRng, RngCore};
+use traits::Ntt;
+
+/// Max. degree supported by bootstrapping. CoeffToSlot and SlotToCoeff are dense N x N
+/// transforms, which require N plaintexts and up to N rotations each.
+const MAX_BOOTSTRAPPING_DEGREE: usize = 1 << 10;
+
+/// Key for bootstrapping ciphertexts with plaintext modulus t = p^r (see `Evaluator::bootstrap`).
+///
+/// Bootstrapping is evaluated under plaintext modulus p^(r+1) with the same ciphertext moduli.
+/// Hence, the key consists of encryption of secret key under p^(r+1), relinearization and galois
+/// keys for p^(r+1), and CoeffToSlot and SlotToCoeff transforms.
+///
+/// CoeffToSlot and SlotToCoeff are dense N x N matrices applied in a single level, thus degree
+/// must be at most 2^10. Digit extraction evaluates polynomial of degree p - 1 and requires
+/// around r*log2(p) + 2*log2(p) multiplicative levels. Mod switching error of bootstrapping is
+/// bounded by (1 + hw)/2, hence p must be greater than hw + 1.
+pub struct BootstrappingKey {
+    /// evaluator with plaintext modulus p^(r+1)
+    pub(crate) evaluator: Evaluator,
+    /// encryption of secret key (coefficient encoded) under p^(r+1)
+    pub(crate) sk_ct: Ciphertext,
+    pub(crate) ek: EvaluationKey,
+    pub(crate) coeff_to_slot: LinearTransform,
+    pub(crate) slot_to_coeff: LinearTransform,
+    /// polynomial that maps x^(p^r) to lowest base p digit of x mod p^(r+1)
+    pub(crate) digit_poly: Vec<u64>,
+    pub(crate) p: u64,
+    pub(crate) r: u32,
+}
+
+impl BootstrappingKey {
+    /// Generates bootstrapping key for ciphertexts of `params` encrypted under `sk`.
+    ///
+    /// Panics if plaintext modulus is not a prime power, degree or hamming weight of secret key is
+    /// too large, or hybrid key switching is not enabled
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> BootstrappingKey {
+        BootstrappingKey::try_new(params, sk, rng).unwrap()
+    }
+
+    pub fn try_new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> Result<BootstrappingKey, BfvError> {
+        let t = params.plaintext_modulus;
+        let (p, r) = prime_power(t).ok_or_else(|| {
+            BfvError::InvalidParameters(format!("plaintext modulus {t} must be a prime power"))
+        })?;
+        if params.degree > MAX_BOOTSTRAPPING_DEGREE {
+            return Err(BfvError::InvalidParameters(format!(
+                "bootstrapping supports degree up to {MAX_BOOTSTRAPPING_DEGREE}, found {}",
+                params.degree
+            )));
+        }
+        if p <= params.hw as u64 + 1 {
+            return Err(BfvError::InvalidParameters(format!(
+                "prime {p} of plaintext modulus must be greater than hamming weight {} + 1",
+                params.hw
+            )));
+        }
+        let special_moduli: &[u64; 3] = params
+            .special_moduli
+            .as_deref()
+            .and_then(|moduli| moduli.try_into().ok())
+            .ok_or_else(|| {
+                BfvError::InvalidParameters(
+                    "bootstrapping requires hybrid key switching".to_string(),
+                )
+            })?;
+
+        // p^(r+1)
+        let bootstrapping_modulus = t.checked_mul(p).ok_or_else(|| {
+            BfvError::InvalidParameters(format!("plaintext modulus {t} is too large"))
+        })?;
+        let mut params_bt = BfvParameters::try_with_moduli(
+            &params.ciphertext_moduli,
+            &params.extension_moduli,
+            bootstrapping_modulus,
+            params.degree,
+        )?;
+        params_bt.variance = params.variance;
+        params_bt.change_hamming_weight(params.hw);
+        params_bt.try_enable_hybrid_key_switching_with_moduli(special_moduli)?;
+
+        let sk_pt = Plaintext::try_encode_signed(
+            &sk.coefficients,
+            &params_bt,
+            Encoding::poly(0, PolyCache::None),
+        )?;
+        let sk_ct = sk.try_encrypt(&params_bt, &sk_pt, rng)?;
+
+        let degree = params.degree;
+        let unit = |i: usize| {
+            let mut v = vec![0; degree];
+            v[i] = 1;
+            v
+        };
+        // slot_polys[j] is the plaintext polynomial with 1 in slot j and 0 elsewhere
+        let slot_polys = (0..degree)
+            .map(|j| {
+                let mut v = unit(params_bt.matrix_reps_index_map[j]);
+                params_bt.plaintext_ntt_op.backward(&mut v);
+                v
+            })
+            .collect_vec();
+        // monomial_slots[i] are slots of X^i
+        let monomial_slots = (0..degree)
+            .map(|i| {
+                let mut v = unit(i);
+                params_bt.plaintext_ntt_op.forward(&mut v);
+                params_bt
+                    .matrix_reps_index_map
+                    .iter()
+                    .map(|index| v[*index])
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // Plaintext polynomial with coefficients m is Σ_j slots_j * slot_polys[j], hence slot i
+        // of CoeffToSlot output is Σ_j slot_polys[j][i] * slots_j. SlotToCoeff is the inverse.
+        let coeff_to_slot = (0..degree)
+            .map(|i| slot_polys.iter().map(|poly| poly[i]).collect_vec())
+            .collect_vec();
+        let slot_to_coeff = (0..degree)
+            .map(|j| monomial_slots.iter().map(|slots| slots[j]).collect_vec())
+            .collect_vec();
+        let coeff_to_slot = LinearTransform::try_new(&coeff_to_slot, &params_bt, 0)?;
+        let slot_to_coeff = LinearTransform::try_new(&slot_to_coeff, &params_bt, 0)?;
+
+        let rtg_indices = coeff_to_slot
+            .rotation_indices()
+            .into_iter()
+            .chain(slot_to_coeff.rotation_indices())
+            .unique()
+            .collect_vec();
+        let ek = EvaluationKey::new(
+            &params_bt,
+            sk,
+            &[0],
+            &vec![0; rtg_indices.len()],
+            &rtg_indices,
+            rng,
+        );
+
+        let digit_poly = lowest_digit_polynomial(p, r, &params_bt.plaintext_modulus_op);
+
+        Ok(BootstrappingKey {
+            evaluator: Evaluator::new(params_bt),
+            sk_ct,
+            ek,
+            coeff_to_slot,
+            slot_to_coeff,
+            digit_poly,
+            p,
+            r,
+        })
+    }
+}
+
+/// Bootstrapping of [Chen-Han](https://eprint.iacr.org/2018/067) with a single digit removal.
+///
+/// Let t = p^r and q' = p^(r+1). Ciphertext is switched to q' such that
+/// c_0 + c_1*s = p*m + v mod q' with |v| < p/2. c_0 + c_1*s is then evaluated homomorphically
+/// under plaintext modulus q' with encryption of s, and CoeffToSlot moves its coefficients to
+/// slots. Since z^(p^r) mod q' only depends on z mod p, lowest digit v + (p-1)/2 of each slot
+/// (shifted by (p-1)/2) is extracted by evaluating interpolation polynomial on z^(p^r) and
+/// subtracted. Ciphertext of p*m under q' is a ciphertext of m under t, which SlotToCoeff moves
+/// back to coefficients.
+impl Evaluator {
+    /// Refreshes noise of ciphertext `ct` at any level. Returned ciphertext is at level 0 in
+    /// `Coefficient` representation and encrypts the same message.
+    ///
+    /// Ciphertext modulus must be large enough for depth of bootstrapping (see `BootstrappingKey`)
+    /// with remaining noise budget. Mod switching noise is (1 + ||s||_1)/2, thus decryption of
+    /// `ct` must not be too close to failure.
+    pub fn bootstrap(&self, ct: &Ciphertext, bk: &BootstrappingKey) -> Ciphertext {
+        self.try_bootstrap(ct, bk).unwrap()
+    }
+
+    pub fn try_bootstrap(
+        &self,
+        ct: &Ciphertext,
+        bk: &BootstrappingKey,
+    ) -> Result<Ciphertext, BfvError> {
+        let evaluator = &bk.evaluator;
+        let params_bt = evaluator.params();
+        if params_bt.ciphertext_moduli != self.params.ciphertext_moduli
+            || params_bt.plaintext_modulus != self.params.plaintext_modulus * bk.p
+        {
+            return Err(BfvError::InvalidParameters(
+                "bootstrapping key was generated for different parameters".to_string(),
+            ));
+        }
+        check_poly_count(2, ct.c.len())?;
+
+        // c_0 + c_1*s = p*m + v mod q'
+        let c = self.try_mod_switch_to_modulus(ct, params_bt.plaintext_modulus)?;
+        let c0 = Plaintext::try_encode(
+            &c[0],
+            params_bt,
+            Encoding::poly(0, PolyCache::AddSub(Representation::Coefficient)),
+        )?;
+        let c1 = Plaintext::try_encode(
+            &c[1],
+            params_bt,
+            Encoding::poly(0, PolyCache::Mul(PolyType::Q)),
+        )?;
+        let mut x = bk.sk_ct.clone();
+        evaluator.ciphertext_change_representation(&mut x, Representation::Evaluation);
+        evaluator.try_mul_plaintext_assign(&mut x, &c1)?;
+        evaluator.ciphertext_change_representation(&mut x, Representation::Coefficient);
+        evaluator.try_add_assign_plaintext(&mut x, &c0)?;
+
+        // z = p*m + v + (p-1)/2 in slots, lowest digit of z is v + (p-1)/2
+        let mut z = evaluator.try_apply_linear_transform(&x, &bk.coeff_to_slot, &bk.ek)?;
+        let shift = Plaintext::try_encode(
+            &[(bk.p - 1) / 2],
+            params_bt,
+            Encoding::poly(0, PolyCache::AddSub(Representation::Coefficient)),
+        )?;
+        evaluator.try_add_assign_plaintext(&mut z, &shift)?;
+
+        let mut w = z.clone();
+        for _ in 0..bk.r {
+            w = evaluator.try_pow(&w, bk.p, &bk.ek)?;
+        }
+        let digit = evaluator.try_evaluate_polynomial(&w, &bk.digit_poly, &bk.ek)?;
+        let pm = evaluator.try_sub(&z, &digit)?;
+
+        #[cfg_attr(not(feature = "noise-estimate"), allow(unused_mut))]
+        let mut res = evaluator.try_apply_linear_transform(&pm, &bk.slot_to_coeff, &bk.ek)?;
+        #[cfg(feature = "noise-estimate")]
+        {
+            // floor(Q*p*m/q') and floor(Q*m/t) differ by at most 1. Digit extraction fails
+            // unless mod switching error v*q'/Q + (1 + ||s||_1)/2 is < p/2.
+            let p = bk.p as f64;
+            let max_noise =
+                self.params.noise_bound(ct.level) + ((p - 1.0 - self.params.hw as f64) / p).log2();
+            res.noise = if ct.noise < max_noise {
+                self.params.noise_add(res.noise, f64::NEG_INFINITY)
+            } else {
+                f64::INFINITY
+            };
+        }
+        Ok(res)
+    }
+
+    /// Computes ct^exponent with square and multiply
+    fn try_pow(
+        &self,
+        ct: &Ciphertext,
+        mut exponent: u64,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        debug_assert!(exponent > 0);
+        let mut base = ct.clone();
+        let mut res: Option<Ciphertext> = None;
+        loop {
+            if exponent & 1 == 1 {
+                res = Some(match res {
+                    Some(res) => self.try_relinearize(&self.try_mul(&res, &base)?, ek)?,
+                    None => base.clone(),
+                });
+            }
+            exponent >>= 1;
+            if exponent == 0 {
+                break;
+            }
+            base = self.try_relinearize(&self.try_mul(&base, &base)?, ek)?;
+        }
+        Ok(res.unwrap())
+    }
+}
+
+/// Returns coefficients of polynomial of degree p - 1 that maps a^(p^r) mod p^(r+1) to a for
+/// a in [0, p). Points a^(p^r) are distinct mod p, hence Lagrange interpolation exists mod
+/// p^(r+1).
+fn lowest_digit_polynomial(p: u64, r: u32, modulus: &Modulus) -> Vec<u64> {
+    // order of multiplicative group mod p^(r+1)
+    let phi = (modulus.modulus() / p) * (p - 1);
+    let points = (0..p)
+        .map(|a| modulus.exp(a, p.pow(r) as usize))
+        .collect_vec();
+
+    // Π_a (x - points[a])
+    let mut prod = vec![1u64];
+    for point in points.iter() {
+        let mut next = vec![0; prod.len() + 1];
+        for (i, c) in prod.iter().enumerate() {
+            next[i + 1] = modulus.add_mod_fast(next[i + 1], *c);
+            next[i] = modulus.sub_mod_fast(next[i], modulus.mul_mod_fast(*c, *point));
+        }
+        prod = next;
+    }
+
+    let mut coeffs = vec![0; p as usize];
+    for (a, point) in points.iter().enumerate() {
+        // prod / (x - points[a]) with synthetic division
+        let mut quotient = vec![0; p as usize];
+        let mut carry = 0;
+        for i in (1..prod.len()).rev() {
+            carry = modulus.add_mod_fast(prod[i], modulus.mul_mod_fast(carry, *point));
+            quotient[i - 1] = carry;
+        }
+
+        // Π_(b != a) (points[a] - points[b])
+        let denominator = quotient.iter().rev().fold(0, |acc, c| {
+            modulus.add_mod_fast(modulus.mul_mod_fast(acc, *point), *c)
+        });
+        let scale = modulus.mul_mod_fast(a as u64, modulus.exp(denominator, (phi - 1) as usize));
+        for (c, q) in coeffs.iter_mut().zip(quotient.iter()) {
+            *c = modulus.add_mod_fast(*c, modulus.mul_mod_fast(*q, scale));
+        }
+    }
+    coeffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn lowest_digit_polynomial_works() {
+        let mut rng = thread_rng();
+        let (p, r) = (97u64, 2);
+        let modulus = Modulus::new(p.pow(r + 1));
+        let coeffs = lowest_digit_polynomial(p, r, &modulus);
+
+        for z in modulus.random_vec(100, &mut rng) {
+            let w = modulus.exp(z, p.pow(r) as usize);
+            let digit = coeffs.iter().rev().fold(0, |acc, c| {
+                modulus.add_mod_fast(modulus.mul_mod_fast(acc, w), *c)
+            });
+            assert_eq!(digit, z % p);
+        }
+    }
+
+    #[test]
+    fn bootstrap_refreshes_noise_budget() {
+        let mut rng = thread_rng();
+        let mut params = BfvParameters::new(&[60; 8], 97, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+        let level = params.max_level - 1;
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0, level], &[], &[], &mut rng);
+        let bk = BootstrappingKey::new(&params, &sk, &mut rng);
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let mut ct = evaluator.encrypt(&sk, &pt, &mut rng);
+        evaluator.mod_down_level(&mut ct, level);
+
+        // use up noise budget by multiplying with ones
+        let ones =
+            evaluator.plaintext_encode(&vec![1; evaluator.params().degree], Encoding::default());
+        let mut ct_ones = evaluator.encrypt(&sk, &ones, &mut rng);
+        evaluator.mod_down_level(&mut ct_ones, level);
+        while evaluator.noise_budget(&sk, &ct) > 30 {
+            ct = evaluator.relinearize(&evaluator.mul(&ct, &ct_ones), &ek);
+        }
+        let budget = evaluator.noise_budget(&sk, &ct);
+        assert!(budget > 0);
+
+        let ct_bt = evaluator.bootstrap(&ct, &bk);
+        assert!(evaluator.noise_budget(&sk, &ct_bt) > budget);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_bt), Encoding::default()),
+            m
+        );
+
+        // estimated noise of refreshed ciphertext is within the bound if estimated noise of
+        // input ciphertext is small enough
+        #[cfg(feature = "noise-estimate")]
+        {
+            let ct_fresh = evaluator.encrypt(&sk, &pt, &mut rng);
+            let ct_fresh_bt = evaluator.bootstrap(&ct_fresh, &bk);
+            assert!(evaluator.check_noise_budget(&ct_fresh_bt).is_ok());
+        }
+
+        // refreshed ciphertext supports further multiplications
+        let ct_ones = evaluator.encrypt(&sk, &ones, &mut rng);
+        let ct_bt = evaluator.relinearize(&evaluator.mul(&ct_bt, &ct_ones), &ek);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_bt), Encoding::default()),
+            m
+        );
+
+        // key of different parameters
+        let other = Evaluator::new(BfvParameters::default(3, 1 << 4));
+        let ct_other = other.encrypt(
+            &sk,
+            &other.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        assert!(other.try_bootstrap(&ct_other, &bk).is_err());
+    }
+
+    #[test]
+    fn bootstrap_prime_power_plaintext_modulus() {
+        let mut rng = thread_rng();
+        let mut params = BfvParameters::new(&[60; 14], 97 * 97, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let bk = BootstrappingKey::new(&params, &sk, &mut rng);
+        assert_eq!((bk.p, bk.r), (97, 2));
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let ct = evaluator.encrypt(&sk, &pt, &mut rng);
+
+        let ct_bt = evaluator.bootstrap(&ct, &bk);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_bt), Encoding::default()),
+            m
+        );
+    }
+
+    #[test]
+    fn bootstrapping_key_rejects_unsupported_parameters() {
+        let mut rng = thread_rng();
+
+        // degree too large for dense CoeffToSlot and SlotToCoeff
+        let mut params = BfvParameters::new(&[60; 3], 12289, 1 << 11);
+        params.enable_hybrid_key_switching(&[60; 3]);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        assert!(matches!(
+            BootstrappingKey::try_new(&params, &sk, &mut rng),
+            Err(BfvError::InvalidParameters(_))
+        ));
+
+        // p <= hw + 1
+        let mut params = BfvParameters::new(&[60; 3], 97, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+        params.change_hamming_weight(96);
+        let sk = SecretKey::random(params.degree, 8, &mut rng);
+        assert!(matches!(
+            BootstrappingKey::try_new(&params, &sk, &mut rng),
+            Err(BfvError::InvalidParameters(_))
+        ));
+
+        // hybrid key switching disabled
+        let params = BfvParameters::new(&[60; 3], 97, 1 << 4);
+        assert!(matches!(
+            BootstrappingKey::try_new(&params, &sk, &mut rng),
+            Err(BfvError::InvalidParameters(_))
+        ));
+    }
+}
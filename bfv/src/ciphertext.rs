@@ -9,6 +9,9 @@ pub struct Ciphertext {
     pub(crate) poly_type: PolyType,
     pub(crate) seed: Option<<ChaCha8Rng as SeedableRng>::Seed>,
     pub(crate) level: usize,
+    /// Estimated noise in bits (see `BfvParameters::noise_bound`)
+    #[cfg(feature = "noise-estimate")]
+    pub(crate) noise: f64,
 }
 
 impl Ciphertext {
@@ -18,6 +21,8 @@ impl Ciphertext {
             poly_type,
             level,
             seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: f64::INFINITY,
         }
     }
 
@@ -27,6 +32,8 @@ impl Ciphertext {
             poly_type: PolyType::Q,
             level: 0,
             seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: f64::INFINITY,
         }
     }
 
@@ -45,6 +52,20 @@ impl Ciphertext {
     pub fn level(&self) -> usize {
         self.level
     }
+
+    /// Returns upper bound on noise (in bits) estimated by operations that produced the
+    /// ciphertext. Noise of ciphertexts created with `new` (or deserialized) is unknown and is
+    /// set to +inf, so that `Evaluator::check_noise_budget` fails for them and for ciphertexts
+    /// computed from them until a bound is set with `set_noise_estimate`.
+    #[cfg(feature = "noise-estimate")]
+    pub fn noise_estimate(&self) -> f64 {
+        self.noise
+    }
+
+    #[cfg(feature = "noise-estimate")]
+    pub fn set_noise_estimate(&mut self, noise: f64) {
+        self.noise = noise;
+    }
 }
 
 mod tests {
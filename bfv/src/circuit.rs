@@ -0,0 +1,595 @@
+use crate::error::BfvError;
+use crate::{
+    BfvParameters, Ciphertext, Encoding, EvaluationKey, Evaluator, PolyCache, PolyType,
+    Representation, SecretKey,
+};
+use itertools::Itertools;
+use rand::{CryptoRng, RngCore};
+use std::collections::BTreeSet;
+
+/// Maximum bits of noise budget that `mod_down_next` may consume when inserted by
+/// `Circuit::compile`. Modulus switching preserves the budget (up to rounding) once noise is much
+/// larger than the dropped modulus.
+const MOD_DOWN_MAX_BUDGET_LOSS: f64 = 1.0;
+
+/// Symbolic ciphertext of a `Circuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitCiphertext(usize);
+
+/// Symbolic plaintext of a `Circuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitPlaintext(usize);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Input,
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    MulPlaintext(usize, usize),
+    AddPlaintext(usize, usize),
+    Rotate(usize, isize),
+}
+
+/// Records operations on symbolic ciphertexts and plaintexts to be executed later against real
+/// ciphertexts.
+///
+/// `compile` schedules relinearizations and modulus switching of the recorded operations and
+/// returns the exact set of keys they require. For ex,
+///
+/// ```
+/// # use bfv::{BfvParameters, Circuit, Encoding, Evaluator, SecretKey};
+/// # let mut rng = rand::thread_rng();
+/// # let params = BfvParameters::default(5, 1 << 6);
+/// # let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+/// # let evaluator = Evaluator::new(params);
+/// # let modt = &evaluator.params().plaintext_modulus_op;
+/// # let m_x = modt.random_vec(evaluator.params().degree, &mut rng);
+/// # let m_y = modt.random_vec(evaluator.params().degree, &mut rng);
+/// # let pt_x = evaluator.plaintext_encode(&m_x, Encoding::default());
+/// # let ct_x = evaluator.encrypt(&sk, &pt_x, &mut rng);
+/// # let pt_y = evaluator.plaintext_encode(&m_y, Encoding::default());
+/// # let ct_y = evaluator.encrypt(&sk, &pt_y, &mut rng);
+/// let mut circuit = Circuit::new();
+/// let (x, y) = (circuit.ciphertext(), circuit.ciphertext());
+/// let xy = circuit.mul(x, y);
+/// let rotated = circuit.rotate(xy, 1);
+/// let sum = circuit.add(xy, rotated);
+/// circuit.output(sum);
+///
+/// let compiled = circuit.compile(evaluator.params());
+/// let ek = compiled.evaluation_key(evaluator.params(), &sk, &mut rng);
+/// let outputs = compiled.execute(&evaluator, &ek, &[ct_x, ct_y], &[]);
+/// # let mut xy = m_x.clone();
+/// # modt.mul_mod_fast_vec(&mut xy, &m_y);
+/// # let row_size = evaluator.params().degree / 2;
+/// # let mut sum = xy.clone();
+/// # sum.chunks_mut(row_size).for_each(|row| row.rotate_left(1));
+/// # modt.add_mod_fast_vec(&mut sum, &xy);
+/// # let pt = evaluator.decrypt(&sk, &outputs[0]);
+/// # assert_eq!(evaluator.plaintext_decode(&pt, Encoding::default()), sum);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Circuit {
+    nodes: Vec<Node>,
+    ciphertext_inputs: Vec<usize>,
+    plaintext_inputs: usize,
+    outputs: Vec<usize>,
+}
+
+impl Circuit {
+    pub fn new() -> Circuit {
+        Circuit::default()
+    }
+
+    /// Adds ciphertext input. Inputs are expected at level 0 in the order they are added.
+    pub fn ciphertext(&mut self) -> CircuitCiphertext {
+        let ct = self.push(Node::Input);
+        self.ciphertext_inputs.push(ct.0);
+        ct
+    }
+
+    /// Adds plaintext input. Plaintext inputs are messages that are SIMD encoded at the level
+    /// they are used at.
+    pub fn plaintext(&mut self) -> CircuitPlaintext {
+        self.plaintext_inputs += 1;
+        CircuitPlaintext(self.plaintext_inputs - 1)
+    }
+
+    pub fn add(&mut self, lhs: CircuitCiphertext, rhs: CircuitCiphertext) -> CircuitCiphertext {
+        self.push(Node::Add(lhs.0, rhs.0))
+    }
+
+    pub fn sub(&mut self, lhs: CircuitCiphertext, rhs: CircuitCiphertext) -> CircuitCiphertext {
+        self.push(Node::Sub(lhs.0, rhs.0))
+    }
+
+    pub fn mul(&mut self, lhs: CircuitCiphertext, rhs: CircuitCiphertext) -> CircuitCiphertext {
+        self.push(Node::Mul(lhs.0, rhs.0))
+    }
+
+    pub fn mul_plaintext(
+        &mut self,
+        ct: CircuitCiphertext,
+        pt: CircuitPlaintext,
+    ) -> CircuitCiphertext {
+        self.push(Node::MulPlaintext(ct.0, pt.0))
+    }
+
+    pub fn add_plaintext(
+        &mut self,
+        ct: CircuitCiphertext,
+        pt: CircuitPlaintext,
+    ) -> CircuitCiphertext {
+        self.push(Node::AddPlaintext(ct.0, pt.0))
+    }
+
+    /// Rotates ciphertext by `rotate_by` (same as `Evaluator::rotate`)
+    pub fn rotate(&mut self, ct: CircuitCiphertext, rotate_by: isize) -> CircuitCiphertext {
+        self.push(Node::Rotate(ct.0, rotate_by))
+    }
+
+    /// Marks ciphertext as output. Outputs are returned by `CompiledCircuit::execute` in the
+    /// order they are marked.
+    pub fn output(&mut self, ct: CircuitCiphertext) {
+        self.outputs.push(ct.0);
+    }
+
+    /// Schedules execution of the circuit with `params`.
+    ///
+    /// Products are relinearized lazily, ie only once they are used by an operation that
+    /// requires a ciphertext with 2 polynomials (for ex, rotation), thus a sum of products is
+    /// relinearized once. Before multiplications, rotations and outputs, ciphertexts are switched
+    /// to lower levels with `mod_down_next` as long as it does not consume noise budget
+    /// (estimated with `BfvParameters::noise_*` methods assuming ciphertext inputs are fresh
+    /// public key encryptions). Ciphertexts that are key switched later on are kept at levels that
+    /// support key switching.
+    ///
+    /// Panics if hybrid key switching is not enabled.
+    pub fn compile(&self, params: &BfvParameters) -> CompiledCircuit {
+        let mut compiler = Compiler {
+            params,
+            key_switched: self.key_switched(),
+            values: Vec::with_capacity(self.nodes.len()),
+            steps: vec![],
+            rlk_levels: BTreeSet::new(),
+            rotations: BTreeSet::new(),
+        };
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let value = match *node {
+                Node::Input => Value {
+                    level: 0,
+                    relinearized: true,
+                    noise: params.noise_fresh_pk(),
+                },
+                Node::Add(lhs, rhs) | Node::Sub(lhs, rhs) => {
+                    compiler.align(lhs, rhs);
+                    let (lhs, rhs) = (&compiler.values[lhs], &compiler.values[rhs]);
+                    Value {
+                        level: lhs.level,
+                        relinearized: lhs.relinearized,
+                        noise: params.noise_add(lhs.noise, rhs.noise),
+                    }
+                }
+                Node::Mul(lhs, rhs) => {
+                    for operand in [lhs, rhs] {
+                        compiler.relinearize(operand);
+                        compiler.mod_down(operand);
+                    }
+                    compiler.align(lhs, rhs);
+                    let (lhs, rhs) = (&compiler.values[lhs], &compiler.values[rhs]);
+                    Value {
+                        level: lhs.level,
+                        relinearized: false,
+                        noise: params.noise_mul(lhs.noise, rhs.noise, lhs.level),
+                    }
+                }
+                Node::MulPlaintext(ct, _) => {
+                    let ct = &compiler.values[ct];
+                    Value {
+                        noise: params.noise_mul_plaintext(ct.noise),
+                        ..*ct
+                    }
+                }
+                Node::AddPlaintext(ct, _) => {
+                    compiler.relinearize(ct);
+                    let ct = &compiler.values[ct];
+                    Value {
+                        noise: params.noise_add(ct.noise, f64::NEG_INFINITY),
+                        ..*ct
+                    }
+                }
+                Node::Rotate(ct, rotate_by) => {
+                    compiler.relinearize(ct);
+                    compiler.mod_down(ct);
+                    let ct = &compiler.values[ct];
+                    compiler.rotations.insert((rotate_by, ct.level));
+                    Value {
+                        noise: params.noise_key_switch(ct.noise, ct.level),
+                        ..*ct
+                    }
+                }
+            };
+            compiler.values.push(value);
+            compiler.steps.push(Step::Evaluate(index));
+        }
+
+        for output in self.outputs.iter() {
+            compiler.relinearize(*output);
+            compiler.mod_down(*output);
+        }
+
+        CompiledCircuit {
+            nodes: self.nodes.clone(),
+            ciphertext_inputs: self.ciphertext_inputs.clone(),
+            plaintext_inputs: self.plaintext_inputs,
+            outputs: self.outputs.clone(),
+            steps: compiler.steps,
+            rlk_levels: compiler.rlk_levels.into_iter().collect(),
+            rotations: compiler.rotations.into_iter().collect(),
+        }
+    }
+
+    fn push(&mut self, node: Node) -> CircuitCiphertext {
+        self.nodes.push(node);
+        CircuitCiphertext(self.nodes.len() - 1)
+    }
+
+    /// Returns whether value of each node is key switched by itself or by any node that depends
+    /// on it. Products are always relinearized.
+    fn key_switched(&self) -> Vec<bool> {
+        let mut key_switched = self
+            .nodes
+            .iter()
+            .map(|node| matches!(node, Node::Mul(..) | Node::Rotate(..)))
+            .collect_vec();
+        // nodes are in topological order
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            if key_switched[index] {
+                match *node {
+                    Node::Input => {}
+                    Node::Add(lhs, rhs) | Node::Sub(lhs, rhs) | Node::Mul(lhs, rhs) => {
+                        key_switched[lhs] = true;
+                        key_switched[rhs] = true;
+                    }
+                    Node::MulPlaintext(ct, _) | Node::AddPlaintext(ct, _) | Node::Rotate(ct, _) => {
+                        key_switched[ct] = true
+                    }
+                }
+            }
+        }
+        key_switched
+    }
+}
+
+/// Estimated state of a node's ciphertext during execution
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    level: usize,
+    /// Whether ciphertext has 2 polynomials
+    relinearized: bool,
+    noise: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Computes ciphertext of the node from its operands
+    Evaluate(usize),
+    /// Relinearizes ciphertext of the node
+    Relinearize(usize),
+    /// Switches ciphertext of the node down to the level
+    ModDown(usize, usize),
+}
+
+struct Compiler<'a> {
+    params: &'a BfvParameters,
+    key_switched: Vec<bool>,
+    values: Vec<Value>,
+    steps: Vec<Step>,
+    rlk_levels: BTreeSet<usize>,
+    rotations: BTreeSet<(isize, usize)>,
+}
+
+impl Compiler<'_> {
+    fn relinearize(&mut self, index: usize) {
+        let value = &mut self.values[index];
+        if !value.relinearized {
+            value.relinearized = true;
+            value.noise = self.params.noise_key_switch(value.noise, value.level);
+            self.rlk_levels.insert(value.level);
+            self.steps.push(Step::Relinearize(index));
+        }
+    }
+
+    /// Switches relinearized ciphertext to lower levels while it costs at most
+    /// `MOD_DOWN_MAX_BUDGET_LOSS` bits of noise budget
+    fn mod_down(&mut self, index: usize) {
+        let max_level = if self.key_switched[index] {
+            self.params.max_level - 1
+        } else {
+            self.params.max_level
+        };
+
+        let mut value = self.values[index];
+        while value.level < max_level {
+            let noise = self.params.noise_mod_down(value.noise, value.level);
+            let loss = (self.params.noise_bound(value.level) - value.noise)
+                - (self.params.noise_bound(value.level + 1) - noise);
+            if loss > MOD_DOWN_MAX_BUDGET_LOSS {
+                break;
+            }
+            value.level += 1;
+            value.noise = noise;
+        }
+        self.switch_level(index, value);
+    }
+
+    /// Switches operands of binary operation to the same level
+    fn align(&mut self, lhs: usize, rhs: usize) {
+        let level = self.values[lhs].level.max(self.values[rhs].level);
+        for operand in [lhs, rhs] {
+            let mut value = self.values[operand];
+            if value.level != level {
+                // only ciphertexts with 2 polynomials are switched
+                self.relinearize(operand);
+                value = self.values[operand];
+                for l in value.level..level {
+                    value.noise = self.params.noise_mod_down(value.noise, l);
+                }
+                value.level = level;
+                self.switch_level(operand, value);
+            }
+        }
+
+        // ciphertexts with different no. of polynomials cannot be added
+        if self.values[lhs].relinearized != self.values[rhs].relinearized {
+            self.relinearize(lhs);
+            self.relinearize(rhs);
+        }
+    }
+
+    fn switch_level(&mut self, index: usize, value: Value) {
+        if value.level != self.values[index].level {
+            self.values[index] = value;
+            self.steps.push(Step::ModDown(index, value.level));
+        }
+    }
+}
+
+/// Circuit scheduled by `Circuit::compile`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledCircuit {
+    nodes: Vec<Node>,
+    ciphertext_inputs: Vec<usize>,
+    plaintext_inputs: usize,
+    outputs: Vec<usize>,
+    steps: Vec<Step>,
+    rlk_levels: Vec<usize>,
+    rotations: Vec<(isize, usize)>,
+}
+
+impl CompiledCircuit {
+    /// Levels of relinearization keys required by the circuit
+    pub fn rlk_levels(&self) -> &[usize] {
+        &self.rlk_levels
+    }
+
+    /// `(rotate_by, level)` of galois keys required by the circuit
+    pub fn rotations(&self) -> &[(isize, usize)] {
+        &self.rotations
+    }
+
+    /// Generates evaluation key with exactly the keys required by the circuit
+    pub fn evaluation_key<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> EvaluationKey {
+        let (rtg_indices, rtg_levels): (Vec<isize>, Vec<usize>) =
+            self.rotations.iter().copied().unzip();
+        EvaluationKey::new(params, sk, &self.rlk_levels, &rtg_levels, &rtg_indices, rng)
+    }
+
+    /// Executes the circuit on ciphertext inputs `cts` (at level 0) and plaintext inputs `pts`
+    /// (messages of SIMD encoding). Returns output ciphertexts.
+    pub fn execute(
+        &self,
+        evaluator: &Evaluator,
+        ek: &EvaluationKey,
+        cts: &[Ciphertext],
+        pts: &[Vec<u64>],
+    ) -> Vec<Ciphertext> {
+        self.try_execute(evaluator, ek, cts, pts).unwrap()
+    }
+
+    pub fn try_execute(
+        &self,
+        evaluator: &Evaluator,
+        ek: &EvaluationKey,
+        cts: &[Ciphertext],
+        pts: &[Vec<u64>],
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        if cts.len() != self.ciphertext_inputs.len() || pts.len() != self.plaintext_inputs {
+            return Err(BfvError::CircuitInputsMismatch {
+                expected: (self.ciphertext_inputs.len(), self.plaintext_inputs),
+                found: (cts.len(), pts.len()),
+            });
+        }
+
+        let mut values: Vec<Option<Ciphertext>> = vec![None; self.nodes.len()];
+        let mut inputs = cts.iter();
+        for step in self.steps.iter() {
+            match *step {
+                Step::Evaluate(index) => {
+                    let mut ct = self.evaluate(evaluator, ek, index, &values, &mut inputs, pts)?;
+                    // ciphertexts are kept in coefficient representation
+                    evaluator
+                        .ciphertext_change_representation(&mut ct, Representation::Coefficient);
+                    values[index] = Some(ct);
+                }
+                Step::Relinearize(index) => {
+                    let ct = evaluator.try_relinearize(value(&values, index), ek)?;
+                    values[index] = Some(ct);
+                }
+                Step::ModDown(index, level) => {
+                    let ct = values[index].as_mut().expect("value is evaluated");
+                    evaluator.try_mod_down_level(ct, level)?;
+                }
+            }
+        }
+
+        Ok(self
+            .outputs
+            .iter()
+            .map(|index| value(&values, *index).clone())
+            .collect())
+    }
+
+    fn evaluate<'a>(
+        &self,
+        evaluator: &Evaluator,
+        ek: &EvaluationKey,
+        index: usize,
+        values: &[Option<Ciphertext>],
+        inputs: &mut impl Iterator<Item = &'a Ciphertext>,
+        pts: &[Vec<u64>],
+    ) -> Result<Ciphertext, BfvError> {
+        match self.nodes[index] {
+            Node::Input => {
+                let ct = inputs.next().expect("ciphertext inputs are checked");
+                if ct.level() != 0 {
+                    return Err(BfvError::LevelMismatch {
+                        expected: 0,
+                        found: ct.level(),
+                    });
+                }
+                Ok(ct.clone())
+            }
+            Node::Add(lhs, rhs) => evaluator.try_add(value(values, lhs), value(values, rhs)),
+            Node::Sub(lhs, rhs) => evaluator.try_sub(value(values, lhs), value(values, rhs)),
+            Node::Mul(lhs, rhs) => evaluator.try_mul(value(values, lhs), value(values, rhs)),
+            Node::MulPlaintext(ct, pt) => {
+                let mut ct = value(values, ct).clone();
+                let pt = evaluator.try_plaintext_encode(
+                    &pts[pt],
+                    Encoding::simd(ct.level(), PolyCache::Mul(PolyType::Q)),
+                )?;
+                evaluator.ciphertext_change_representation(&mut ct, Representation::Evaluation);
+                evaluator.try_mul_plaintext(&ct, &pt)
+            }
+            Node::AddPlaintext(ct, pt) => {
+                let ct = value(values, ct);
+                let pt = evaluator.try_plaintext_encode(
+                    &pts[pt],
+                    Encoding::simd(ct.level(), PolyCache::AddSub(Representation::Coefficient)),
+                )?;
+                evaluator.try_add_plaintext(ct, &pt)
+            }
+            Node::Rotate(ct, rotate_by) => evaluator.try_rotate(value(values, ct), rotate_by, ek),
+        }
+    }
+}
+
+fn value(values: &[Option<Ciphertext>], index: usize) -> &Ciphertext {
+    values[index]
+        .as_ref()
+        .expect("operands are evaluated first")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn circuit_matches_evaluator() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+
+        let mut circuit = Circuit::new();
+        let x = circuit.ciphertext();
+        let y = circuit.ciphertext();
+        let p = circuit.plaintext();
+        // sum of products is relinearized once
+        let xx = circuit.mul(x, x);
+        let xy = circuit.mul(x, y);
+        let s = circuit.add(xx, xy);
+        let r = circuit.rotate(s, 1);
+        let m = circuit.mul(r, s);
+        let o = circuit.mul_plaintext(m, p);
+        let o = circuit.add_plaintext(o, p);
+        let w = circuit.mul(o, x);
+        let w = circuit.sub(w, x);
+        circuit.output(o);
+        circuit.output(w);
+
+        let compiled = circuit.compile(&params);
+        let relinearizations = compiled
+            .steps
+            .iter()
+            .filter(|step| matches!(step, Step::Relinearize(_)))
+            .count();
+        assert_eq!(relinearizations, 3);
+        // noise of `o` is large enough to drop a modulus for free before the last
+        // multiplication
+        assert!(compiled.steps.contains(&Step::ModDown(o.0, 1)));
+        assert_eq!(compiled.rlk_levels(), &[0, 1]);
+        assert_eq!(compiled.rotations(), &[(1, 0)]);
+
+        let ek = compiled.evaluation_key(&params, &sk, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let params = evaluator.params();
+        let modt = &params.plaintext_modulus_op;
+
+        let mx = modt.random_vec(params.degree, &mut rng);
+        let my = modt.random_vec(params.degree, &mut rng);
+        let mp = modt.random_vec(params.degree, &mut rng);
+        let encrypt = |m: &[u64], rng: &mut _| {
+            evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(m, Encoding::default()),
+                rng,
+            )
+        };
+        let cts = [encrypt(&mx, &mut rng), encrypt(&my, &mut rng)];
+        let outputs = compiled.execute(&evaluator, &ek, &cts, std::slice::from_ref(&mp));
+
+        // same circuit with evaluator
+        let rotate = |v: &[u64]| {
+            let half = v.len() / 2;
+            let mut rotated = v.to_vec();
+            rotated[..half].rotate_left(1);
+            rotated[half..].rotate_left(1);
+            rotated
+        };
+        let mul = |a: &[u64], b: &[u64]| {
+            let mut a = a.to_vec();
+            modt.mul_mod_fast_vec(&mut a, b);
+            a
+        };
+        let mut s = mul(&mx, &mx);
+        modt.add_mod_fast_vec(&mut s, &mul(&mx, &my));
+        let mut o = mul(&mul(&rotate(&s), &s), &mp);
+        modt.add_mod_fast_vec(&mut o, &mp);
+        let mut w = mul(&o, &mx);
+        modt.sub_mod_fast_vec(&mut w, &mx);
+
+        for (ct, expected) in outputs.iter().zip([o, w]) {
+            assert_eq!(ct.c_ref().len(), 2);
+            assert!(evaluator.noise_budget(&sk, ct) > 0);
+            assert_eq!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default()),
+                expected
+            );
+        }
+        assert!(outputs.iter().all(|ct| ct.level() == 1));
+
+        assert!(matches!(
+            compiled.try_execute(&evaluator, &ek, &cts[..1], &[mp]),
+            Err(BfvError::CircuitInputsMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{PolyType, Representation};
+use std::fmt::{Display, Formatter};
+
+/// Errors returned by fallible (ie `try_*`) variants of operations.
+///
+/// Infallible variants panic on the same errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BfvError {
+    /// Operands are at different levels
+    LevelMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// Level is outside of range [min, max] supported by the operation
+    InvalidLevel {
+        level: usize,
+        min: usize,
+        max: usize,
+    },
+    PolyTypeMismatch {
+        expected: PolyType,
+        found: PolyType,
+    },
+    RepresentationMismatch {
+        expected: Representation,
+        found: Representation,
+    },
+    /// Ciphertext has unexpected no. of polynomials
+    InvalidPolyCount {
+        expected: usize,
+        found: usize,
+    },
+    /// Polynomial coefficients do not have shape (no. of moduli, degree) expected at level
+    InvalidPolyShape {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    RelinearizationKeyMissing {
+        level: usize,
+    },
+    GaloisKeyMissing {
+        rotate_by: isize,
+        level: usize,
+    },
+    /// Galois key for automorphism X -> X^exponent is missing
+    SubstitutionKeyMissing {
+        exponent: usize,
+        level: usize,
+    },
+    EncodingMissing,
+    /// Only plaintexts obtained by decryption can be decoded
+    EncodedPlaintext,
+    MulPolyMissing,
+    AddSubPolyMissing,
+    /// Message has more values than polynomial degree
+    InvalidMessageLength {
+        max: usize,
+        found: usize,
+    },
+    /// Signed message value is outside of range (-t/2, t/2]
+    ValueOutOfRange {
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    Deserialization(String),
+    /// Bits in QP exceed the maximum allowed for security level
+    InsecureParameters {
+        log_qp: usize,
+        max_log_qp: Option<usize>,
+    },
+    InvalidParameters(String),
+    /// No shares provided for aggregation
+    SharesMissing,
+    /// Batch operands have different lengths
+    BatchLengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// LWE ciphertext and key have different dimensions
+    LweDimensionMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// Circuit received unexpected no. of (ciphertext, plaintext) inputs
+    CircuitInputsMismatch {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    /// Estimated noise (in bits) of ciphertext exceeds the bound for correct decryption
+    NoiseBudgetExhausted {
+        level: usize,
+        noise: f64,
+        bound: f64,
+    },
+}
+
+impl Display for BfvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BfvError::LevelMismatch { expected, found } => {
+                write!(f, "Level mismatch: expected {expected}, found {found}")
+            }
+            BfvError::InvalidLevel { level, min, max } => {
+                write!(f, "Level {level} not in range [{min}, {max}]")
+            }
+            BfvError::PolyTypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "PolyType mismatch: expected {expected:?}, found {found:?}"
+                )
+            }
+            BfvError::RepresentationMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Representation mismatch: expected {expected:?}, found {found:?}"
+                )
+            }
+            BfvError::InvalidPolyCount { expected, found } => {
+                write!(
+                    f,
+                    "Invalid no. of ciphertext polynomials: expected {expected}, found {found}"
+                )
+            }
+            BfvError::InvalidPolyShape { expected, found } => {
+                write!(
+                    f,
+                    "Invalid shape of polynomial coefficients: expected {expected:?}, found {found:?}"
+                )
+            }
+            BfvError::RelinearizationKeyMissing { level } => {
+                write!(f, "Rlk missing at level {level}")
+            }
+            BfvError::GaloisKeyMissing { rotate_by, level } => {
+                write!(f, "Rtg missing for rotation {rotate_by} at level {level}")
+            }
+            BfvError::SubstitutionKeyMissing { exponent, level } => {
+                write!(
+                    f,
+                    "Galois key missing for galois element {exponent} at level {level}"
+                )
+            }
+            BfvError::EncodingMissing => write!(f, "Plaintext encoding missing"),
+            BfvError::EncodedPlaintext => {
+                write!(f, "Plaintext must be output of decryption to decode")
+            }
+            BfvError::MulPolyMissing => write!(f, "Plaintext mul poly missing"),
+            BfvError::AddSubPolyMissing => write!(f, "Plaintext add_sub poly missing"),
+            BfvError::InvalidMessageLength { max, found } => {
+                write!(f, "Message length {found} exceeds {max}")
+            }
+            BfvError::ValueOutOfRange { value, min, max } => {
+                write!(f, "Message value {value} not in range [{min}, {max}]")
+            }
+            BfvError::Deserialization(reason) => write!(f, "Deserialization failed: {reason}"),
+            BfvError::InsecureParameters { log_qp, max_log_qp } => match max_log_qp {
+                Some(max_log_qp) => write!(
+                    f,
+                    "Insecure parameters: log(QP) {log_qp} exceeds {max_log_qp}"
+                ),
+                None => write!(
+                    f,
+                    "Insecure parameters: no secure log(QP) bound for polynomial degree"
+                ),
+            },
+            BfvError::InvalidParameters(reason) => write!(f, "Invalid parameters: {reason}"),
+            BfvError::SharesMissing => write!(f, "No shares to aggregate"),
+            BfvError::BatchLengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Batch length mismatch: expected {expected}, found {found}"
+                )
+            }
+            BfvError::LweDimensionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "LWE dimension mismatch: expected {expected}, found {found}"
+                )
+            }
+            BfvError::CircuitInputsMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Circuit inputs mismatch: expected {} ciphertexts and {} plaintexts, found {} and {}",
+                    expected.0, expected.1, found.0, found.1
+                )
+            }
+            BfvError::NoiseBudgetExhausted {
+                level,
+                noise,
+                bound,
+            } => {
+                write!(
+                    f,
+                    "Noise budget exhausted: estimated noise of {noise:.1} bits exceeds {bound:.1} bits at level {level}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BfvError {}
+
+pub(crate) fn check_level(expected: usize, found: usize) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::LevelMismatch { expected, found });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_level_range(level: usize, min: usize, max: usize) -> Result<(), BfvError> {
+    if level < min || level > max {
+        return Err(BfvError::InvalidLevel { level, min, max });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_poly_type(expected: &PolyType, found: &PolyType) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::PolyTypeMismatch {
+            expected: expected.clone(),
+            found: found.clone(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_representation(
+    expected: &Representation,
+    found: &Representation,
+) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::RepresentationMismatch {
+            expected: expected.clone(),
+            found: found.clone(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_poly_count(expected: usize, found: usize) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::InvalidPolyCount { expected, found });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_poly_shape(
+    expected: (usize, usize),
+    found: (usize, usize),
+) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::InvalidPolyShape { expected, found });
+    }
+    Ok(())
+}
@@ -7,6 +7,8 @@ use std::collections::HashMap;
 pub struct EvaluationKey {
     pub(crate) rlks: HashMap<usize, RelinearizationKey>,
     pub(crate) rtgs: HashMap<(isize, usize), GaloisKey>,
+    /// Galois keys for automorphisms X -> X^exponent keyed by (exponent, level)
+    pub(crate) substitution_keys: HashMap<(usize, usize), GaloisKey>,
 }
 
 impl EvaluationKey {
@@ -40,7 +42,167 @@ impl EvaluationKey {
             );
         });
 
-        EvaluationKey { rlks, rtgs }
+        EvaluationKey {
+            rlks,
+            rtgs,
+            substitution_keys: HashMap::new(),
+        }
+    }
+
+    /// Creates evaluation key from relinearization keys and galois keys paired with their
+    /// rotation index (for ex, keys generated collectively with `multiparty` protocols)
+    pub fn from_keys(
+        rlks: Vec<RelinearizationKey>,
+        rtgs: Vec<(isize, GaloisKey)>,
+    ) -> EvaluationKey {
+        EvaluationKey {
+            rlks: rlks.into_iter().map(|rlk| (rlk.level, rlk)).collect(),
+            rtgs: rtgs
+                .into_iter()
+                .map(|(index, rtg)| ((index, rtg.level), rtg))
+                .collect(),
+            substitution_keys: HashMap::new(),
+        }
+    }
+
+    /// Generates relinearization keys for `rlk_levels` and galois keys for rotations by
+    /// powers of two (both left and right) and row swap for each of `rtg_levels`.
+    ///
+    /// `Evaluator::rotate` composes rotation by any amount from these keys.
+    pub fn new_with_power_of_two_rotations<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        rtg_levels: &[usize],
+        rng: &mut R,
+    ) -> EvaluationKey {
+        let indices = EvaluationKey::power_of_two_rotation_indices(params.degree);
+        EvaluationKey::new_with_rotations_at_levels(
+            params, sk, rlk_levels, rtg_levels, &indices, rng,
+        )
+    }
+
+    /// Generates relinearization keys for `rlk_levels` and exactly the galois keys required by
+    /// `Evaluator::inner_sum` and `Evaluator::row_sum` for each of `rtg_levels`.
+    pub fn new_for_inner_sum<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        rtg_levels: &[usize],
+        rng: &mut R,
+    ) -> EvaluationKey {
+        let indices = EvaluationKey::inner_sum_rotation_indices(params.degree);
+        EvaluationKey::new_with_rotations_at_levels(
+            params, sk, rlk_levels, rtg_levels, &indices, rng,
+        )
+    }
+
+    /// Generates relinearization keys for `rlk_levels` and galois keys for automorphisms
+    /// X -> X^exponent for each pair of `levels` and `galois_elements`.
+    ///
+    /// Panics if a galois element is even or not smaller than 2N
+    pub fn new_with_galois_elements<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        levels: &[usize],
+        galois_elements: &[usize],
+        rng: &mut R,
+    ) -> EvaluationKey {
+        assert!(levels.len() == galois_elements.len());
+
+        let mut ek = EvaluationKey::new(params, sk, rlk_levels, &[], &[], rng);
+        izip!(galois_elements.iter(), levels.iter()).for_each(|(exponent, level)| {
+            assert!(exponent & 1 == 1 && *exponent < 2 * params.degree);
+            ek.substitution_keys.insert(
+                (*exponent, *level),
+                GaloisKey::new(*exponent, params, *level, sk, rng),
+            );
+        });
+        ek
+    }
+
+    /// Generates relinearization keys for `rlk_levels` and galois keys for automorphisms
+    /// X -> X^(2^k + 1), 1 <= k <= log(N), required by `Evaluator::pack_lwes` and
+    /// `Evaluator::expand_query` for each of `levels`.
+    pub fn new_for_ring_packing<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        levels: &[usize],
+        rng: &mut R,
+    ) -> EvaluationKey {
+        let exponents = EvaluationKey::ring_packing_galois_elements(params.degree);
+        let mut key_levels = vec![];
+        let mut key_exponents = vec![];
+        levels.iter().for_each(|l| {
+            key_levels.extend(std::iter::repeat_n(*l, exponents.len()));
+            key_exponents.extend_from_slice(&exponents);
+        });
+
+        EvaluationKey::new_with_galois_elements(
+            params,
+            sk,
+            rlk_levels,
+            &key_levels,
+            &key_exponents,
+            rng,
+        )
+    }
+
+    /// Returns galois elements 2^k + 1 for 1 <= k <= log(N)
+    pub fn ring_packing_galois_elements(degree: usize) -> Vec<usize> {
+        (1..=degree.ilog2()).map(|k| (1 << k) + 1).collect_vec()
+    }
+
+    /// Returns galois elements N/2^j + 1 for 0 <= j < `log_count` required by
+    /// `Evaluator::expand_query` to expand a query into 2^`log_count` ciphertexts
+    pub fn query_expansion_galois_elements(degree: usize, log_count: usize) -> Vec<usize> {
+        (0..log_count).map(|j| (degree >> j) + 1).collect_vec()
+    }
+
+    /// Generates galois keys for all `rtg_indices` at each of `rtg_levels`
+    fn new_with_rotations_at_levels<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        rtg_levels: &[usize],
+        rtg_indices: &[isize],
+        rng: &mut R,
+    ) -> EvaluationKey {
+        let mut levels = vec![];
+        let mut indices = vec![];
+        rtg_levels.iter().for_each(|l| {
+            levels.extend(std::iter::repeat_n(*l, rtg_indices.len()));
+            indices.extend_from_slice(rtg_indices);
+        });
+
+        EvaluationKey::new(params, sk, rlk_levels, &levels, &indices, rng)
+    }
+
+    /// Returns rotation indices ±2^k for 2^k < N/2 and row swap index 2N-1
+    pub fn power_of_two_rotation_indices(degree: usize) -> Vec<isize> {
+        let mut indices = vec![];
+        let mut k = 1;
+        while k < degree / 2 {
+            indices.push(k as isize);
+            indices.push(-(k as isize));
+            k <<= 1;
+        }
+        indices.push((2 * degree - 1) as isize);
+        indices
+    }
+
+    /// Returns rotation indices 1, 2, 4, ..., N/4 and row swap index 2N-1
+    pub fn inner_sum_rotation_indices(degree: usize) -> Vec<isize> {
+        let mut indices = vec![];
+        let mut k = 1;
+        while k < degree / 2 {
+            indices.push(k as isize);
+            k <<= 1;
+        }
+        indices.push((2 * degree - 1) as isize);
+        indices
     }
 
     pub fn get_rtg_ref(&self, rot_by: isize, level: usize) -> &GaloisKey {
@@ -1,11 +1,24 @@
+use crate::error::{
+    check_level, check_level_range, check_poly_count, check_poly_shape, check_poly_type,
+    check_representation, BfvError,
+};
 use crate::relinearization_key::RelinearizationKey;
+use crate::{
+    naf, Encoding, GaloisKey, HybridKeySwitchingKey, LinearTransform, LweCiphertext, Plaintext,
+    PolyCache, PublicKey, SecretKey, Workspace,
+};
 use crate::{BfvParameters, Ciphertext, EvaluationKey, PolyType};
-use crate::{Encoding, GaloisKey, Plaintext, SecretKey};
 use crate::{Poly, Representation};
 use itertools::{izip, Itertools};
 use num_bigint::{BigUint, RandBigInt};
+use num_traits::ToPrimitive;
 use rand::{thread_rng, CryptoRng, Rng, RngCore};
 
+/// Returns largest power of two smaller than `value` (> 1)
+fn prev_power_of_two(value: usize) -> usize {
+    1 << (usize::BITS - 1 - (value - 1).leading_zeros())
+}
+
 pub struct Evaluator {
     pub(crate) params: BfvParameters,
 }
@@ -27,37 +40,103 @@ impl Evaluator {
     }
 
     pub fn mul(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
-        let mut res = self.mul_lazy(lhs, rhs);
-        self.scale_and_round(&mut res)
+        self.try_mul(lhs, rhs).unwrap()
+    }
+
+    pub fn try_mul(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Result<Ciphertext, BfvError> {
+        let mut res = self.try_mul_lazy(lhs, rhs)?;
+        self.try_scale_and_round(&mut res)
+    }
+
+    /// Same as `mul` but writes the product into `out` and takes temporary polynomials from `ws`.
+    /// Reusing `out` and `ws` across multiplications avoids heap allocations.
+    pub fn mul_into(
+        &self,
+        lhs: &Ciphertext,
+        rhs: &Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) {
+        self.try_mul_into(lhs, rhs, out, ws).unwrap()
+    }
+
+    pub fn try_mul_into(
+        &self,
+        lhs: &Ciphertext,
+        rhs: &Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        let mut res = Ciphertext::new(ws.take_polys(), PolyType::PQ, lhs.level);
+        let outcome = self
+            .try_mul_lazy_into(lhs, rhs, &mut res, ws)
+            .and_then(|_| self.try_scale_and_round_into(&mut res, out, ws));
+        ws.give_polys(res.c);
+        outcome
     }
 
     pub fn mul_lazy(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
-        assert!(lhs.c.len() == 2);
-        assert!(rhs.c.len() == 2);
-        #[cfg(debug_assertions)]
+        self.try_mul_lazy(lhs, rhs).unwrap()
+    }
+
+    pub fn try_mul_lazy(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Result<Ciphertext, BfvError> {
+        let mut out = Ciphertext::placeholder();
+        self.try_mul_lazy_into(lhs, rhs, &mut out, &mut Workspace::new())?;
+        Ok(out)
+    }
+
+    /// Same as `mul_lazy` but writes the product into `out` and takes temporary polynomials from
+    /// `ws`
+    pub fn mul_lazy_into(
+        &self,
+        lhs: &Ciphertext,
+        rhs: &Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) {
+        self.try_mul_lazy_into(lhs, rhs, out, ws).unwrap()
+    }
+
+    pub fn try_mul_lazy_into(
+        &self,
+        lhs: &Ciphertext,
+        rhs: &Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        check_poly_count(2, lhs.c.len())?;
+        check_poly_count(2, rhs.c.len())?;
+        self.check_ciphertext(lhs, &PolyType::Q)?;
+        self.check_ciphertext(rhs, &PolyType::Q)?;
+        check_level(lhs.level, rhs.level)?;
+
+        // We save 2 ntts if polynomial passed to `fast_expand_crt_basis_p_over_q` is in coefficient form. Hence
+        // it is cheaper to pass ciphertexts in coefficient form. If one ciphertext is in coefficient and another
+        // in evaluation form, ciphertext in evaluation form is used as `lhs`. This way ciphertext in coefficient
+        // form is passed to `fast_expand_crt_basis_p_over_q` giving us same saving as if both ciphertexts were
+        // in coefficient form.
+        let (lhs, rhs) = if lhs.c[0].representation == Representation::Coefficient
+            && rhs.c[0].representation != Representation::Coefficient
         {
-            // We save 2 ntts if polynomial passed to `fast_expand_crt_basis_p_over_q` is in coefficient form. Hence
-            // it is cheaper to pass ciphertexts in coefficient form. But if you are stuck with two ciphertext one in coefficient
-            // and another in evaluation, pass the one in evaluation form as `self`. This way ciphertext in coefficient
-            // form is passed to `fast_expand_crt_basis_p_over_q`  giving us same saving as if both ciphertexts were
-            // in coefficient form.
-            if (lhs.c[0].representation != rhs.c[0].representation)
-                && (rhs.c[0].representation != Representation::Coefficient)
-            {
-                panic!("Different representation in multiply1 only allows when self is in `Evalaution`")
-            }
-        }
-        assert!(lhs.level == rhs.level);
-        assert!(lhs.poly_type == rhs.poly_type);
-        assert!(lhs.poly_type == PolyType::Q);
+            (rhs, lhs)
+        } else {
+            (lhs, rhs)
+        };
 
         let level = lhs.level;
         let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
         let p_ctx = self.params.poly_ctx(&PolyType::P, level);
         let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
 
+        ws.fit_polys(&mut out.c, 3, &pq_ctx);
+        let [c_r0, c00, c01] = &mut out.c[..] else {
+            unreachable!()
+        };
+        let mut c10 = ws.take_poly(&pq_ctx, Representation::Unknown);
+        let mut c11 = ws.take_poly(&pq_ctx, Representation::Unknown);
+
         // let mut now = std::time::Instant::now();
-        let mut c00 = q_ctx.expand_crt_basis(
+        q_ctx.expand_crt_basis_into(
             &lhs.c[0],
             &pq_ctx,
             &p_ctx,
@@ -66,8 +145,10 @@ impl Evaluator {
             &self.params.ql_hat_inv_modql_shoup[level],
             &self.params.ql_inv[level],
             &self.params.alphal_modpl[level],
+            c00,
+            ws,
         );
-        let mut c01 = q_ctx.expand_crt_basis(
+        q_ctx.expand_crt_basis_into(
             &lhs.c[1],
             &pq_ctx,
             &p_ctx,
@@ -76,16 +157,18 @@ impl Evaluator {
             &self.params.ql_hat_inv_modql_shoup[level],
             &self.params.ql_inv[level],
             &self.params.alphal_modpl[level],
+            c01,
+            ws,
         );
         // println!("Extend1 {:?}", now.elapsed());
         if c00.representation != Representation::Evaluation {
-            pq_ctx.change_representation(&mut c00, Representation::Evaluation);
-            pq_ctx.change_representation(&mut c01, Representation::Evaluation);
+            pq_ctx.change_representation(c00, Representation::Evaluation);
+            pq_ctx.change_representation(c01, Representation::Evaluation);
         }
         // println!("Extend1 (In Evaluation) {:?}", now.elapsed());
 
         // now = std::time::Instant::now();
-        let mut c10 = q_ctx.fast_expand_crt_basis_p_over_q(
+        q_ctx.fast_expand_crt_basis_p_over_q_into(
             &rhs.c[0],
             &p_ctx,
             &pq_ctx,
@@ -98,8 +181,10 @@ impl Evaluator {
             &self.params.pl_hat_inv_modpl_shoup[level],
             &self.params.pl_inv[level],
             &self.params.alphal_modql[level],
+            &mut c10,
+            ws,
         );
-        let mut c11 = q_ctx.fast_expand_crt_basis_p_over_q(
+        q_ctx.fast_expand_crt_basis_p_over_q_into(
             &rhs.c[1],
             &p_ctx,
             &pq_ctx,
@@ -112,6 +197,8 @@ impl Evaluator {
             &self.params.pl_hat_inv_modpl_shoup[level],
             &self.params.pl_inv[level],
             &self.params.alphal_modql[level],
+            &mut c11,
+            ws,
         );
         // println!("Extend2 {:?}", now.elapsed());
         pq_ctx.change_representation(&mut c10, Representation::Evaluation);
@@ -121,119 +208,590 @@ impl Evaluator {
         // now = std::time::Instant::now();
         // tensor
         // c00 * c10
-        let c_r0 = pq_ctx.mul(&c00, &c10);
+        pq_ctx.mul_into(c00, &c10, c_r0);
 
         // c00 * c11 + c01 * c10
-        pq_ctx.mul_assign(&mut c00, &c11);
-        pq_ctx.mul_assign(&mut c10, &c01);
-        pq_ctx.add_assign(&mut c00, &c10);
+        pq_ctx.mul_assign(c00, &c11);
+        pq_ctx.mul_assign(&mut c10, c01);
+        pq_ctx.add_assign(c00, &c10);
 
         // c01 * c11
-        pq_ctx.mul_assign(&mut c01, &c11);
+        pq_ctx.mul_assign(c01, &c11);
         // println!("Tensor {:?}", now.elapsed());
 
-        Ciphertext {
-            c: vec![c_r0, c00, c01],
-            poly_type: PolyType::PQ,
-            level: level,
-            seed: None,
+        ws.give_poly(c10);
+        ws.give_poly(c11);
+
+        out.poly_type = PolyType::PQ;
+        out.level = level;
+        out.seed = None;
+        // noise of the product after `scale_and_round`
+        #[cfg(feature = "noise-estimate")]
+        {
+            out.noise = self.params.noise_mul(lhs.noise, rhs.noise, level);
         }
+        Ok(())
     }
 
     pub fn scale_and_round(&self, c0: &mut Ciphertext) -> Ciphertext {
-        // debug_assert!(c0.c[0].representation == Representation::E)
-        assert!(c0.poly_type == PolyType::PQ);
+        self.try_scale_and_round(c0).unwrap()
+    }
+
+    pub fn try_scale_and_round(&self, c0: &mut Ciphertext) -> Result<Ciphertext, BfvError> {
+        let mut out = Ciphertext::placeholder();
+        self.try_scale_and_round_into(c0, &mut out, &mut Workspace::new())?;
+        Ok(out)
+    }
+
+    /// Same as `scale_and_round` but writes the result into `out`. `ws` is only used to resize
+    /// `out`.
+    pub fn scale_and_round_into(
+        &self,
+        c0: &mut Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) {
+        self.try_scale_and_round_into(c0, out, ws).unwrap()
+    }
+
+    pub fn try_scale_and_round_into(
+        &self,
+        c0: &mut Ciphertext,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        self.check_ciphertext(c0, &PolyType::PQ)?;
         let level = c0.level;
         let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
         let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
         let p_ctx = self.params.poly_ctx(&PolyType::P, level);
 
-        let c =
-            c0.c.iter_mut()
-                .map(|pq_poly| {
-                    pq_ctx.change_representation(pq_poly, Representation::Coefficient);
-                    pq_ctx.scale_and_round(
-                        pq_poly,
-                        &q_ctx,
-                        &p_ctx,
-                        &q_ctx,
-                        &self.params.tql_pl_hat_inv_modpl_divpl_modql[level],
-                        &self.params.tql_pl_hat_inv_modpl_divpl_frachi[level],
-                        &self.params.tql_pl_hat_inv_modpl_divpl_fraclo[level],
-                    )
-                })
-                .collect_vec();
+        ws.fit_polys(&mut out.c, c0.c.len(), &q_ctx);
+        izip!(c0.c.iter_mut(), out.c.iter_mut()).for_each(|(pq_poly, q_poly)| {
+            pq_ctx.change_representation(pq_poly, Representation::Coefficient);
+            pq_ctx.scale_and_round_into(
+                pq_poly,
+                &q_ctx,
+                &p_ctx,
+                &q_ctx,
+                &self.params.tql_pl_hat_inv_modpl_divpl_modql[level],
+                &self.params.tql_pl_hat_inv_modpl_divpl_frachi[level],
+                &self.params.tql_pl_hat_inv_modpl_divpl_fraclo[level],
+                q_poly,
+            );
+        });
 
-        Ciphertext {
-            c,
-            poly_type: PolyType::Q,
-            level,
-            seed: None,
+        out.poly_type = PolyType::Q;
+        out.level = level;
+        out.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            out.noise = c0.noise;
         }
+        Ok(())
     }
 
     pub fn relinearize(&self, c0: &Ciphertext, ek: &EvaluationKey) -> Ciphertext {
+        self.try_relinearize(c0, ek).unwrap()
+    }
+
+    pub fn try_relinearize(
+        &self,
+        c0: &Ciphertext,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        ek.rlks
+            .get(&c0.level)
+            .ok_or(BfvError::RelinearizationKeyMissing { level: c0.level })?
+            .try_relinearize(&c0, &self.params)
+    }
+
+    /// Same as `relinearize` but writes the result into `out` and takes temporary polynomials
+    /// from `ws`. Reusing `out` and `ws` across relinearizations avoids heap allocations.
+    pub fn relinearize_into(
+        &self,
+        c0: &Ciphertext,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) {
+        self.try_relinearize_into(c0, ek, out, ws).unwrap()
+    }
+
+    pub fn try_relinearize_into(
+        &self,
+        c0: &Ciphertext,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
         ek.rlks
             .get(&c0.level)
-            .expect("Rlk missing!")
-            .relinearize(&c0, &self.params)
+            .ok_or(BfvError::RelinearizationKeyMissing { level: c0.level })?
+            .try_relinearize_into(c0, &self.params, out, ws)
     }
 
     pub fn rotate(&self, c0: &Ciphertext, rotate_by: isize, ek: &EvaluationKey) -> Ciphertext {
-        ek.rtgs
-            .get(&(rotate_by, c0.level))
-            .expect(&format!("Rtg missing! :{rotate_by} {}", c0.level))
-            .rotate(&c0, &self.params)
+        self.try_rotate(c0, rotate_by, ek).unwrap()
+    }
+
+    /// Rotates ciphertext by `rotate_by` using galois key for `rotate_by` if present. Otherwise
+    /// rotation is composed from rotations by powers of two (in NAF) for which keys are
+    /// present in `ek` (see `EvaluationKey::new_with_power_of_two_rotations`).
+    pub fn try_rotate(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: isize,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        let mut out = Ciphertext::placeholder();
+        self.try_rotate_into(c0, rotate_by, ek, &mut out, &mut Workspace::new())?;
+        Ok(out)
+    }
+
+    /// Same as `rotate` but writes the result into `out` and takes temporary polynomials from
+    /// `ws`. Reusing `out` and `ws` across rotations avoids heap allocations, except for
+    /// decomposition of `rotate_by` if `ek` has no galois key for it.
+    pub fn rotate_into(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: isize,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) {
+        self.try_rotate_into(c0, rotate_by, ek, out, ws).unwrap()
+    }
+
+    pub fn try_rotate_into(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: isize,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        if let Some(rtg) = ek.rtgs.get(&(rotate_by, c0.level)) {
+            return rtg.try_rotate_into(c0, &self.params, out, ws);
+        }
+
+        // row swap cannot be composed
+        if rotate_by == (2 * self.params.degree - 1) as isize {
+            return Err(BfvError::GaloisKeyMissing {
+                rotate_by,
+                level: c0.level,
+            });
+        }
+
+        let rtgs = self
+            .power_of_two_rotations(rotate_by)
+            .into_iter()
+            .map(|r| {
+                ek.rtgs
+                    .get(&(r, c0.level))
+                    .ok_or(BfvError::GaloisKeyMissing {
+                        rotate_by: r,
+                        level: c0.level,
+                    })
+            })
+            .collect::<Result<Vec<&GaloisKey>, BfvError>>()?;
+
+        let Some((first, rest)) = rtgs.split_first() else {
+            *out = c0.clone();
+            return Ok(());
+        };
+        first.try_rotate_into(c0, &self.params, out, ws)?;
+        let mut tmp = Ciphertext::new(ws.take_polys(), PolyType::Q, c0.level);
+        let outcome = rest.iter().try_for_each(|rtg| {
+            std::mem::swap(out, &mut tmp);
+            rtg.try_rotate_into(&tmp, &self.params, out, ws)
+        });
+        ws.give_polys(tmp.c);
+        outcome
+    }
+
+    /// Decomposes rotation by `rotate_by` into rotations by ±2^k using NAF of `rotate_by`
+    /// modulo row size N/2
+    fn power_of_two_rotations(&self, rotate_by: isize) -> Vec<isize> {
+        let row_size = self.params.degree / 2;
+        let rotate_by = rotate_by.rem_euclid(row_size as isize) as usize;
+
+        naf(rotate_by)
+            .iter()
+            .enumerate()
+            // rotation by N/2 is identity
+            .filter(|(k, d)| **d != 0 && (1 << k) != row_size)
+            .map(|(k, d)| *d as isize * (1 << k))
+            .collect_vec()
+    }
+
+    /// Rotates ciphertext by each of `rotate_by` amounts. `c1` is decomposed and switched to
+    /// QP only once and reused by all rotations.
+    pub fn rotate_hoisted(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: &[isize],
+        ek: &EvaluationKey,
+    ) -> Vec<Ciphertext> {
+        self.try_rotate_hoisted(c0, rotate_by, ek).unwrap()
+    }
+
+    pub fn try_rotate_hoisted(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: &[isize],
+        ek: &EvaluationKey,
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        check_poly_count(2, c0.c.len())?;
+
+        let rtgs = rotate_by
+            .iter()
+            .map(|r| {
+                ek.rtgs
+                    .get(&(*r, c0.level))
+                    .ok_or(BfvError::GaloisKeyMissing {
+                        rotate_by: *r,
+                        level: c0.level,
+                    })
+            })
+            .collect::<Result<Vec<&GaloisKey>, BfvError>>()?;
+
+        if rtgs.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let qp_ctx = self.params.poly_ctx(&PolyType::QP, c0.level);
+        let mut c1 = c0.c[1].clone();
+        if c1.representation == Representation::Evaluation {
+            let q_ctx = self.params.poly_ctx(&PolyType::Q, c0.level);
+            q_ctx.change_representation(&mut c1, Representation::Coefficient);
+        }
+        let c1_digits = HybridKeySwitchingKey::decompose(
+            self.params.hybrid_key_switching_params_at_level(c0.level),
+            &c1,
+            &qp_ctx,
+        );
+
+        rtgs.iter()
+            .map(|rtg| rtg.try_rotate_decomposed(c0, &c1_digits, &self.params))
+            .collect()
+    }
+
+    /// Multiplies plaintext matrix of `lt` with SIMD encoded ciphertext using baby-step
+    /// giant-step diagonal method. Baby step rotations are hoisted.
+    ///
+    /// Requires galois keys for `lt.rotation_indices()` at ciphertext's level. Returned
+    /// ciphertext is in `Coefficient` representation.
+    pub fn apply_linear_transform(
+        &self,
+        c0: &Ciphertext,
+        lt: &LinearTransform,
+        ek: &EvaluationKey,
+    ) -> Ciphertext {
+        self.try_apply_linear_transform(c0, lt, ek).unwrap()
+    }
+
+    pub fn try_apply_linear_transform(
+        &self,
+        c0: &Ciphertext,
+        lt: &LinearTransform,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        check_poly_count(2, c0.c.len())?;
+        check_level(lt.level, c0.level)?;
+
+        // baby steps rot_j(swap^b(x)) in `Evaluation` representation
+        let mut baby_steps = vec![];
+        for b in 0..2 {
+            let steps = lt.baby_step_indices(b);
+            if steps.is_empty() {
+                baby_steps.push(vec![]);
+                continue;
+            }
+
+            let x = if b == 0 {
+                c0.clone()
+            } else {
+                self.try_rotate(c0, (2 * self.params.degree - 1) as isize, ek)?
+            };
+            let rotate_by = steps
+                .iter()
+                .filter(|j| **j != 0)
+                .map(|j| *j as isize)
+                .collect_vec();
+            let mut rotated = self.try_rotate_hoisted(&x, &rotate_by, ek)?.into_iter();
+
+            let mut x_steps = vec![None; lt.baby_steps];
+            for j in steps {
+                let mut ct = if j == 0 {
+                    x.clone()
+                } else {
+                    rotated.next().unwrap()
+                };
+                if ct.c[0].representation != Representation::Evaluation {
+                    self.ciphertext_change_representation(&mut ct, Representation::Evaluation);
+                }
+                x_steps[j] = Some(ct);
+            }
+            baby_steps.push(x_steps);
+        }
+
+        // Σ_g rot_(g*k)(Σ_(b,j) d'_(b, g*k + j) ⊙ rot_j(swap^b(x)))
+        let mut res: Option<Ciphertext> = None;
+        for giant in lt.giant_step_indices() {
+            let mut inner: Option<Ciphertext> = None;
+            for (diagonals, x_steps) in izip!(lt.diagonals.iter(), baby_steps.iter()) {
+                for (d, x) in izip!(diagonals[giant..giant + lt.baby_steps].iter(), x_steps) {
+                    if let (Some(d), Some(x)) = (d, x) {
+                        let prod = self.try_mul_plaintext(x, d)?;
+                        match inner.as_mut() {
+                            Some(inner) => self.try_add_assign(inner, &prod)?,
+                            None => inner = Some(prod),
+                        }
+                    }
+                }
+            }
+
+            let mut inner = inner.unwrap();
+            self.ciphertext_change_representation(&mut inner, Representation::Coefficient);
+            if giant != 0 {
+                inner = self.try_rotate(&inner, giant as isize, ek)?;
+            }
+            match res.as_mut() {
+                Some(res) => self.try_add_assign(res, &inner)?,
+                None => res = Some(inner),
+            }
+        }
+
+        Ok(res.unwrap_or_else(|| {
+            // zero matrix
+            let ctx = self.params.poly_ctx(&PolyType::Q, c0.level);
+            Ciphertext {
+                c: vec![
+                    ctx.zero(Representation::Coefficient),
+                    ctx.zero(Representation::Coefficient),
+                ],
+                poly_type: PolyType::Q,
+                level: c0.level,
+                seed: None,
+                #[cfg(feature = "noise-estimate")]
+                noise: f64::NEG_INFINITY,
+            }
+        }))
+    }
+
+    /// Sums all slots of SIMD encoded ciphertext. The sum is replicated in all slots.
+    ///
+    /// Requires galois keys for rotations by 1, 2, 4, ..., N/4 and row swap at ciphertext's level
+    /// (see `EvaluationKey::new_for_inner_sum`).
+    pub fn inner_sum(&self, c0: &Ciphertext, ek: &EvaluationKey) -> Ciphertext {
+        self.try_inner_sum(c0, ek).unwrap()
+    }
+
+    pub fn try_inner_sum(
+        &self,
+        c0: &Ciphertext,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        let mut ct = self.try_row_sum(c0, ek)?;
+        let swapped = self.try_rotate(&ct, (2 * self.params.degree - 1) as isize, ek)?;
+        self.try_add_assign(&mut ct, &swapped)?;
+        Ok(ct)
+    }
+
+    /// Sums slots in each of the two rows of SIMD encoded ciphertext. Sum of a row is replicated
+    /// in all slots of the row.
+    ///
+    /// Requires galois keys for rotations by 1, 2, 4, ..., N/4 at ciphertext's level.
+    pub fn row_sum(&self, c0: &Ciphertext, ek: &EvaluationKey) -> Ciphertext {
+        self.try_row_sum(c0, ek).unwrap()
+    }
+
+    pub fn try_row_sum(&self, c0: &Ciphertext, ek: &EvaluationKey) -> Result<Ciphertext, BfvError> {
+        let mut ct = c0.clone();
+        let mut k = 1;
+        while k < self.params.degree / 2 {
+            let rotated = self.try_rotate(&ct, k as isize, ek)?;
+            self.try_add_assign(&mut ct, &rotated)?;
+            k <<= 1;
+        }
+        Ok(ct)
+    }
+
+    /// Evaluates polynomial f(x) = coeffs\[0\] + coeffs\[1\]x + ... + coeffs\[d\]x^d over Z_t on
+    /// ciphertext using Paterson–Stockmeyer.
+    ///
+    /// f is split into m chunks of k coefficients, f(x) = Σ q_i(x) * x^(ki), where k is the power of
+    /// two closest to sqrt(d+1) from above. Baby step powers x, ..., x^k and giant step powers
+    /// x^(2k), ..., x^((m-1)k) are computed with relinearization. Chunks q_i(x) only require scalar
+    /// multiplications and products q_i(x) * x^(ki) are accumulated with `mul_lazy` so that their
+    /// sum is scaled and relinearized once. Consumes ceil(log2(k)) + ceil(log2(m-1)) + 1
+    /// multiplicative levels.
+    ///
+    /// Requires relinearization key at ciphertext's level. Returned ciphertext is in `Coefficient`
+    /// representation.
+    pub fn evaluate_polynomial(
+        &self,
+        ct: &Ciphertext,
+        coeffs: &[u64],
+        ek: &EvaluationKey,
+    ) -> Ciphertext {
+        self.try_evaluate_polynomial(ct, coeffs, ek).unwrap()
+    }
+
+    pub fn try_evaluate_polynomial(
+        &self,
+        ct: &Ciphertext,
+        coeffs: &[u64],
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        self.check_ciphertext(ct, &PolyType::Q)?;
+        check_poly_count(2, ct.c.len())?;
+
+        let t = self.params.plaintext_modulus;
+        let mut coeffs = coeffs.iter().map(|c| c % t).collect_vec();
+        while coeffs.last() == Some(&0) {
+            coeffs.pop();
+        }
+        if coeffs.is_empty() {
+            coeffs.push(0);
+        }
+
+        let mut x = ct.clone();
+        if x.c[0].representation != Representation::Coefficient {
+            self.ciphertext_change_representation(&mut x, Representation::Coefficient);
+        }
+
+        let degree = coeffs.len() - 1;
+        let k = (((degree + 1) as f64).sqrt().ceil() as usize).next_power_of_two();
+        let m = (degree + k) / k;
+
+        // baby step powers; powers[i] = x^(i+1)
+        let baby_steps = if m > 1 { k } else { degree };
+        let mut powers = vec![x];
+        for i in 2..=baby_steps {
+            let h = prev_power_of_two(i);
+            let p = self.try_relinearize(&self.try_mul(&powers[h - 1], &powers[i - h - 1])?, ek)?;
+            powers.push(p);
+        }
+
+        // giant step powers; giants[j] = x^(k(j+1))
+        let mut giants = vec![];
+        if m > 1 {
+            giants.push(powers[k - 1].clone());
+        }
+        for j in 2..m {
+            let h = prev_power_of_two(j);
+            let p = self.try_relinearize(&self.try_mul(&giants[h - 1], &giants[j - h - 1])?, ek)?;
+            giants.push(p);
+        }
+
+        let chunks = coeffs.chunks(k).collect_vec();
+
+        // Accumulate q_i(x) * x^(ki) in PQ
+        let mut acc: Option<Ciphertext> = None;
+        for (chunk, giant) in izip!(chunks.iter().skip(1), giants.iter()) {
+            if chunk.iter().all(|c| *c == 0) {
+                continue;
+            }
+            let q = self.evaluate_baby_step(chunk, &powers, ct.level)?;
+            let prod = self.try_mul_lazy(&q, giant)?;
+            match acc.as_mut() {
+                Some(acc) => self.try_add_assign(acc, &prod)?,
+                None => acc = Some(prod),
+            }
+        }
+
+        let mut res = self.evaluate_baby_step(chunks[0], &powers, ct.level)?;
+        if let Some(mut acc) = acc {
+            let acc = self.try_relinearize(&self.try_scale_and_round(&mut acc)?, ek)?;
+            self.try_add_assign(&mut res, &acc)?;
+        }
+        Ok(res)
     }
 
     pub fn add_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) {
-        // TODO: perform checks
+        self.try_add_assign(c0, c1).unwrap()
+    }
+
+    pub fn try_add_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) -> Result<(), BfvError> {
+        self.check_binary_operands(c0, c1)?;
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         izip!(c0.c.iter_mut(), c1.c.iter()).for_each(|(p0, p1)| {
             ctx.add_assign(p0, p1);
         });
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self.params.noise_add(c0.noise, c1.noise);
+        }
+        Ok(())
     }
 
     pub fn add(&self, c0: &Ciphertext, c1: &Ciphertext) -> Ciphertext {
+        self.try_add(c0, c1).unwrap()
+    }
+
+    pub fn try_add(&self, c0: &Ciphertext, c1: &Ciphertext) -> Result<Ciphertext, BfvError> {
+        self.check_binary_operands(c0, c1)?;
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         let c = izip!(c0.c.iter(), c1.c.iter())
             .map(|(p0, p1)| ctx.add(p0, p1))
             .collect_vec();
 
-        Ciphertext {
+        Ok(Ciphertext {
             c,
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: self.params.noise_add(c0.noise, c1.noise),
+        })
     }
 
     pub fn sub_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) {
-        // TODO: perform checks
+        self.try_sub_assign(c0, c1).unwrap()
+    }
+
+    pub fn try_sub_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) -> Result<(), BfvError> {
+        self.check_binary_operands(c0, c1)?;
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         izip!(c0.c.iter_mut(), c1.c.iter()).for_each(|(p0, p1)| {
             ctx.sub_assign(p0, p1);
         });
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self.params.noise_add(c0.noise, c1.noise);
+        }
+        Ok(())
     }
 
     pub fn sub(&self, c0: &Ciphertext, c1: &Ciphertext) -> Ciphertext {
+        self.try_sub(c0, c1).unwrap()
+    }
+
+    pub fn try_sub(&self, c0: &Ciphertext, c1: &Ciphertext) -> Result<Ciphertext, BfvError> {
+        self.check_binary_operands(c0, c1)?;
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         let c = izip!(c0.c.iter(), c1.c.iter())
             .map(|(p0, p1)| ctx.sub(p0, p1))
             .collect_vec();
 
-        Ciphertext {
+        Ok(Ciphertext {
             c,
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: self.params.noise_add(c0.noise, c1.noise),
+        })
     }
 
     pub fn negate_assign(&self, c0: &mut Ciphertext) {
@@ -251,10 +809,15 @@ impl Evaluator {
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: c0.noise,
         }
     }
 
     /// c0 += c1 * poly
+    ///
+    /// Noise estimate assumes that coefficients of `poly` are in [0, t) (as of plaintext mul
+    /// poly). Same applies to `mul_poly_assign` and `mul_poly`.
     pub fn fma_poly(&self, c0: &mut Ciphertext, c1: &Ciphertext, poly: &Poly) {
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
         izip!(c0.c.iter_mut(), c1.c.iter()).for_each(|(p0, p1)| {
@@ -262,6 +825,12 @@ impl Evaluator {
         });
 
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self
+                .params
+                .noise_add(c0.noise, self.params.noise_mul_plaintext(c1.noise));
+        }
     }
 
     pub fn mul_poly_assign(&self, c0: &mut Ciphertext, poly: &Poly) {
@@ -269,6 +838,10 @@ impl Evaluator {
         c0.c.iter_mut().for_each(|p0| ctx.mul_assign(p0, poly));
 
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self.params.noise_mul_plaintext(c0.noise);
+        }
     }
 
     pub fn mul_poly(&self, c0: &Ciphertext, poly: &Poly) -> Ciphertext {
@@ -279,75 +852,155 @@ impl Evaluator {
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: self.params.noise_mul_plaintext(c0.noise),
         }
     }
 
+    /// Multiplies ciphertext with plaintext encoded with `PolyCache::Mul` or `PolyCache::All`.
+    ///
+    /// Ciphertext must be in `Evaluation` representation.
     pub fn mul_plaintext_assign(&self, ct: &mut Ciphertext, pt: &Plaintext) {
-        assert!(ct.level() == pt.level());
-        assert!(ct.poly_type() == pt.mul_poly_type());
+        self.try_mul_plaintext_assign(ct, pt).unwrap()
+    }
 
-        self.mul_poly_assign(ct, pt.mul_poly_ref());
+    pub fn try_mul_plaintext_assign(
+        &self,
+        ct: &mut Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<(), BfvError> {
+        self.check_ciphertext(ct, &pt.try_mul_poly_type()?)?;
+        check_level(pt.try_level()?, ct.level())?;
+        // mul poly is in `Evaluation` representation
+        ct.c.iter().try_for_each(|p| {
+            check_representation(&Representation::Evaluation, &p.representation)
+        })?;
+
+        self.mul_poly_assign(ct, pt.try_mul_poly_ref()?);
+        Ok(())
     }
 
     pub fn mul_plaintext(&self, ct: &Ciphertext, pt: &Plaintext) -> Ciphertext {
-        assert!(ct.level() == pt.level());
-        assert!(ct.poly_type() == pt.mul_poly_type());
+        self.try_mul_plaintext(ct, pt).unwrap()
+    }
 
-        self.mul_poly(ct, pt.mul_poly_ref())
+    pub fn try_mul_plaintext(
+        &self,
+        ct: &Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<Ciphertext, BfvError> {
+        self.check_ciphertext(ct, &pt.try_mul_poly_type()?)?;
+        check_level(pt.try_level()?, ct.level())?;
+        // mul poly is in `Evaluation` representation
+        ct.c.iter().try_for_each(|p| {
+            check_representation(&Representation::Evaluation, &p.representation)
+        })?;
+
+        Ok(self.mul_poly(ct, pt.try_mul_poly_ref()?))
     }
 
     pub fn add_assign_plaintext(&self, ct: &mut Ciphertext, pt: &Plaintext) {
-        assert!(ct.level() == pt.encoding.as_ref().unwrap().level);
-        assert!(ct.poly_type() == PolyType::Q);
+        self.try_add_assign_plaintext(ct, pt).unwrap()
+    }
+
+    pub fn try_add_assign_plaintext(
+        &self,
+        ct: &mut Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<(), BfvError> {
+        self.check_ciphertext(ct, &PolyType::Q)?;
+        check_level(pt.try_level()?, ct.level())?;
+        let add_sub_poly = pt.try_add_sub_poly_ref()?;
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        ctx.add_assign(&mut ct.c_ref_mut()[0], pt.add_sub_poly_ref());
+        ctx.add_assign(&mut ct.c_ref_mut()[0], add_sub_poly);
+        #[cfg(feature = "noise-estimate")]
+        {
+            ct.noise = self.params.noise_add(ct.noise, f64::NEG_INFINITY);
+        }
+        Ok(())
     }
 
     pub fn add_plaintext(&self, ct: &Ciphertext, pt: &Plaintext) -> Ciphertext {
-        assert!(ct.level() == pt.encoding.as_ref().unwrap().level);
-        assert!(ct.poly_type() == PolyType::Q);
-        assert!(ct.c.len() == 2);
+        self.try_add_plaintext(ct, pt).unwrap()
+    }
+
+    pub fn try_add_plaintext(
+        &self,
+        ct: &Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<Ciphertext, BfvError> {
+        self.check_ciphertext(ct, &PolyType::Q)?;
+        check_poly_count(2, ct.c.len())?;
+        check_level(pt.try_level()?, ct.level())?;
+        let add_sub_poly = pt.try_add_sub_poly_ref()?;
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        let c0 = ctx.add(&ct.c_ref()[0], pt.add_sub_poly_ref());
+        let c0 = ctx.add(&ct.c_ref()[0], add_sub_poly);
 
         let c = vec![c0, ct.c_ref()[1].clone()];
 
-        Ciphertext {
+        Ok(Ciphertext {
             c,
             // since c1 does not changes seed remains valid
             seed: ct.seed.clone(),
             poly_type: ct.poly_type.clone(),
             level: ct.level,
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: self.params.noise_add(ct.noise, f64::NEG_INFINITY),
+        })
     }
 
     pub fn sub_assign_plaintext(&self, ct: &mut Ciphertext, pt: &Plaintext) {
-        assert!(ct.level() == pt.encoding.as_ref().unwrap().level);
-        assert!(ct.poly_type() == PolyType::Q);
+        self.try_sub_assign_plaintext(ct, pt).unwrap()
+    }
+
+    pub fn try_sub_assign_plaintext(
+        &self,
+        ct: &mut Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<(), BfvError> {
+        self.check_ciphertext(ct, &PolyType::Q)?;
+        check_level(pt.try_level()?, ct.level())?;
+        let add_sub_poly = pt.try_add_sub_poly_ref()?;
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        ctx.sub_assign(&mut ct.c_ref_mut()[0], pt.add_sub_poly_ref());
+        ctx.sub_assign(&mut ct.c_ref_mut()[0], add_sub_poly);
+        #[cfg(feature = "noise-estimate")]
+        {
+            ct.noise = self.params.noise_add(ct.noise, f64::NEG_INFINITY);
+        }
+        Ok(())
     }
 
     pub fn sub_plaintext(&self, ct: &Ciphertext, pt: &Plaintext) -> Ciphertext {
-        assert!(ct.level() == pt.encoding.as_ref().unwrap().level);
-        assert!(ct.poly_type() == PolyType::Q);
-        assert!(ct.c.len() == 2);
+        self.try_sub_plaintext(ct, pt).unwrap()
+    }
+
+    pub fn try_sub_plaintext(
+        &self,
+        ct: &Ciphertext,
+        pt: &Plaintext,
+    ) -> Result<Ciphertext, BfvError> {
+        self.check_ciphertext(ct, &PolyType::Q)?;
+        check_poly_count(2, ct.c.len())?;
+        check_level(pt.try_level()?, ct.level())?;
+        let add_sub_poly = pt.try_add_sub_poly_ref()?;
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        let c0 = ctx.sub(&ct.c_ref()[0], pt.add_sub_poly_ref());
+        let c0 = ctx.sub(&ct.c_ref()[0], add_sub_poly);
 
         let c = vec![c0, ct.c_ref()[1].clone()];
 
-        Ciphertext {
+        Ok(Ciphertext {
             c,
             // since c1 does not changes seed remains valid
             seed: ct.seed.clone(),
             poly_type: ct.poly_type.clone(),
             level: ct.level,
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: self.params.noise_add(ct.noise, f64::NEG_INFINITY),
+        })
     }
 
     /// c0 = poly - c0
@@ -357,50 +1010,599 @@ impl Evaluator {
         ctx.neg_assign(&mut c0.c[1]);
 
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self.params.noise_add(c0.noise, f64::NEG_INFINITY);
+        }
+    }
+
+    pub fn mod_down_next(&self, c0: &mut Ciphertext) {
+        self.try_mod_down_next(c0).unwrap()
+    }
+
+    pub fn try_mod_down_next(&self, c0: &mut Ciphertext) -> Result<(), BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        // Cannot drop the last modulus
+        check_level_range(c0.level, 0, self.params.max_level - 1)?;
+
+        let level = c0.level;
+        let ctx = self.params.poly_ctx(&c0.poly_type, level);
+        c0.c.iter_mut().for_each(|p| {
+            ctx.mod_down_next(p, &self.params.lastq_inv_modql[level]);
+        });
+        c0.level = level + 1;
+
+        c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            c0.noise = self.params.noise_mod_down(c0.noise, level);
+        }
+        Ok(())
+    }
+
+    pub fn mod_down_level(&self, c0: &mut Ciphertext, level: usize) {
+        self.try_mod_down_level(c0, level).unwrap()
+    }
+
+    pub fn try_mod_down_level(&self, c0: &mut Ciphertext, level: usize) -> Result<(), BfvError> {
+        check_level_range(level, 0, self.params.max_level)?;
+        let start_level = c0.level;
+        for _ in start_level..level {
+            self.try_mod_down_next(c0)?;
+        }
+        Ok(())
+    }
+
+    /// Switches ciphertext from Q (at ciphertext's level) to a single (not necessarily prime)
+    /// `modulus` q', ie computes round(q'/Q * c_i) for each ciphertext polynomial c_i.
+    ///
+    /// Returns coefficients of switched polynomials. Switched ciphertext decrypts as
+    /// round(t/q' * [c_0 + c_1*s]_q'). Mod switching adds noise bounded by (1 + ||s||_1)/2,
+    /// thus q' must be sufficiently larger than t.
+    pub fn mod_switch_to_modulus(&self, c0: &Ciphertext, modulus: u64) -> Vec<Vec<u64>> {
+        self.try_mod_switch_to_modulus(c0, modulus).unwrap()
+    }
+
+    pub fn try_mod_switch_to_modulus(
+        &self,
+        c0: &Ciphertext,
+        modulus: u64,
+    ) -> Result<Vec<Vec<u64>>, BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        let ctx = self.params.poly_ctx(&PolyType::Q, c0.level);
+        let big_q = ctx.big_q();
+        if modulus < 2 || BigUint::from(modulus) >= big_q {
+            return Err(BfvError::InvalidParameters(format!(
+                "modulus {modulus} must be in range [2, Q)"
+            )));
+        }
+
+        let half_q = &big_q >> 1;
+        Ok(c0
+            .c
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.representation != Representation::Coefficient {
+                    ctx.change_representation(&mut p, Representation::Coefficient);
+                }
+                ctx.try_convert_to_biguint(&p)
+                    .iter()
+                    .map(|v| {
+                        let v: BigUint = (v * modulus + &half_q) / &big_q;
+                        (v % modulus).to_u64().unwrap()
+                    })
+                    .collect_vec()
+            })
+            .collect_vec())
+    }
+
+    /// Extracts coefficient at `index` of the plaintext polynomial encrypted by `c0` as LWE
+    /// ciphertext under coefficients of the secret key (see `LweSecretKey::from_secret_key`).
+    ///
+    /// The ciphertext is first switched down to the last level, so the LWE ciphertext is modulo
+    /// q_0. Only meaningful for ciphertexts with coefficient (`Encoding::poly`) encoded
+    /// plaintexts.
+    pub fn sample_extract(&self, c0: &Ciphertext, index: usize) -> LweCiphertext {
+        self.try_sample_extract(c0, index).unwrap()
+    }
+
+    pub fn try_sample_extract(
+        &self,
+        c0: &Ciphertext,
+        index: usize,
+    ) -> Result<LweCiphertext, BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        check_poly_count(2, c0.c.len())?;
+        let degree = self.params.degree;
+        if index >= degree {
+            return Err(BfvError::InvalidMessageLength {
+                max: degree,
+                found: index + 1,
+            });
+        }
+
+        let mut ct = c0.clone();
+        self.try_mod_down_level(&mut ct, self.params.max_level)?;
+        self.ciphertext_change_representation(&mut ct, Representation::Coefficient);
+
+        let ctx = self.params.poly_ctx(&PolyType::Q, ct.level);
+        let modq = &ctx.moduli_ops()[0];
+        let c0 = ct.c[0].coefficients.row(0);
+        let c1 = ct.c[1].coefficients.row(0);
+
+        // coefficient `index` of c_0 + c_1*s in X^N + 1:
+        // c_0[k] + Σ_{j <= k} c_1[k - j]*s_j - Σ_{j > k} c_1[N + k - j]*s_j
+        let a = (0..degree)
+            .map(|j| {
+                if j <= index {
+                    c1[index - j]
+                } else {
+                    modq.sub_mod_fast(0, c1[degree + index - j])
+                }
+            })
+            .collect_vec();
+
+        Ok(LweCiphertext::new(a, c0[index], modq.modulus()))
+    }
+
+    /// Packs LWE ciphertexts modulo q_0 under coefficients of the secret key (for ex, output of
+    /// `sample_extract`) into a single ciphertext at `level` using ring packing of
+    /// [Chen-Dai-Kim-Song](https://eprint.iacr.org/2020/015).
+    ///
+    /// No. of LWE ciphertexts n must be at most N and is padded to the next power of two n'.
+    /// Output ciphertext encrypts message of LWE ciphertext i in coefficient i * N/n' of
+    /// plaintext polynomial (ie decodes with `Encoding::poly`). Requires galois keys generated
+    /// with `EvaluationKey::new_for_ring_packing` at `level`, thus `level` cannot be the last
+    /// level.
+    ///
+    /// LWE ciphertexts do not carry noise estimate, hence noise estimate of the output is
+    /// unknown (see `Ciphertext::noise_estimate`).
+    pub fn pack_lwes(
+        &self,
+        lwes: &[LweCiphertext],
+        level: usize,
+        ek: &EvaluationKey,
+    ) -> Ciphertext {
+        self.try_pack_lwes(lwes, level, ek).unwrap()
+    }
+
+    pub fn try_pack_lwes(
+        &self,
+        lwes: &[LweCiphertext],
+        level: usize,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        let degree = self.params.degree;
+        if lwes.is_empty() {
+            return Err(BfvError::InvalidParameters(
+                "no LWE ciphertexts to pack".to_string(),
+            ));
+        }
+        if lwes.len() > degree {
+            return Err(BfvError::InvalidMessageLength {
+                max: degree,
+                found: lwes.len(),
+            });
+        }
+        check_level_range(level, 0, self.params.max_level - 1)?;
+
+        let ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let modq0 = &ctx.moduli_ops()[0];
+        let q0 = modq0.modulus();
+        for lwe in lwes {
+            if lwe.modulus != q0 {
+                return Err(BfvError::InvalidParameters(format!(
+                    "LWE ciphertext modulus {} does not match q_0 {q0}",
+                    lwe.modulus
+                )));
+            }
+            if lwe.dimension() != degree {
+                return Err(BfvError::LweDimensionMismatch {
+                    expected: degree,
+                    found: lwe.dimension(),
+                });
+            }
+        }
+
+        // Lifting x mod q_0 to Q/q_0 * x mod Q cancels multiples of q_0. Since packing and trace
+        // multiply messages by N, LWE ciphertexts are also scaled by N^-1 mod q_0.
+        let scale = ctx.moduli_ops()[1..]
+            .iter()
+            .fold(modq0.inv(degree as u64 % q0), |acc, modqi| {
+                modq0.mul_mod_fast(acc, modqi.modulus() % q0)
+            });
+
+        // RLWE ciphertext with b + <a, s> as the constant coefficient of c_0 + c_1*s. Q/q_0 is
+        // zero modulo rest of the moduli.
+        let mut cts = lwes
+            .iter()
+            .map(|lwe| {
+                let mut c0 = ctx.zero(Representation::Coefficient);
+                let mut c1 = ctx.zero(Representation::Coefficient);
+                c0.coefficients[[0, 0]] = modq0.mul_mod_fast(lwe.b, scale);
+                c1.coefficients[[0, 0]] = modq0.mul_mod_fast(lwe.a[0], scale);
+                (1..degree).for_each(|j| {
+                    c1.coefficients[[0, degree - j]] =
+                        modq0.sub_mod_fast(0, modq0.mul_mod_fast(lwe.a[j], scale));
+                });
+                Ciphertext::new(vec![c0, c1], PolyType::Q, level)
+            })
+            .collect_vec();
+        cts.resize_with(lwes.len().next_power_of_two(), || {
+            Ciphertext::new(
+                vec![
+                    ctx.zero(Representation::Coefficient),
+                    ctx.zero(Representation::Coefficient),
+                ],
+                PolyType::Q,
+                level,
+            )
+        });
+
+        let mut ct = self.pack_ciphertexts(&cts, ek)?;
+
+        // Trace from n' to N zeroes out all coefficients except multiples of N/n'
+        for k in (cts.len().ilog2() + 1)..=degree.ilog2() {
+            let ct_sub = self.try_substitute(&ct, (1 << k) + 1, ek)?;
+            self.try_add_assign(&mut ct, &ct_sub)?;
+        }
+        Ok(ct)
+    }
+
+    /// Packs n (power of two) RLWE ciphertexts recursively such that constant coefficient of
+    /// ciphertext i, scaled by n, is at coefficient i * N/n and all coefficients that are not
+    /// multiples of N/n are zero.
+    fn pack_ciphertexts(
+        &self,
+        cts: &[Ciphertext],
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        let n = cts.len();
+        if n == 1 {
+            return Ok(cts[0].clone());
+        }
+
+        let even = cts.iter().step_by(2).cloned().collect_vec();
+        let odd = cts.iter().skip(1).step_by(2).cloned().collect_vec();
+        let ct_even = self.pack_ciphertexts(&even, ek)?;
+        let ct_odd = self.mul_monomial(
+            &self.pack_ciphertexts(&odd, ek)?,
+            (self.params.degree / n) as isize,
+        );
+
+        // (ct_even + X^(N/n)*ct_odd) + τ_(n+1)(ct_even - X^(N/n)*ct_odd)
+        let mut ct = self.try_add(&ct_even, &ct_odd)?;
+        let ct_sub = self.try_sub(&ct_even, &ct_odd)?;
+        self.try_add_assign(&mut ct, &self.try_substitute(&ct_sub, n + 1, ek)?)?;
+        Ok(ct)
+    }
+
+    /// Expands query ciphertext encrypting Σ m_i X^i into 2^`log_count` ciphertexts, where
+    /// ciphertext i encrypts 2^`log_count` * m_i as the constant coefficient, with query
+    /// expansion of [SealPIR](https://eprint.iacr.org/2017/1142).
+    ///
+    /// Coefficients of the query must be zero except for the first 2^`log_count`, otherwise
+    /// outputs have non-zero coefficients other than constant. Query values are usually scaled
+    /// by 2^-`log_count` modulo t to get m_i. Requires galois keys for
+    /// `EvaluationKey::query_expansion_galois_elements` at ciphertext's level.
+    pub fn expand_query(
+        &self,
+        c0: &Ciphertext,
+        log_count: usize,
+        ek: &EvaluationKey,
+    ) -> Vec<Ciphertext> {
+        self.try_expand_query(c0, log_count, ek).unwrap()
+    }
+
+    pub fn try_expand_query(
+        &self,
+        c0: &Ciphertext,
+        log_count: usize,
+        ek: &EvaluationKey,
+    ) -> Result<Vec<Ciphertext>, BfvError> {
+        self.check_ciphertext(c0, &PolyType::Q)?;
+        check_poly_count(2, c0.c.len())?;
+        let degree = self.params.degree;
+        if log_count > degree.ilog2() as usize {
+            return Err(BfvError::InvalidMessageLength {
+                max: degree,
+                found: 1 << log_count,
+            });
+        }
+
+        let mut cts = vec![c0.clone()];
+        for j in 0..log_count {
+            // X^(k*2^j) -> (-1)^k X^(k*2^j). Adding (resp. subtracting) automorphism keeps even
+            // (resp. odd) k.
+            let exponent = (degree >> j) + 1;
+            let mut odd_cts = Vec::with_capacity(cts.len());
+            for ct in cts.iter_mut() {
+                let ct_sub = self.try_substitute(ct, exponent, ek)?;
+                let ct_odd = self.mul_monomial(&self.try_sub(ct, &ct_sub)?, -(1 << j));
+                self.try_add_assign(ct, &ct_sub)?;
+                odd_cts.push(ct_odd);
+            }
+            cts.extend(odd_cts);
+        }
+        Ok(cts)
+    }
+
+    /// Applies automorphism X -> X^exponent to the ciphertext. Requires galois key for
+    /// `exponent` (see `EvaluationKey::new_with_galois_elements`) at ciphertext's level.
+    pub fn substitute(&self, c0: &Ciphertext, exponent: usize, ek: &EvaluationKey) -> Ciphertext {
+        self.try_substitute(c0, exponent, ek).unwrap()
+    }
+
+    pub fn try_substitute(
+        &self,
+        c0: &Ciphertext,
+        exponent: usize,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        ek.substitution_keys
+            .get(&(exponent, c0.level))
+            .ok_or(BfvError::SubstitutionKeyMissing {
+                exponent,
+                level: c0.level,
+            })?
+            .try_rotate(c0, &self.params)
+    }
+
+    /// Multiplies ciphertext by monomial X^k modulo X^N + 1. Negative `k` multiplies by the
+    /// inverse X^-k = -X^(N-k).
+    pub fn mul_monomial(&self, c0: &Ciphertext, k: isize) -> Ciphertext {
+        let degree = self.params.degree;
+        let k = k.rem_euclid(2 * degree as isize) as usize;
+        let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
+
+        let c =
+            c0.c.iter()
+                .map(|p| {
+                    let mut p = p.clone();
+                    let representation = p.representation.clone();
+                    if representation != Representation::Coefficient {
+                        ctx.change_representation(&mut p, Representation::Coefficient);
+                    }
+
+                    // X^j * X^k = (-1)^((j + k) / N) * X^((j + k) mod N)
+                    let mut r = ctx.zero(Representation::Coefficient);
+                    izip!(
+                        r.coefficients.outer_iter_mut(),
+                        p.coefficients.outer_iter(),
+                        ctx.iter_moduli_ops()
+                    )
+                    .for_each(|(mut r, p, modqi)| {
+                        p.iter().enumerate().for_each(|(j, v)| {
+                            let e = j + k;
+                            r[e % degree] = if (e / degree) & 1 == 1 {
+                                modqi.sub_mod_fast(0, *v)
+                            } else {
+                                *v
+                            };
+                        });
+                    });
+
+                    if representation != Representation::Coefficient {
+                        ctx.change_representation(&mut r, representation);
+                    }
+                    r
+                })
+                .collect_vec();
+
+        Ciphertext {
+            c,
+            poly_type: c0.poly_type.clone(),
+            level: c0.level,
+            seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: c0.noise,
+        }
+    }
+
+    pub fn plaintext_encode(&self, m: &[u64], encoding: Encoding) -> Plaintext {
+        Plaintext::encode(m, &self.params, encoding)
+    }
+
+    pub fn try_plaintext_encode(
+        &self,
+        m: &[u64],
+        encoding: Encoding,
+    ) -> Result<Plaintext, BfvError> {
+        Plaintext::try_encode(m, &self.params, encoding)
+    }
+
+    /// Encodes signed message with values in (-t/2, t/2]
+    pub fn plaintext_encode_signed(&self, m: &[i64], encoding: Encoding) -> Plaintext {
+        Plaintext::encode_signed(m, &self.params, encoding)
+    }
+
+    pub fn try_plaintext_encode_signed(
+        &self,
+        m: &[i64],
+        encoding: Encoding,
+    ) -> Result<Plaintext, BfvError> {
+        Plaintext::try_encode_signed(m, &self.params, encoding)
+    }
+
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        &self,
+        sk: &SecretKey,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Ciphertext {
+        sk.encrypt(&self.params, pt, rng)
+    }
+
+    pub fn try_encrypt<R: RngCore + CryptoRng>(
+        &self,
+        sk: &SecretKey,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Result<Ciphertext, BfvError> {
+        sk.try_encrypt(&self.params, pt, rng)
+    }
+
+    pub fn encrypt_pk<R: RngCore + CryptoRng>(
+        &self,
+        pk: &PublicKey,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Ciphertext {
+        pk.encrypt(&self.params, pt, rng)
+    }
+
+    pub fn try_encrypt_pk<R: RngCore + CryptoRng>(
+        &self,
+        pk: &PublicKey,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Result<Ciphertext, BfvError> {
+        pk.try_encrypt(&self.params, pt, rng)
+    }
+
+    pub fn decrypt(&self, sk: &SecretKey, ct: &Ciphertext) -> Plaintext {
+        sk.decrypt(ct, &self.params)
+    }
+
+    pub fn try_decrypt(&self, sk: &SecretKey, ct: &Ciphertext) -> Result<Plaintext, BfvError> {
+        sk.try_decrypt(ct, &self.params)
+    }
+
+    pub fn plaintext_decode(&self, pt: &Plaintext, encoding: Encoding) -> Vec<u64> {
+        pt.decode(encoding, &self.params)
+    }
+
+    pub fn try_plaintext_decode(
+        &self,
+        pt: &Plaintext,
+        encoding: Encoding,
+    ) -> Result<Vec<u64>, BfvError> {
+        pt.try_decode(encoding, &self.params)
+    }
+
+    /// Decodes plaintext to signed values in (-t/2, t/2]
+    pub fn plaintext_decode_signed(&self, pt: &Plaintext, encoding: Encoding) -> Vec<i64> {
+        pt.decode_signed(encoding, &self.params)
+    }
+
+    pub fn try_plaintext_decode_signed(
+        &self,
+        pt: &Plaintext,
+        encoding: Encoding,
+    ) -> Result<Vec<i64>, BfvError> {
+        pt.try_decode_signed(encoding, &self.params)
+    }
+
+    pub fn measure_noise(&self, sk: &SecretKey, ct: &Ciphertext) -> u64 {
+        sk.measure_noise(ct, &self.params)
     }
 
-    pub fn mod_down_next(&self, c0: &mut Ciphertext) {
-        assert!(c0.poly_type == PolyType::Q);
-        let level = c0.level;
-        let ctx = self.params.poly_ctx(&c0.poly_type, level);
-        c0.c.iter_mut().for_each(|p| {
-            ctx.mod_down_next(p, &self.params.lastq_inv_modql[level]);
-        });
-        c0.level = level + 1;
+    /// Returns invariant noise budget of the ciphertext in bits (see `SecretKey::noise_budget`)
+    pub fn noise_budget(&self, sk: &SecretKey, ct: &Ciphertext) -> i64 {
+        sk.noise_budget(ct, &self.params)
+    }
 
-        c0.seed = None;
+    pub fn try_noise_budget(&self, sk: &SecretKey, ct: &Ciphertext) -> Result<i64, BfvError> {
+        sk.try_noise_budget(ct, &self.params)
     }
 
-    pub fn mod_down_level(&self, c0: &mut Ciphertext, level: usize) {
-        let start_level = c0.level;
-        for _ in start_level..level {
-            self.mod_down_next(c0);
-        }
+    /// Returns estimated noise budget of the ciphertext in bits, ie noise bound at ciphertext's
+    /// level (see `BfvParameters::noise_bound`) minus `Ciphertext::noise_estimate`. Unlike
+    /// `measure_noise` does not require the secret key.
+    #[cfg(feature = "noise-estimate")]
+    pub fn estimated_noise_budget(&self, ct: &Ciphertext) -> f64 {
+        self.params.noise_bound(ct.level) - ct.noise
     }
 
-    pub fn plaintext_encode(&self, m: &[u64], encoding: Encoding) -> Plaintext {
-        Plaintext::encode(m, &self.params, encoding)
+    /// Returns error if estimated noise of the ciphertext exceeds the bound for correct
+    /// decryption. Can be used to refuse further operations on the ciphertext (for ex, a server
+    /// can check output of each operation of a circuit).
+    #[cfg(feature = "noise-estimate")]
+    pub fn check_noise_budget(&self, ct: &Ciphertext) -> Result<(), BfvError> {
+        let bound = self.params.noise_bound(ct.level);
+        if ct.noise >= bound {
+            return Err(BfvError::NoiseBudgetExhausted {
+                level: ct.level,
+                noise: ct.noise,
+                bound,
+            });
+        }
+        Ok(())
     }
 
-    pub fn encrypt<R: RngCore + CryptoRng>(
+    /// Evaluates Σ chunk\[j\] * x^j using `powers` of x where powers\[j\] = x^(j+1)
+    fn evaluate_baby_step(
         &self,
-        sk: &SecretKey,
-        pt: &Plaintext,
-        rng: &mut R,
-    ) -> Ciphertext {
-        sk.encrypt(&self.params, pt, rng)
-    }
+        chunk: &[u64],
+        powers: &[Ciphertext],
+        level: usize,
+    ) -> Result<Ciphertext, BfvError> {
+        let ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let mut res = Ciphertext {
+            c: vec![
+                ctx.zero(Representation::Coefficient),
+                ctx.zero(Representation::Coefficient),
+            ],
+            poly_type: PolyType::Q,
+            level,
+            seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: f64::NEG_INFINITY,
+        };
+
+        for (a, power) in izip!(chunk.iter().skip(1), powers.iter()) {
+            if *a != 0 {
+                izip!(res.c.iter_mut(), power.c.iter()).for_each(|(r, p)| {
+                    let mut p = p.clone();
+                    izip!(p.coefficients.outer_iter_mut(), ctx.iter_moduli_ops()).for_each(
+                        |(mut coeffs, modqi)| {
+                            modqi.scalar_mul_mod_fast_vec(coeffs.as_slice_mut().unwrap(), *a);
+                        },
+                    );
+                    ctx.add_assign(r, &p);
+                });
+                #[cfg(feature = "noise-estimate")]
+                {
+                    res.noise = self
+                        .params
+                        .noise_add(res.noise, self.params.noise_mul_plaintext(power.noise));
+                }
+            }
+        }
 
-    pub fn decrypt(&self, sk: &SecretKey, ct: &Ciphertext) -> Plaintext {
-        sk.decrypt(ct, &self.params)
+        if chunk[0] != 0 {
+            let pt = self.try_plaintext_encode(
+                &vec![chunk[0]; self.params.degree],
+                Encoding::simd(level, PolyCache::AddSub(Representation::Coefficient)),
+            )?;
+            self.try_add_assign_plaintext(&mut res, &pt)?;
+        }
+
+        Ok(res)
     }
 
-    pub fn plaintext_decode(&self, pt: &Plaintext, encoding: Encoding) -> Vec<u64> {
-        pt.decode(encoding, &self.params)
+    /// Checks that ciphertext is of `poly_type` and its level is supported by parameters
+    fn check_ciphertext(&self, ct: &Ciphertext, poly_type: &PolyType) -> Result<(), BfvError> {
+        check_poly_type(poly_type, &ct.poly_type)?;
+        check_level_range(ct.level, 0, self.params.max_level)?;
+
+        // polys with unexpected shape would be indexed out of bounds by unchecked poly ops
+        let ctx = self.params.poly_ctx(poly_type, ct.level);
+        ct.c.iter().try_for_each(|p| {
+            check_poly_shape((ctx.moduli_count(), ctx.degree()), p.coefficients.dim())?;
+            // all polys are expected in the same representation
+            check_representation(&ct.c[0].representation, &p.representation)
+        })
     }
 
-    pub fn measure_noise(&self, sk: &SecretKey, ct: &Ciphertext) -> u64 {
-        sk.measure_noise(ct, &self.params)
+    /// Checks that ciphertexts can be added/subtracted
+    fn check_binary_operands(&self, c0: &Ciphertext, c1: &Ciphertext) -> Result<(), BfvError> {
+        self.check_ciphertext(c0, &c0.poly_type)?;
+        check_poly_type(&c0.poly_type, &c1.poly_type)?;
+        check_level(c0.level, c1.level)?;
+        self.check_ciphertext(c1, &c0.poly_type)?;
+        check_poly_count(c0.c.len(), c1.c.len())
     }
 
     pub unsafe fn add_noise(&self, c0: &mut Ciphertext, bit_size: usize) {
@@ -420,6 +1622,16 @@ impl Evaluator {
             ctx.add_assign(p, &noise_poly);
         });
         c0.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            // noise poly is added to all polynomials of ciphertext
+            let expansion = (0..c0.c.len())
+                .map(|i| self.params.expansion_factor().powi(i as i32))
+                .sum::<f64>();
+            c0.noise = self
+                .params
+                .noise_add(c0.noise, bit_size as f64 + expansion.log2());
+        }
     }
 }
 
@@ -429,7 +1641,10 @@ mod tests {
 
     use rand::thread_rng;
 
-    use crate::{relinearization_key::RelinearizationKey, utils::rot_to_galois_element, PolyCache};
+    use crate::{
+        relinearization_key::RelinearizationKey, utils::rot_to_galois_element, LweSecretKey,
+        PolyCache,
+    };
 
     use super::*;
 
@@ -549,6 +1764,98 @@ mod tests {
         assert_eq!(res_sub, expected_sub);
     }
 
+    #[test]
+    fn test_poly_encoding() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let degree = params.degree;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let pk = PublicKey::new(&params, &sk, 0, &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let modt = &evaluator.params().plaintext_modulus_op;
+        let t = evaluator.params().plaintext_modulus;
+
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::poly(0, PolyCache::None));
+        let pt1 = evaluator.plaintext_encode(
+            &m1,
+            Encoding::poly(0, PolyCache::All(PolyType::Q, Representation::Coefficient)),
+        );
+
+        for ct in [
+            evaluator.encrypt(&sk, &pt0, &mut rng),
+            evaluator.encrypt_pk(&pk, &pt0, &mut rng),
+        ] {
+            let res = evaluator.plaintext_decode(
+                &evaluator.decrypt(&sk, &ct),
+                Encoding::poly(0, PolyCache::None),
+            );
+            assert_eq!(res, m0);
+            assert!(
+                evaluator.measure_noise(&sk, &ct) as f64
+                    <= evaluator.params().noise_fresh_pk() + 1.0
+            );
+
+            // add and sub plaintext
+            let ct_add = evaluator.add_plaintext(&ct, &pt1);
+            let res_add = evaluator.plaintext_decode(
+                &evaluator.decrypt(&sk, &ct_add),
+                Encoding::poly(0, PolyCache::None),
+            );
+            let mut expected_add = m0.clone();
+            modt.add_mod_fast_vec(&mut expected_add, &m1);
+            assert_eq!(res_add, expected_add);
+
+            let ct_sub = evaluator.sub_plaintext(&ct, &pt1);
+            let res_sub = evaluator.plaintext_decode(
+                &evaluator.decrypt(&sk, &ct_sub),
+                Encoding::poly(0, PolyCache::None),
+            );
+            let mut expected_sub = m0.clone();
+            modt.sub_mod_fast_vec(&mut expected_sub, &m1);
+            assert_eq!(res_sub, expected_sub);
+
+            // mul plaintext is negacyclic convolution
+            assert!(evaluator.try_mul_plaintext(&ct, &pt1).is_err());
+            let mut ct = ct.clone();
+            evaluator.ciphertext_change_representation(&mut ct, Representation::Evaluation);
+            let ct_mul = evaluator.mul_plaintext(&ct, &pt1);
+            assert!(
+                evaluator.measure_noise(&sk, &ct_mul) as f64
+                    <= evaluator
+                        .params()
+                        .noise_mul_plaintext(evaluator.params().noise_fresh_pk())
+                        + 1.0
+            );
+            let res_mul = evaluator.plaintext_decode(
+                &evaluator.decrypt(&sk, &ct_mul),
+                Encoding::poly(0, PolyCache::None),
+            );
+            let mut expected_mul = vec![0u64; degree];
+            for (i, a) in m0.iter().enumerate() {
+                for (j, b) in m1.iter().enumerate() {
+                    let v = modt.mul_mod_fast(*a, *b);
+                    let k = (i + j) % degree;
+                    if i + j >= degree {
+                        expected_mul[k] = (expected_mul[k] + t - v) % t;
+                    } else {
+                        expected_mul[k] = (expected_mul[k] + v) % t;
+                    }
+                }
+            }
+            assert_eq!(res_mul, expected_mul);
+        }
+    }
+
     #[test]
     fn test_mul_poly() {
         let mut rng = thread_rng();
@@ -718,4 +2025,553 @@ mod tests {
         evaluator.mod_down_next(&mut ct0);
         assert!(evaluator.measure_noise(&sk, &ct0) <= noise_before);
     }
+
+    #[test]
+    fn test_rotate_hoisted() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(7, 1 << 4);
+        let degree = params.degree;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let rotations = [1, 2, 3, -1, -4, (2 * degree - 1) as isize];
+        let rtg_levels = [vec![0; rotations.len()], vec![1; rotations.len()]].concat();
+        let rtg_indices = [rotations, rotations].concat();
+        let ek = EvaluationKey::new(&params, &sk, &[], &rtg_levels, &rtg_indices, &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        for level in [0, 1] {
+            let mut ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+            evaluator.mod_down_level(&mut ct0, level);
+
+            let cts_hoisted = evaluator.rotate_hoisted(&ct0, &rotations, &ek);
+            izip!(rotations.iter(), cts_hoisted.iter()).for_each(|(r, ct_hoisted)| {
+                let res_m = evaluator
+                    .plaintext_decode(&evaluator.decrypt(&sk, ct_hoisted), Encoding::default());
+
+                // rotating within rows or swapping rows
+                let row = degree / 2;
+                let expected_m = (0..degree)
+                    .map(|i| {
+                        let (row_index, col) = (i / row, i % row);
+                        if *r == (2 * degree - 1) as isize {
+                            m0[((row_index + 1) % 2) * row + col]
+                        } else {
+                            let col = (col as isize + r).rem_euclid(row as isize) as usize;
+                            m0[row_index * row + col]
+                        }
+                    })
+                    .collect_vec();
+                assert_eq!(res_m, expected_m);
+
+                let ct_rotated = evaluator.rotate(&ct0, *r, &ek);
+                assert_eq!(
+                    evaluator.plaintext_decode(
+                        &evaluator.decrypt(&sk, &ct_rotated),
+                        Encoding::default()
+                    ),
+                    res_m
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn test_rotate_with_power_of_two_rotations() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 5);
+        let degree = params.degree;
+        let row = degree / 2;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new_with_power_of_two_rotations(&params, &sk, &[], &[0], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+
+        for r in [0, 3, 7, 11, 15, 16, 21, -5, -13, -16] {
+            let ct_rotated = evaluator.rotate(&ct0, r, &ek);
+            let res_m = evaluator
+                .plaintext_decode(&evaluator.decrypt(&sk, &ct_rotated), Encoding::default());
+
+            let expected_m = (0..degree)
+                .map(|i| {
+                    let (row_index, col) = (i / row, i % row);
+                    let col = (col as isize + r).rem_euclid(row as isize) as usize;
+                    m0[row_index * row + col]
+                })
+                .collect_vec();
+            assert_eq!(res_m, expected_m);
+        }
+
+        // rotation keys at level 1 are missing
+        let mut ct1 = ct0.clone();
+        evaluator.mod_down_next(&mut ct1);
+        assert!(evaluator.try_rotate(&ct1, 3, &ek).is_err());
+    }
+
+    #[test]
+    fn test_inner_sum_and_row_sum() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 5);
+        let degree = params.degree;
+        let row = degree / 2;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new_for_inner_sum(&params, &sk, &[], &[0, 1], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let mut ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+
+        let t = evaluator.params().plaintext_modulus;
+        let row_sums = m0
+            .chunks(row)
+            .map(|r| r.iter().fold(0, |acc, v| (acc + v) % t))
+            .collect_vec();
+
+        for _ in 0..2 {
+            let ct_row_sum = evaluator.row_sum(&ct0, &ek);
+            let res_m = evaluator
+                .plaintext_decode(&evaluator.decrypt(&sk, &ct_row_sum), Encoding::default());
+            let expected_m = (0..degree).map(|i| row_sums[i / row]).collect_vec();
+            assert_eq!(res_m, expected_m);
+
+            let ct_inner_sum = evaluator.inner_sum(&ct0, &ek);
+            let res_m = evaluator
+                .plaintext_decode(&evaluator.decrypt(&sk, &ct_inner_sum), Encoding::default());
+            assert_eq!(res_m, vec![(row_sums[0] + row_sums[1]) % t; degree]);
+
+            evaluator.mod_down_next(&mut ct0);
+        }
+
+        // rotation keys at level 2 are missing
+        assert!(evaluator.try_inner_sum(&ct0, &ek).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_polynomial() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(10, 1 << 6);
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+
+        let modt = &evaluator.params().plaintext_modulus_op;
+        let t = evaluator.params().plaintext_modulus;
+        for degree in [0, 1, 2, 3, 5, 8, 15] {
+            let coeffs = modt.random_vec(degree + 1, &mut rng);
+            let ct_res = evaluator.evaluate_polynomial(&ct0, &coeffs, &ek);
+            let res_m =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_res), Encoding::default());
+
+            // Horner's
+            let expected_m = m0
+                .iter()
+                .map(|x| {
+                    coeffs
+                        .iter()
+                        .rev()
+                        .fold(0, |acc, c| (modt.mul_mod_fast(acc, *x) + c) % t)
+                })
+                .collect_vec();
+            assert_eq!(res_m, expected_m);
+        }
+    }
+
+    #[test]
+    fn test_apply_linear_transform() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 5);
+        let degree = params.degree;
+        let modt = params.plaintext_modulus_op.clone();
+        let t = params.plaintext_modulus;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+
+        let m0 = modt.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+
+        // dense, half size (does not mix rows) and sparse (diagonal) matrices
+        let dense = (0..degree)
+            .map(|_| modt.random_vec(degree, &mut rng))
+            .collect_vec();
+        let half = (0..degree / 2)
+            .map(|_| modt.random_vec(degree / 2, &mut rng))
+            .collect_vec();
+        let diagonal = (0..degree)
+            .map(|i| {
+                let mut row = vec![0; degree];
+                row[i] = modt.random_vec(1, &mut rng)[0];
+                row
+            })
+            .collect_vec();
+
+        for matrix in [dense, half, diagonal] {
+            let lt = LinearTransform::new(&matrix, evaluator.params(), 0);
+            let indices = lt.rotation_indices();
+            let ek = EvaluationKey::new(
+                evaluator.params(),
+                &sk,
+                &[],
+                &vec![0; indices.len()],
+                &indices,
+                &mut rng,
+            );
+
+            let ct = evaluator.apply_linear_transform(&ct0, &lt, &ek);
+            let res_m =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default());
+
+            let expected_m = (0..degree)
+                .map(|i| {
+                    matrix.get(i).map_or(0, |row| {
+                        izip!(row.iter(), m0.iter())
+                            .fold(0, |acc, (a, b)| (acc + modt.mul_mod_fast(*a, *b)) % t)
+                    })
+                })
+                .collect_vec();
+            assert_eq!(res_m, expected_m);
+        }
+    }
+
+    #[test]
+    fn test_mod_switch_to_modulus() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let t = params.plaintext_modulus;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+
+        let m = params.plaintext_modulus_op.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::poly(0, PolyCache::None)),
+            &mut rng,
+        );
+
+        let modulus = t * t * t;
+        let c = evaluator.mod_switch_to_modulus(&ct, modulus);
+
+        // [c0 + c1*s]_q'
+        let modulus = modulus as i128;
+        let mut v = c[0].iter().map(|v| *v as i128).collect_vec();
+        for (i, s) in sk.coefficients.iter().enumerate() {
+            for (j, c1) in c[1].iter().enumerate() {
+                let prod = *s as i128 * *c1 as i128;
+                if i + j >= degree {
+                    v[i + j - degree] -= prod;
+                } else {
+                    v[i + j] += prod;
+                }
+            }
+        }
+
+        // round(t/q' * v)
+        let res_m = v
+            .iter()
+            .map(|v| {
+                let v = v.rem_euclid(modulus);
+                (((v * t as i128 + modulus / 2) / modulus) % t as i128) as u64
+            })
+            .collect_vec();
+        assert_eq!(res_m, m);
+
+        assert!(evaluator.try_mod_switch_to_modulus(&ct, 1).is_err());
+    }
+
+    #[test]
+    fn test_sample_extract() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let lwe_sk = LweSecretKey::from_secret_key(&sk);
+
+        let m = params.plaintext_modulus_op.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::poly(0, PolyCache::None)),
+            &mut rng,
+        );
+
+        let res_m = (0..degree)
+            .map(|index| {
+                let lwe_ct = evaluator.sample_extract(&ct, index);
+                assert_eq!(lwe_ct.dimension(), degree);
+                assert_eq!(lwe_ct.modulus(), evaluator.params.ciphertext_moduli[0]);
+                lwe_ct.decrypt(&lwe_sk, &evaluator.params)
+            })
+            .collect_vec();
+        assert_eq!(res_m, m);
+
+        assert!(evaluator.try_sample_extract(&ct, degree).is_err());
+    }
+
+    #[test]
+    fn test_pack_lwes() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new_for_ring_packing(&params, &sk, &[], &[0, 1], &mut rng);
+
+        let m = params.plaintext_modulus_op.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::poly(0, PolyCache::None)),
+            &mut rng,
+        );
+        let lwes = (0..degree)
+            .map(|index| evaluator.sample_extract(&ct, index))
+            .collect_vec();
+
+        for level in [0, 1] {
+            for count in [1, 3, degree / 2, degree] {
+                let packed = evaluator.pack_lwes(&lwes[..count], level, &ek);
+                let res = evaluator.plaintext_decode(
+                    &evaluator.decrypt(&sk, &packed),
+                    Encoding::poly(level, PolyCache::None),
+                );
+
+                let gap = degree / count.next_power_of_two();
+                let mut expected = vec![0u64; degree];
+                (0..count).for_each(|i| expected[i * gap] = m[i]);
+                assert_eq!(res, expected);
+            }
+        }
+
+        // no key switching at the last level
+        assert!(evaluator.try_pack_lwes(&lwes, 2, &ek).is_err());
+    }
+
+    #[test]
+    fn test_mul_monomial() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let modt = params.plaintext_modulus_op.clone();
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let m = modt.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::poly(0, PolyCache::None)),
+            &mut rng,
+        );
+
+        for k in [0, 1, 5, degree as isize, 2 * degree as isize - 1, -3] {
+            let res = evaluator.plaintext_decode(
+                &evaluator.decrypt(&sk, &evaluator.mul_monomial(&ct, k)),
+                Encoding::poly(0, PolyCache::None),
+            );
+
+            let k = k.rem_euclid(2 * degree as isize) as usize;
+            let mut expected = vec![0u64; degree];
+            m.iter().enumerate().for_each(|(j, v)| {
+                let e = j + k;
+                expected[e % degree] = if (e / degree) & 1 == 1 {
+                    modt.sub_mod_fast(0, *v)
+                } else {
+                    *v
+                };
+            });
+            assert_eq!(res, expected);
+        }
+    }
+
+    #[test]
+    fn test_expand_query() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let modt = params.plaintext_modulus_op.clone();
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        for log_count in [0, 2, degree.ilog2() as usize] {
+            let count = 1 << log_count;
+            let exponents = EvaluationKey::query_expansion_galois_elements(degree, log_count);
+            let ek = EvaluationKey::new_with_galois_elements(
+                evaluator.params(),
+                &sk,
+                &[],
+                &vec![0; exponents.len()],
+                &exponents,
+                &mut rng,
+            );
+
+            // scale query by 2^-log_count so that expanded ciphertexts encrypt m_i
+            let m = modt.random_vec(count, &mut rng);
+            let mut query = m.clone();
+            modt.scalar_mul_mod_fast_vec(&mut query, modt.inv(count as u64));
+            let ct = evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&query, Encoding::poly(0, PolyCache::None)),
+                &mut rng,
+            );
+
+            let cts = evaluator.expand_query(&ct, log_count, &ek);
+            assert_eq!(cts.len(), count);
+            cts.iter().zip(m.iter()).for_each(|(ct, m)| {
+                let res = evaluator.plaintext_decode(
+                    &evaluator.decrypt(&sk, ct),
+                    Encoding::poly(0, PolyCache::None),
+                );
+                let mut expected = vec![0u64; degree];
+                expected[0] = *m;
+                assert_eq!(res, expected);
+            });
+        }
+    }
+
+    #[test]
+    fn test_try_ops_return_errors() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+        let mut ct1 = evaluator.encrypt(&sk, &pt0, &mut rng);
+        evaluator.mod_down_next(&mut ct1);
+
+        assert_eq!(
+            evaluator.try_mul(&ct0, &ct1),
+            Err(BfvError::LevelMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            evaluator.try_add(&ct0, &ct1),
+            Err(BfvError::LevelMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            evaluator.try_relinearize(&ct0, &ek),
+            Err(BfvError::InvalidPolyCount {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            evaluator.try_relinearize(&evaluator.mul(&ct1, &ct1), &ek),
+            Err(BfvError::RelinearizationKeyMissing { level: 1 })
+        );
+        assert_eq!(
+            evaluator.try_rotate(&ct0, 1, &ek),
+            Err(BfvError::GaloisKeyMissing {
+                rotate_by: 1,
+                level: 0
+            })
+        );
+        assert_eq!(
+            evaluator.try_mul_plaintext(&ct0, &pt0),
+            Err(BfvError::MulPolyMissing)
+        );
+        assert_eq!(
+            evaluator.try_add_plaintext(&ct0, &pt0),
+            Err(BfvError::AddSubPolyMissing)
+        );
+        assert_eq!(
+            evaluator.try_scale_and_round(&mut ct0.clone()),
+            Err(BfvError::PolyTypeMismatch {
+                expected: PolyType::PQ,
+                found: PolyType::Q
+            })
+        );
+        assert!(evaluator
+            .try_plaintext_encode(&vec![0; evaluator.params().degree + 1], Encoding::default())
+            .is_err());
+        assert_eq!(
+            evaluator
+                .try_plaintext_decode(&pt0, Encoding::default())
+                .unwrap_err(),
+            BfvError::EncodedPlaintext
+        );
+
+        // polys of level 1 in ciphertext at level 0
+        let mut ct_bad = ct0.clone();
+        ct_bad.c = ct1.c.clone();
+        let err = BfvError::InvalidPolyShape {
+            expected: (5, 16),
+            found: (4, 16),
+        };
+        assert_eq!(evaluator.try_add(&ct0, &ct_bad), Err(err.clone()));
+        assert_eq!(evaluator.try_mul(&ct_bad, &ct0), Err(err.clone()));
+        assert_eq!(evaluator.try_sub(&ct_bad, &ct0), Err(err));
+
+        // polys in different representations
+        let mut ct_mixed = ct0.clone();
+        evaluator
+            .params()
+            .poly_ctx(&PolyType::Q, 0)
+            .change_representation(&mut ct_mixed.c[1], Representation::Evaluation);
+        let err = BfvError::RepresentationMismatch {
+            expected: Representation::Coefficient,
+            found: Representation::Evaluation,
+        };
+        assert_eq!(evaluator.try_mul(&ct0, &ct_mixed), Err(err.clone()));
+        assert_eq!(evaluator.try_add(&ct_mixed, &ct0), Err(err));
+
+        // ciphertexts in different representations can be multiplied in any order
+        let mut ct0_eval = ct0.clone();
+        evaluator.ciphertext_change_representation(&mut ct0_eval, Representation::Evaluation);
+        assert_eq!(
+            evaluator.try_mul(&ct0, &ct0_eval),
+            evaluator.try_mul(&ct0_eval, &ct0)
+        );
+    }
 }
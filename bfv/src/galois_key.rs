@@ -1,7 +1,9 @@
+use crate::error::{check_level, check_poly_count, check_poly_type, BfvError};
 use crate::{
     BfvParameters, Ciphertext, HybridKeySwitchingKey, Modulus, Poly, PolyContext, PolyType,
-    Representation, SecretKey, Substitution,
+    Representation, SecretKey, Substitution, Workspace,
 };
+use itertools::Itertools;
 use rand::{CryptoRng, RngCore};
 
 #[derive(Debug, PartialEq)]
@@ -49,24 +51,108 @@ impl GaloisKey {
     }
 
     pub fn rotate(&self, ct: &Ciphertext, params: &BfvParameters) -> Ciphertext {
-        assert!(ct.c.len() == 2);
-        assert!(ct.level == self.level);
-        assert!(ct.poly_type == PolyType::Q);
+        self.try_rotate(ct, params).unwrap()
+    }
+
+    pub fn try_rotate(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+    ) -> Result<Ciphertext, BfvError> {
+        let mut out = Ciphertext::placeholder();
+        self.try_rotate_into(ct, params, &mut out, &mut Workspace::new())?;
+        Ok(out)
+    }
+
+    /// Same as `try_rotate` but writes the result into `out` and takes temporary polynomials
+    /// from `ws`
+    pub fn try_rotate_into(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        check_poly_count(2, ct.c.len())?;
+        check_level(self.level, ct.level)?;
+        check_poly_type(&PolyType::Q, &ct.poly_type)?;
 
         let level = self.level;
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
         let qp_ctx = params.poly_ctx(&PolyType::QP, level);
         let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
 
+        ws.fit_polys(&mut out.c, 2, &q_ctx);
+        let [cs0, cs1] = &mut out.c[..] else {
+            unreachable!()
+        };
+
         // Key switch c1
-        let mut c1 = q_ctx.substitute(&ct.c[1], &self.substitution);
-        if c1.representation == Representation::Evaluation {
-            q_ctx.change_representation(&mut c1, Representation::Coefficient);
+        let mut tmp = ws.take_poly(&q_ctx, Representation::Unknown);
+        q_ctx.substitute_into(&ct.c[1], &self.substitution, &mut tmp);
+        if tmp.representation == Representation::Evaluation {
+            q_ctx.change_representation(&mut tmp, Representation::Coefficient);
+        }
+
+        self.ksk_key.switch_into(
+            params.hybrid_key_switching_params_at_level(level),
+            &tmp,
+            &qp_ctx,
+            &q_ctx,
+            &specialp_ctx,
+            cs0,
+            cs1,
+            ws,
+        );
+
+        // Key switch returns polynomial in Evaluation form
+        if ct.c[0].representation != cs0.representation {
+            q_ctx.change_representation(cs0, ct.c[0].representation.clone());
+            q_ctx.change_representation(cs1, ct.c[0].representation.clone());
+        }
+
+        q_ctx.substitute_into(&ct.c[0], &self.substitution, &mut tmp);
+        q_ctx.add_assign(cs0, &tmp);
+        ws.give_poly(tmp);
+
+        out.poly_type = PolyType::Q;
+        out.level = level;
+        out.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            out.noise = params.noise_key_switch(ct.noise, level);
         }
+        Ok(())
+    }
+
+    /// Rotates ciphertext using digits of `c1` obtained with `HybridKeySwitchingKey::decompose`.
+    ///
+    /// Since automorphism commutes with decomposition, substituting digits of `c1` in QP is
+    /// equivalent to decomposing substituted `c1`. This allows to decompose `c1` once for multiple
+    /// rotations of the same ciphertext.
+    pub fn try_rotate_decomposed(
+        &self,
+        ct: &Ciphertext,
+        c1_digits: &[Poly],
+        params: &BfvParameters,
+    ) -> Result<Ciphertext, BfvError> {
+        check_poly_count(2, ct.c.len())?;
+        check_level(self.level, ct.level)?;
+        check_poly_type(&PolyType::Q, &ct.poly_type)?;
 
-        let (mut cs0, mut cs1) = self.ksk_key.switch(
+        let level = self.level;
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+        let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
+
+        let digits = c1_digits
+            .iter()
+            .map(|d| qp_ctx.substitute(d, &self.substitution))
+            .collect_vec();
+
+        let (mut cs0, mut cs1) = self.ksk_key.switch_decomposed(
             params.hybrid_key_switching_params_at_level(level),
-            &c1,
+            &digits,
             &qp_ctx,
             &q_ctx,
             &specialp_ctx,
@@ -80,11 +166,13 @@ impl GaloisKey {
 
         q_ctx.add_assign(&mut cs0, &q_ctx.substitute(&ct.c[0], &self.substitution));
 
-        Ciphertext {
+        Ok(Ciphertext {
             c: vec![cs0, cs1],
             poly_type: PolyType::Q,
             level,
             seed: None,
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: params.noise_key_switch(ct.noise, level),
+        })
     }
 }
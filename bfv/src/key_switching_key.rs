@@ -2,10 +2,11 @@ use crate::modulus::Modulus;
 use crate::{mod_inverse_biguint, mod_inverse_biguint_u64};
 use crate::{
     secret_key::SecretKey, HybridKeySwitchingParameters, Poly, PolyContext, Representation,
+    Workspace,
 };
 use crypto_bigint::rand_core::CryptoRngCore;
 use itertools::{izip, Itertools};
-use ndarray::{azip, s, Array1, Array2, Array3, Axis, IntoNdProducer};
+use ndarray::{azip, s, Array1, Array2, Array3, Axis};
 use num_bigint::{BigUint, ToBigInt};
 use num_traits::{FromPrimitive, One, ToPrimitive};
 use rand::{CryptoRng, SeedableRng};
@@ -189,120 +190,285 @@ impl HybridKeySwitchingKey {
         ksk_ctx: &PolyContext<'_>,
         specialp_ctx: &PolyContext<'_>,
     ) -> (Poly, Poly) {
+        let mut c0_out = ksk_ctx.zero(Representation::Evaluation);
+        let mut c1_out = ksk_ctx.zero(Representation::Evaluation);
+        self.switch_into(
+            ksk_params,
+            poly,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c0_out,
+            &mut c1_out,
+            &mut Workspace::new(),
+        );
+        (c0_out, c1_out)
+    }
+
+    /// Same as `switch` but writes the results into `c0_out` and `c1_out`, which must be in
+    /// `ksk_ctx`, and takes temporary polynomials from `ws`.
+    // arguments of `switch`, plus outputs and workspace
+    #[allow(clippy::too_many_arguments)]
+    pub fn switch_into(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        c0_out: &mut Poly,
+        c1_out: &mut Poly,
+        ws: &mut Workspace,
+    ) {
+        // Digits are decomposed and key switched one at a time, thus only a single digit is in
+        // memory. Use `decompose` and `switch_decomposed` to reuse digits across key switches.
+        let mut digit = ws.take_poly(qp_ctx, Representation::Coefficient);
+        let mut c0_qp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        let mut c1_qp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        let mut tmp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        for i in 0..ksk_params.dnum {
+            HybridKeySwitchingKey::decompose_digit_into(
+                ksk_params, poly, i, qp_ctx, &mut digit, ws,
+            );
+            self.accumulate_digit(i, &digit, qp_ctx, &mut c0_qp, &mut c1_qp, &mut tmp);
+        }
+
+        // switch results from QP to Q
+        HybridKeySwitchingKey::mod_down_into(
+            ksk_params,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c0_qp,
+            c0_out,
+        );
+        HybridKeySwitchingKey::mod_down_into(
+            ksk_params,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c1_qp,
+            c1_out,
+        );
+
+        ws.give_poly(digit);
+        ws.give_poly(c0_qp);
+        ws.give_poly(c1_qp);
+        ws.give_poly(tmp);
+    }
+
+    /// Divides `poly` into `dnum` parts and switches each part from Qj to QP.
+    ///
+    /// Returns `dnum` polynomials in QP in `Evaluation` representation. Since decomposition
+    /// commutes with automorphisms, digits can be computed once and reused across multiple
+    /// key switches of the same polynomial (ie hoisting).
+    pub fn decompose(
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+    ) -> Vec<Poly> {
+        let mut digits = (0..ksk_params.dnum)
+            .map(|_| qp_ctx.zero(Representation::Coefficient))
+            .collect_vec();
+        HybridKeySwitchingKey::decompose_into(
+            ksk_params,
+            poly,
+            qp_ctx,
+            &mut digits,
+            &mut Workspace::new(),
+        );
+        digits
+    }
+
+    /// Same as `decompose` but writes digits into `digits`, which must be `dnum` polynomials
+    /// in `qp_ctx`.
+    pub fn decompose_into(
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        digits: &mut [Poly],
+        ws: &mut Workspace,
+    ) {
+        debug_assert!(digits.len() == ksk_params.dnum);
+        digits.iter_mut().enumerate().for_each(|(i, qp_poly)| {
+            HybridKeySwitchingKey::decompose_digit_into(ksk_params, poly, i, qp_ctx, qp_poly, ws);
+        });
+    }
+
+    /// Switches `i`th part of `poly` from Qj to QP and writes it into `qp_poly` in `Evaluation`
+    /// representation
+    fn decompose_digit_into(
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        i: usize,
+        qp_ctx: &PolyContext<'_>,
+        qp_poly: &mut Poly,
+        ws: &mut Workspace,
+    ) {
         // TODO: check poly context
         debug_assert!(poly.representation == Representation::Coefficient);
+        debug_assert!(qp_poly.coefficients.dim() == (qp_ctx.moduli_count, qp_ctx.degree));
 
         let alpha = ksk_params.alpha;
 
-        // divide poly into parts and switch them from Qj to QP and key switch
-        let mut c0_out = Poly::placeholder();
-        let mut c1_out = Poly::placeholder();
-        for i in 0..ksk_params.dnum {
-            let mut qp_poly = qp_ctx.zero(Representation::Coefficient);
-
-            let qj_coefficients = {
-                if (i + 1) == ksk_params.dnum {
-                    poly.coefficients.slice(s![(i * alpha).., ..])
-                } else {
-                    poly.coefficients
-                        .slice(s![(i * alpha)..((i + 1) * alpha), ..])
-                }
-            };
-            let mut parts_count = qj_coefficients.shape()[0];
-
-            let mut p_whole_coefficients = PolyContext::approx_switch_crt_basis(
-                &qj_coefficients,
-                &ksk_params.qj_moduli_ops_parts[i],
-                qp_ctx.degree,
-                &ksk_params.qj_hat_inv_modqj_parts[i],
-                &ksk_params.qj_hat_modqpj_parts[i],
-                &ksk_params.qpj_moduli_ops_parts[i],
-            );
-
-            // ..p_start
-            izip!(
-                qp_poly
-                    .coefficients
-                    .slice_mut(s![..(i * alpha), ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                p_whole_coefficients
-                    .slice(s![..(i * alpha), ..])
-                    .outer_iter()
-                    .into_producer()
-            )
-            .for_each(|(mut qpi, pi)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(pi.as_slice().unwrap());
-            });
-
-            // p_start..p_start+qj
-            izip!(
-                qp_poly
-                    .coefficients
-                    .slice_mut(s![(i * alpha)..(i * alpha + parts_count), ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                qj_coefficients.outer_iter().into_producer()
-            )
-            .for_each(|(mut qpi, qj)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(qj.as_slice().unwrap());
-            });
-
-            // p_start+qj..
-            izip!(
-                qp_poly
-                    .coefficients
-                    .slice_mut(s![(i * alpha + parts_count).., ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                p_whole_coefficients
-                    .slice(s![i * alpha.., ..])
-                    .outer_iter()
-                    .into_producer()
-            )
-            .for_each(|(mut qpi, pi)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(pi.as_slice().unwrap());
-            });
-
-            qp_ctx.change_representation(&mut qp_poly, Representation::Evaluation);
-
-            if c1_out.representation == Representation::Unknown {
-                c1_out = qp_ctx.mul(&qp_poly, &self.c1s[i]);
-                qp_ctx.mul_assign(&mut qp_poly, &self.c0s[i]);
-                c0_out = qp_poly;
+        let qj_coefficients = {
+            if (i + 1) == ksk_params.dnum {
+                poly.coefficients.slice(s![(i * alpha).., ..])
             } else {
-                qp_ctx.add_assign(&mut c1_out, &qp_ctx.mul(&qp_poly, &self.c1s[i]));
-                qp_ctx.mul_assign(&mut qp_poly, &self.c0s[i]);
-                qp_ctx.add_assign(&mut c0_out, &qp_poly);
+                poly.coefficients
+                    .slice(s![(i * alpha)..((i + 1) * alpha), ..])
             }
-        }
+        };
+        let parts_count = qj_coefficients.shape()[0];
+
+        let mut p_whole_coefficients =
+            ws.take(ksk_params.qpj_moduli_ops_parts[i].len(), qp_ctx.degree);
+        PolyContext::approx_switch_crt_basis_into(
+            &qj_coefficients,
+            &ksk_params.qj_moduli_ops_parts[i],
+            &ksk_params.qj_hat_inv_modqj_parts[i],
+            &ksk_params.qj_hat_modqpj_parts[i],
+            &ksk_params.qpj_moduli_ops_parts[i],
+            p_whole_coefficients.view_mut(),
+        );
+
+        // ..p_start
+        qp_poly
+            .coefficients
+            .slice_mut(s![..(i * alpha), ..])
+            .assign(&p_whole_coefficients.slice(s![..(i * alpha), ..]));
+
+        // p_start..p_start+qj
+        qp_poly
+            .coefficients
+            .slice_mut(s![(i * alpha)..(i * alpha + parts_count), ..])
+            .assign(&qj_coefficients);
+
+        // p_start+qj..
+        qp_poly
+            .coefficients
+            .slice_mut(s![(i * alpha + parts_count).., ..])
+            .assign(&p_whole_coefficients.slice(s![i * alpha.., ..]));
+
+        ws.give(p_whole_coefficients);
+
+        qp_poly.representation = Representation::Coefficient;
+        qp_ctx.change_representation(qp_poly, Representation::Evaluation);
+    }
+
+    /// Key switches polynomial decomposed with `decompose` and switches results from QP to Q.
+    ///
+    /// Returns polynomials in `Evaluation` representation.
+    pub fn switch_decomposed(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        digits: &[Poly],
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+    ) -> (Poly, Poly) {
+        let mut c0_out = ksk_ctx.zero(Representation::Evaluation);
+        let mut c1_out = ksk_ctx.zero(Representation::Evaluation);
+        self.switch_decomposed_into(
+            ksk_params,
+            digits,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c0_out,
+            &mut c1_out,
+            &mut Workspace::new(),
+        );
+        (c0_out, c1_out)
+    }
+
+    /// Same as `switch_decomposed` but writes the results into `c0_out` and `c1_out`, which must
+    /// be in `ksk_ctx`, and takes temporary polynomials from `ws`.
+    // arguments of `switch_decomposed`, plus outputs and workspace
+    #[allow(clippy::too_many_arguments)]
+    pub fn switch_decomposed_into(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        digits: &[Poly],
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        c0_out: &mut Poly,
+        c1_out: &mut Poly,
+        ws: &mut Workspace,
+    ) {
+        debug_assert!(digits.len() == ksk_params.dnum);
+
+        let mut c0_qp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        let mut c1_qp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        let mut tmp = ws.take_poly(qp_ctx, Representation::Evaluation);
+        digits.iter().enumerate().for_each(|(i, qp_poly)| {
+            self.accumulate_digit(i, qp_poly, qp_ctx, &mut c0_qp, &mut c1_qp, &mut tmp);
+        });
 
         // switch results from QP to Q
-        let c0_out = qp_ctx.approx_mod_down(
+        HybridKeySwitchingKey::mod_down_into(
+            ksk_params,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c0_qp,
             c0_out,
-            &ksk_ctx,
-            &specialp_ctx,
-            &ksk_params.p_hat_inv_modp,
-            &ksk_params.p_hat_modq,
-            &ksk_params.p_inv_modq,
         );
-
-        let c1_out = qp_ctx.approx_mod_down(
+        HybridKeySwitchingKey::mod_down_into(
+            ksk_params,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            &mut c1_qp,
             c1_out,
-            &ksk_ctx,
-            &specialp_ctx,
+        );
+
+        ws.give_poly(c0_qp);
+        ws.give_poly(c1_qp);
+        ws.give_poly(tmp);
+    }
+
+    /// Adds product of `i`th digit `qp_poly` with `i`th key polynomials to `c0_qp` and `c1_qp`.
+    /// First digit overwrites them.
+    fn accumulate_digit(
+        &self,
+        i: usize,
+        qp_poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        c0_qp: &mut Poly,
+        c1_qp: &mut Poly,
+        tmp: &mut Poly,
+    ) {
+        if i == 0 {
+            qp_ctx.mul_into(qp_poly, &self.c1s[i], c1_qp);
+            qp_ctx.mul_into(qp_poly, &self.c0s[i], c0_qp);
+        } else {
+            qp_ctx.mul_into(qp_poly, &self.c1s[i], tmp);
+            qp_ctx.add_assign(c1_qp, tmp);
+            qp_ctx.mul_into(qp_poly, &self.c0s[i], tmp);
+            qp_ctx.add_assign(c0_qp, tmp);
+        }
+    }
+
+    /// Switches `c_qp` from QP to Q and writes the result into `c_out`
+    fn mod_down_into(
+        ksk_params: &HybridKeySwitchingParameters,
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        c_qp: &mut Poly,
+        c_out: &mut Poly,
+    ) {
+        qp_ctx.approx_mod_down_into(
+            c_qp,
+            ksk_ctx,
+            specialp_ctx,
             &ksk_params.p_hat_inv_modp,
             &ksk_params.p_hat_modq,
             &ksk_params.p_inv_modq,
+            c_out,
         );
-
-        (c0_out, c1_out)
     }
 
     /// Generates `count` polynomials from the seed and returns them in `Coefficient` representation
@@ -1,39 +1,66 @@
+mod batch;
+mod bootstrapping;
 mod ciphertext;
+mod circuit;
+mod error;
 mod evaluation_key;
 mod evaluator;
 mod galois_key;
 mod key_switching_key;
+mod linear_transform;
+mod lwe;
 mod modulus;
+mod multiparty;
 mod nb_theory;
+mod noise;
 mod ntt;
 mod parameters;
+mod pir;
+mod planner;
 mod plaintext;
 mod poly;
+mod public_key;
 mod relinearization_key;
 mod secret_key;
+mod security;
+mod simulator;
 mod utils;
+mod workspace;
 
 #[cfg(feature = "serialize")]
 mod proto;
 #[cfg(feature = "serialize")]
 pub use proto::proto::{
-    Ciphertext as CiphertextProto, EvaluationKey as EvaluationKeyProto, SecretKey as SecretKeyProto,
+    BfvParameters as BfvParametersProto, Ciphertext as CiphertextProto,
+    EvaluationKey as EvaluationKeyProto, PublicKey as PublicKeyProto, SecretKey as SecretKeyProto,
 };
 
+pub use bootstrapping::*;
 pub use ciphertext::*;
+pub use circuit::*;
+pub use error::BfvError;
 pub use evaluation_key::*;
 pub use evaluator::*;
 pub use galois_key::*;
 pub use key_switching_key::*;
+pub use linear_transform::*;
+pub use lwe::*;
 pub use modulus::*;
+pub use multiparty::*;
 pub use nb_theory::*;
-pub use ntt::NttOperator;
+pub use ntt::{NttOperator, PlaintextNttOperator, PrimePowerNttOperator};
 pub use parameters::{HybridKeySwitchingParameters, PolyType};
+pub use pir::*;
+pub use planner::*;
 pub use plaintext::*;
 pub use poly::{Poly, Representation, Substitution};
+pub use public_key::*;
 pub use relinearization_key::*;
 pub use secret_key::*;
+pub use security::*;
+pub use simulator::*;
 pub use utils::*;
+pub use workspace::*;
 
 pub type BfvParameters = parameters::BfvParameters<NttOperator>;
 pub type PolyContext<'a> = poly::PolyContext<'a, NttOperator>;
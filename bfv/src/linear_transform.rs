@@ -0,0 +1,161 @@
+use crate::error::{check_level_range, BfvError};
+use crate::{BfvParameters, Encoding, Plaintext, PolyCache, PolyType};
+use itertools::Itertools;
+
+/// Plaintext matrix pre-encoded for multiplication with SIMD encoded ciphertexts using diagonal
+/// method of [Halevi-Shoup](https://eprint.iacr.org/2014/106).
+///
+/// Slots form a 2 x N/2 matrix. Rotation by r rotates both rows by r and row swap swaps the rows.
+/// Hence, matrix M (acting on message vector of N values where value i is at row i / (N/2) and
+/// column i % (N/2)) is split into 2 x N/2 generalised diagonals such that
+///
+/// M * x = Σ_{b, r} d_(b,r) ⊙ rot_r(swap^b(x)), d_(b,r)\[(ρ, c)\] = M\[(ρ, c)\]\[(ρ ⊕ b, c + r)\]
+///
+/// Rotations are evaluated with baby-step giant-step: r = g*k + j, and diagonals are pre-rotated
+/// by -g*k so that only k baby step rotations (hoisted) and N/(2k) giant step rotations are
+/// required. Zero diagonals are skipped.
+pub struct LinearTransform {
+    /// diagonals\[b\]\[r\] rotated by -g*k. `None` if diagonal is zero
+    pub(crate) diagonals: Vec<Vec<Option<Plaintext>>>,
+    pub(crate) baby_steps: usize,
+    pub(crate) level: usize,
+}
+
+impl LinearTransform {
+    /// Encodes `matrix`, given as rows, for ciphertexts at `level`. Matrix can be smaller than
+    /// N x N, in which case it is padded with zeros.
+    ///
+    /// Panics if matrix has more than N rows or columns
+    pub fn new(matrix: &[Vec<u64>], params: &BfvParameters, level: usize) -> LinearTransform {
+        LinearTransform::try_new(matrix, params, level).unwrap()
+    }
+
+    pub fn try_new(
+        matrix: &[Vec<u64>],
+        params: &BfvParameters,
+        level: usize,
+    ) -> Result<LinearTransform, BfvError> {
+        check_level_range(level, 0, params.max_level)?;
+        let degree = params.degree;
+        if let Some(len) = std::iter::once(matrix.len())
+            .chain(matrix.iter().map(|row| row.len()))
+            .find(|len| *len > degree)
+        {
+            return Err(BfvError::InvalidMessageLength {
+                max: degree,
+                found: len,
+            });
+        }
+
+        let row_size = degree / 2;
+        let baby_steps = 1 << row_size.trailing_zeros().div_ceil(2);
+        let t = params.plaintext_modulus;
+
+        let entry = |i: usize, j: usize| -> u64 {
+            matrix
+                .get(i)
+                .and_then(|row| row.get(j))
+                .map_or(0, |v| *v % t)
+        };
+
+        let diagonals = (0..2)
+            .map(|b| {
+                (0..row_size)
+                    .map(|r| {
+                        // d_(b,r)[(ρ, c)] = M[(ρ, c)][(ρ ⊕ b, c + r)]
+                        let diagonal = (0..degree)
+                            .map(|i| {
+                                let (row, col) = (i / row_size, i % row_size);
+                                let j = (row ^ b) * row_size + (col + r) % row_size;
+                                entry(i, j)
+                            })
+                            .collect_vec();
+
+                        if diagonal.iter().all(|v| *v == 0) {
+                            return Ok(None);
+                        }
+
+                        // rotate by -g*k
+                        let giant = r - (r % baby_steps);
+                        let diagonal = (0..degree)
+                            .map(|i| {
+                                let (row, col) = (i / row_size, i % row_size);
+                                diagonal[row * row_size + (col + row_size - giant) % row_size]
+                            })
+                            .collect_vec();
+
+                        Plaintext::try_encode(
+                            &diagonal,
+                            params,
+                            Encoding::simd(level, PolyCache::Mul(PolyType::Q)),
+                        )
+                        .map(Some)
+                    })
+                    .collect::<Result<Vec<_>, BfvError>>()
+            })
+            .collect::<Result<Vec<_>, BfvError>>()?;
+
+        Ok(LinearTransform {
+            diagonals,
+            baby_steps,
+            level,
+        })
+    }
+
+    /// Returns rotation indices of galois keys required to apply the transform: baby steps,
+    /// giant steps and row swap (2N-1) if the matrix mixes rows. Keys must be generated at
+    /// transform's level.
+    pub fn rotation_indices(&self) -> Vec<isize> {
+        let mut indices = self
+            .baby_step_indices(0)
+            .into_iter()
+            .chain(self.baby_step_indices(1))
+            .chain(self.giant_step_indices())
+            .filter(|r| *r != 0)
+            .map(|r| r as isize)
+            .unique()
+            .sorted()
+            .collect_vec();
+
+        if self.uses_row_swap() {
+            let degree = self.diagonals[0].len() * 2;
+            indices.push((2 * degree - 1) as isize);
+        }
+        indices
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Returns baby steps j (including 0) for which some diagonal d_(b, g*k + j) is non-zero
+    pub(crate) fn baby_step_indices(&self, b: usize) -> Vec<usize> {
+        (0..self.baby_steps)
+            .filter(|j| {
+                self.diagonals[b]
+                    .iter()
+                    .skip(*j)
+                    .step_by(self.baby_steps)
+                    .any(|d| d.is_some())
+            })
+            .collect_vec()
+    }
+
+    /// Returns giant steps g*k for which some diagonal d_(b, g*k + j) is non-zero
+    pub(crate) fn giant_step_indices(&self) -> Vec<usize> {
+        (0..self.diagonals[0].len())
+            .step_by(self.baby_steps)
+            .filter(|giant| {
+                self.diagonals.iter().any(|diagonals| {
+                    diagonals[*giant..*giant + self.baby_steps]
+                        .iter()
+                        .any(|d| d.is_some())
+                })
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn uses_row_swap(&self) -> bool {
+        self.diagonals[1].iter().any(|d| d.is_some())
+    }
+}
@@ -0,0 +1,259 @@
+use crate::error::BfvError;
+use crate::{sample_vec_cbd, BfvParameters, Modulus, SecretKey};
+use itertools::{izip, Itertools};
+use rand::{CryptoRng, Rng, RngCore};
+
+/// LWE ciphertext (b, a) modulo `modulus` such that b + <a, s> = Δm + e
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweCiphertext {
+    pub(crate) a: Vec<u64>,
+    pub(crate) b: u64,
+    pub(crate) modulus: u64,
+}
+
+impl LweCiphertext {
+    pub fn new(a: Vec<u64>, b: u64, modulus: u64) -> LweCiphertext {
+        LweCiphertext { a, b, modulus }
+    }
+
+    pub fn a(&self) -> &[u64] {
+        &self.a
+    }
+
+    pub fn b(&self) -> u64 {
+        self.b
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.a.len()
+    }
+
+    /// Decrypts to round(t/q * [b + <a, s>]_q) where t is plaintext modulus of `params`
+    pub fn decrypt(&self, sk: &LweSecretKey, params: &BfvParameters) -> u64 {
+        self.try_decrypt(sk, params).unwrap()
+    }
+
+    pub fn try_decrypt(&self, sk: &LweSecretKey, params: &BfvParameters) -> Result<u64, BfvError> {
+        let v = self.try_phase(sk)? as u128;
+        let q = self.modulus as u128;
+        let t = params.plaintext_modulus as u128;
+        Ok((((v * t + (q >> 1)) / q) % t) as u64)
+    }
+
+    /// Returns [b + <a, s>]_q
+    fn try_phase(&self, sk: &LweSecretKey) -> Result<u64, BfvError> {
+        check_dimension(self.dimension(), sk.dimension())?;
+
+        let modq = Modulus::new(self.modulus);
+        Ok(
+            izip!(self.a.iter(), sk.coefficients.iter()).fold(self.b, |acc, (a, s)| match s {
+                1 => modq.add_mod_fast(acc, *a),
+                -1 => modq.sub_mod_fast(acc, *a),
+                _ => acc,
+            }),
+        )
+    }
+}
+
+/// Ternary LWE secret key
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweSecretKey {
+    pub(crate) coefficients: Box<[i64]>,
+}
+
+impl LweSecretKey {
+    /// Samples secret key of `dimension` with coefficients from uniform ternary distribution
+    pub fn random<R: CryptoRng + RngCore>(dimension: usize, rng: &mut R) -> LweSecretKey {
+        LweSecretKey {
+            coefficients: (0..dimension)
+                .map(|_| rng.gen_range(-1..=1))
+                .collect_vec()
+                .into_boxed_slice(),
+        }
+    }
+
+    /// Returns LWE secret key with coefficients of `sk`. LWE ciphertexts extracted from
+    /// ciphertexts encrypted under `sk` with `Evaluator::sample_extract` decrypt under it.
+    pub fn from_secret_key(sk: &SecretKey) -> LweSecretKey {
+        LweSecretKey {
+            coefficients: sk.coefficients.clone(),
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// Encrypts `m` (already scaled) as (-<a, s> + e + m, a) with `a` sampled uniformly
+    fn encrypt_scaled<R: CryptoRng + RngCore>(
+        &self,
+        m: u64,
+        modulus: &Modulus,
+        variance: usize,
+        rng: &mut R,
+    ) -> LweCiphertext {
+        let a = modulus.random_vec(self.dimension(), rng);
+        let e = sample_vec_cbd(1, variance, rng).unwrap()[0];
+
+        let mut b = modulus.add_mod_fast(m, modulus.reduce_vec_i64_small(&[e])[0]);
+        izip!(a.iter(), self.coefficients.iter()).for_each(|(a, s)| match s {
+            1 => b = modulus.sub_mod_fast(b, *a),
+            -1 => b = modulus.add_mod_fast(b, *a),
+            _ => {}
+        });
+
+        LweCiphertext {
+            a,
+            b,
+            modulus: modulus.modulus(),
+        }
+    }
+}
+
+/// Key switching key from LWE secret key `s` to LWE secret key `s'` (usually of smaller
+/// dimension). Contains encryptions of s_i * B^l under `s'` for each coefficient s_i and
+/// digit l of gadget base B = 2^log_base.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweKeySwitchingKey {
+    pub(crate) cts: Vec<Vec<LweCiphertext>>,
+    pub(crate) log_base: usize,
+    pub(crate) modulus: u64,
+}
+
+impl LweKeySwitchingKey {
+    pub fn new<R: CryptoRng + RngCore>(
+        from: &LweSecretKey,
+        to: &LweSecretKey,
+        modulus: u64,
+        log_base: usize,
+        variance: usize,
+        rng: &mut R,
+    ) -> LweKeySwitchingKey {
+        assert!(log_base > 0 && log_base < 64);
+
+        let modq = Modulus::new(modulus);
+        let digits = digits_count(modulus, log_base);
+
+        let cts = from
+            .coefficients
+            .iter()
+            .map(|s| {
+                let s = modq.reduce_vec_i64_small(&[*s])[0];
+                (0..digits)
+                    .map(|l| {
+                        let base_power = modq.reduce_naive_u128((1u128) << (l * log_base));
+                        to.encrypt_scaled(modq.mul_mod_fast(s, base_power), &modq, variance, rng)
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        LweKeySwitchingKey {
+            cts,
+            log_base,
+            modulus,
+        }
+    }
+
+    /// Switches LWE ciphertext under `s` to LWE ciphertext under `s'`. Adds noise proportional
+    /// to dimension of `s` * no. of digits * B.
+    pub fn switch(&self, ct: &LweCiphertext) -> LweCiphertext {
+        self.try_switch(ct).unwrap()
+    }
+
+    pub fn try_switch(&self, ct: &LweCiphertext) -> Result<LweCiphertext, BfvError> {
+        check_dimension(self.cts.len(), ct.dimension())?;
+        if self.modulus != ct.modulus {
+            return Err(BfvError::InvalidParameters(format!(
+                "LWE ciphertext modulus {} does not match key switching key modulus {}",
+                ct.modulus, self.modulus
+            )));
+        }
+
+        let modq = Modulus::new(self.modulus);
+        let mask = (1u64 << self.log_base) - 1;
+        let dimension = self.cts[0][0].dimension();
+
+        // b + Σ d_il * b_il, Σ d_il * a_il where a_i = Σ d_il * B^l
+        let mut a = vec![0u64; dimension];
+        let mut b = ct.b;
+        izip!(ct.a.iter(), self.cts.iter()).for_each(|(a_i, cts)| {
+            let mut a_i = *a_i;
+            cts.iter().for_each(|ksk| {
+                let digit = a_i & mask;
+                a_i >>= self.log_base;
+                if digit != 0 {
+                    b = modq.add_mod_fast(b, modq.mul_mod_fast(digit, ksk.b));
+                    izip!(a.iter_mut(), ksk.a.iter()).for_each(|(a, ksk_a)| {
+                        *a = modq.add_mod_fast(*a, modq.mul_mod_fast(digit, *ksk_a));
+                    });
+                }
+            });
+        });
+
+        Ok(LweCiphertext {
+            a,
+            b,
+            modulus: self.modulus,
+        })
+    }
+}
+
+fn digits_count(modulus: u64, log_base: usize) -> usize {
+    (64 - modulus.leading_zeros() as usize).div_ceil(log_base)
+}
+
+fn check_dimension(expected: usize, found: usize) -> Result<(), BfvError> {
+    if expected != found {
+        return Err(BfvError::LweDimensionMismatch { expected, found });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, Evaluator, PolyCache};
+    use rand::thread_rng;
+
+    #[test]
+    fn lwe_key_switching_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 6);
+        let degree = params.degree;
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let lwe_sk = LweSecretKey::from_secret_key(&sk);
+        let lwe_sk_small = LweSecretKey::random(degree / 2, &mut rng);
+
+        let m = params.plaintext_modulus_op.random_vec(degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::poly(0, PolyCache::None)),
+            &mut rng,
+        );
+
+        let modulus = evaluator.params().ciphertext_moduli[0];
+        let ksk = LweKeySwitchingKey::new(&lwe_sk, &lwe_sk_small, modulus, 10, 10, &mut rng);
+
+        (0..degree).for_each(|index| {
+            let lwe_ct = evaluator.sample_extract(&ct, index);
+            let switched = ksk.switch(&lwe_ct);
+            assert_eq!(switched.dimension(), degree / 2);
+            assert_eq!(
+                switched.decrypt(&lwe_sk_small, evaluator.params()),
+                m[index]
+            );
+        });
+
+        // ciphertext under the small key cannot be switched again
+        let switched = ksk.switch(&evaluator.sample_extract(&ct, 0));
+        assert!(ksk.try_switch(&switched).is_err());
+        assert!(switched.try_decrypt(&lwe_sk, evaluator.params()).is_err());
+    }
+}
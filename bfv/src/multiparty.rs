@@ -0,0 +1,629 @@
+//! N-out-of-N multiparty BFV.
+//!
+//! Each party holds its own `SecretKey` s_i and the collective secret key is s = Σ s_i. Parties
+//! agree on a common random seed (CRS) from which polynomials `a` are generated, in the same way
+//! `a` is generated from a seed for `PublicKey` and key switching keys. Protocols follow
+//! [Mouchet et al.](https://eprint.iacr.org/2020/304).
+//!
+//! Shares of two keys must never use the same `a`, otherwise combining the shares leaks secret
+//! keys. Thus, `a` is generated from a seed derived from the CRS separately for each protocol,
+//! level and galois exponent, and a single CRS can be used for all keys of a session. A CRS must
+//! never be reused across sessions.
+//!
+//! Every protocol consists of generating a share with `new` by each party and combining all
+//! shares with `aggregate`.
+use crate::error::{check_level, check_level_range, check_poly_count, check_poly_type, BfvError};
+use crate::{
+    BfvParameters, Ciphertext, GaloisKey, HybridKeySwitchingKey, Plaintext, Poly, PolyContext,
+    PolyType, PublicKey, RelinearizationKey, Representation, SecretKey, Substitution,
+};
+use itertools::{izip, Itertools};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use rand::distributions::Uniform;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Share of collective public key
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKeyShare {
+    pub(crate) c0: Poly,
+    pub(crate) level: usize,
+}
+
+impl PublicKeyShare {
+    /// Generates share -a*s_i + e_i at `level` where `a` is generated from seed derived from `crs`
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        crs: <ChaCha8Rng as SeedableRng>::Seed,
+        level: usize,
+        rng: &mut R,
+    ) -> PublicKeyShare {
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+        let a = crs_poly(&ctx, derive_crs(crs, CrsKey::Public, level));
+
+        let mut c0 = ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+        ctx.change_representation(&mut c0, Representation::Evaluation);
+        ctx.sub_assign(&mut c0, &ctx.mul(&a, &sk.to_poly(&ctx)));
+
+        PublicKeyShare { c0, level }
+    }
+
+    /// Aggregates shares of all parties into collective public key (-a*s + e, a)
+    pub fn aggregate(
+        params: &BfvParameters,
+        shares: &[PublicKeyShare],
+        crs: <ChaCha8Rng as SeedableRng>::Seed,
+    ) -> Result<PublicKey, BfvError> {
+        let level = shares_level(shares.iter().map(|s| s.level))?;
+        check_level_range(level, 0, params.max_level)?;
+
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+        let mut c0 = shares[0].c0.clone();
+        shares.iter().skip(1).for_each(|s| {
+            ctx.add_assign(&mut c0, &s.c0);
+        });
+
+        let seed = derive_crs(crs, CrsKey::Public, level);
+        Ok(PublicKey {
+            c0,
+            c1: crs_poly(&ctx, seed),
+            seed,
+            level,
+        })
+    }
+}
+
+/// Share of the first round of collective relinearization key generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelinearizationKeyShareRound1 {
+    pub(crate) h0s: Vec<Poly>,
+    pub(crate) h1s: Vec<Poly>,
+    pub(crate) level: usize,
+}
+
+impl RelinearizationKeyShareRound1 {
+    /// Generates shares (-u_i*a_j + g_j*s_i + e0_ij, s_i*a_j + e1_ij) for each digit j of hybrid
+    /// key switching, where `a_j` is generated from seed derived from `crs`.
+    ///
+    /// `ephemeral_sk` (u_i) must be freshly sampled by the party (for ex, with
+    /// `SecretKey::random_with_params`), kept private and reused in second round.
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        ephemeral_sk: &SecretKey,
+        crs: <ChaCha8Rng as SeedableRng>::Seed,
+        level: usize,
+        rng: &mut R,
+    ) -> RelinearizationKeyShareRound1 {
+        let ksk_params = params.hybrid_key_switching_params_at_level(level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let sk_poly = sk.to_poly(&qp_ctx);
+        let u_poly = ephemeral_sk.to_poly(&qp_ctx);
+
+        let seed = derive_crs(crs, CrsKey::Relinearization, level);
+        let (h0s, h1s) = izip!(crs_ksk_polys(ksk_params.dnum, &qp_ctx, seed), &ksk_params.g)
+            .map(|(a, g)| {
+                // -u_i*a_j + g_j*s_i + e0_ij
+                let mut h0 = gaussian_poly(&qp_ctx, params.variance, rng);
+                qp_ctx.add_assign(&mut h0, &mul_gadget(&qp_ctx, &sk_poly, g));
+                qp_ctx.sub_assign(&mut h0, &qp_ctx.mul(&u_poly, &a));
+
+                // s_i*a_j + e1_ij
+                let mut h1 = gaussian_poly(&qp_ctx, params.variance, rng);
+                qp_ctx.add_assign(&mut h1, &qp_ctx.mul(&sk_poly, &a));
+
+                (h0, h1)
+            })
+            .unzip();
+
+        RelinearizationKeyShareRound1 { h0s, h1s, level }
+    }
+
+    /// Sums first round shares of all parties. The output is input to second round.
+    pub fn aggregate(
+        params: &BfvParameters,
+        shares: &[RelinearizationKeyShareRound1],
+    ) -> Result<RelinearizationKeyShareRound1, BfvError> {
+        let level = shares_level(shares.iter().map(|s| s.level))?;
+        check_level_range(level, 0, params.max_level - 1)?;
+
+        let dnum = params.hybrid_key_switching_params_at_level(level).dnum;
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        Ok(RelinearizationKeyShareRound1 {
+            h0s: sum_shares(&qp_ctx, shares.iter().map(|s| s.h0s.as_slice()), dnum)?,
+            h1s: sum_shares(&qp_ctx, shares.iter().map(|s| s.h1s.as_slice()), dnum)?,
+            level,
+        })
+    }
+}
+
+/// Share of the second round of collective relinearization key generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelinearizationKeyShareRound2 {
+    pub(crate) h0s: Vec<Poly>,
+    pub(crate) h1s: Vec<Poly>,
+    pub(crate) level: usize,
+}
+
+impl RelinearizationKeyShareRound2 {
+    /// Generates shares (s_i*h0_j + e2_ij, (u_i - s_i)*h1_j + e3_ij) where (h0_j, h1_j) are
+    /// aggregated first round shares and u_i is the ephemeral secret key used in first round.
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        ephemeral_sk: &SecretKey,
+        round1: &RelinearizationKeyShareRound1,
+        rng: &mut R,
+    ) -> RelinearizationKeyShareRound2 {
+        let level = round1.level;
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let sk_poly = sk.to_poly(&qp_ctx);
+        let u_minus_sk = qp_ctx.sub(&ephemeral_sk.to_poly(&qp_ctx), &sk_poly);
+
+        let (h0s, h1s) = izip!(round1.h0s.iter(), round1.h1s.iter())
+            .map(|(h0, h1)| {
+                // s_i*h0_j + e2_ij
+                let mut h0_share = gaussian_poly(&qp_ctx, params.variance, rng);
+                qp_ctx.add_assign(&mut h0_share, &qp_ctx.mul(&sk_poly, h0));
+
+                // (u_i - s_i)*h1_j + e3_ij
+                let mut h1_share = gaussian_poly(&qp_ctx, params.variance, rng);
+                qp_ctx.add_assign(&mut h1_share, &qp_ctx.mul(&u_minus_sk, h1));
+
+                (h0_share, h1_share)
+            })
+            .unzip();
+
+        RelinearizationKeyShareRound2 { h0s, h1s, level }
+    }
+
+    /// Aggregates second round shares of all parties into collective relinearization key
+    /// (Σ h0'_ij + h1'_ij, h1_j) for s^2.
+    pub fn aggregate(
+        params: &BfvParameters,
+        round1: &RelinearizationKeyShareRound1,
+        shares: &[RelinearizationKeyShareRound2],
+    ) -> Result<RelinearizationKey, BfvError> {
+        let level = shares_level(shares.iter().map(|s| s.level))?;
+        check_level(round1.level, level)?;
+        check_level_range(level, 0, params.max_level - 1)?;
+
+        let dnum = params.hybrid_key_switching_params_at_level(level).dnum;
+        check_poly_count(dnum, round1.h1s.len())?;
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let mut c0s = sum_shares(&qp_ctx, shares.iter().map(|s| s.h0s.as_slice()), dnum)?;
+        let h1s = sum_shares(&qp_ctx, shares.iter().map(|s| s.h1s.as_slice()), dnum)?;
+        izip!(c0s.iter_mut(), h1s.iter()).for_each(|(c0, h1)| {
+            qp_ctx.add_assign(c0, h1);
+        });
+
+        Ok(RelinearizationKey {
+            ksk: HybridKeySwitchingKey {
+                seed: None,
+                c0s: c0s.into_boxed_slice(),
+                c1s: round1.h1s.clone().into_boxed_slice(),
+            },
+            level,
+        })
+    }
+}
+
+/// Share of collective galois key
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaloisKeyShare {
+    pub(crate) c0s: Vec<Poly>,
+    pub(crate) exponent: usize,
+    pub(crate) level: usize,
+}
+
+impl GaloisKeyShare {
+    /// Generates shares g_j*σ(s_i) + e_ij - a_j*s_i for each digit j of hybrid key switching
+    /// where σ is the automorphism X -> X^exponent and `a_j` is generated from seed derived from
+    /// `crs`.
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        exponent: usize,
+        crs: <ChaCha8Rng as SeedableRng>::Seed,
+        level: usize,
+        rng: &mut R,
+    ) -> GaloisKeyShare {
+        let ksk_params = params.hybrid_key_switching_params_at_level(level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let sk_poly = sk.to_poly(&qp_ctx);
+        let sk_substituted =
+            qp_ctx.substitute(&sk_poly, &Substitution::new(exponent, params.degree));
+
+        let seed = derive_crs(crs, CrsKey::Galois(exponent), level);
+        let c0s = izip!(crs_ksk_polys(ksk_params.dnum, &qp_ctx, seed), &ksk_params.g)
+            .map(|(a, g)| {
+                let mut c0 = gaussian_poly(&qp_ctx, params.variance, rng);
+                qp_ctx.add_assign(&mut c0, &mul_gadget(&qp_ctx, &sk_substituted, g));
+                qp_ctx.sub_assign(&mut c0, &qp_ctx.mul(&a, &sk_poly));
+                c0
+            })
+            .collect_vec();
+
+        GaloisKeyShare {
+            c0s,
+            exponent,
+            level,
+        }
+    }
+
+    /// Aggregates shares of all parties into collective galois key
+    pub fn aggregate(
+        params: &BfvParameters,
+        shares: &[GaloisKeyShare],
+        crs: <ChaCha8Rng as SeedableRng>::Seed,
+    ) -> Result<GaloisKey, BfvError> {
+        let level = shares_level(shares.iter().map(|s| s.level))?;
+        check_level_range(level, 0, params.max_level - 1)?;
+
+        let exponent = shares[0].exponent;
+        if shares.iter().any(|s| s.exponent != exponent) {
+            return Err(BfvError::InvalidParameters(
+                "galois key shares for different exponents".to_string(),
+            ));
+        }
+
+        let dnum = params.hybrid_key_switching_params_at_level(level).dnum;
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+        let c0s = sum_shares(&qp_ctx, shares.iter().map(|s| s.c0s.as_slice()), dnum)?;
+        let seed = derive_crs(crs, CrsKey::Galois(exponent), level);
+
+        Ok(GaloisKey {
+            substitution: Substitution::new(exponent, params.degree),
+            ksk_key: HybridKeySwitchingKey {
+                seed: Some(seed),
+                c0s: c0s.into_boxed_slice(),
+                c1s: crs_ksk_polys(dnum, &qp_ctx, seed).into_boxed_slice(),
+            },
+            level,
+        })
+    }
+}
+
+/// Share of distributed decryption
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecryptionShare {
+    pub(crate) d: Poly,
+    pub(crate) level: usize,
+}
+
+impl DecryptionShare {
+    /// Generates share c1*s_i + e_i of ciphertext (c0, c1) where smudging noise e_i is sampled
+    /// uniformly from (-2^smudging_bits, 2^smudging_bits). Smudging noise must be much larger than
+    /// ciphertext noise to hide s_i and must not overflow Q/(2t) once summed over all parties.
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        ct: &Ciphertext,
+        smudging_bits: usize,
+        rng: &mut R,
+    ) -> DecryptionShare {
+        DecryptionShare::try_new(params, sk, ct, smudging_bits, rng).unwrap()
+    }
+
+    pub fn try_new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        ct: &Ciphertext,
+        smudging_bits: usize,
+        rng: &mut R,
+    ) -> Result<DecryptionShare, BfvError> {
+        check_poly_count(2, ct.c.len())?;
+        check_poly_type(&PolyType::Q, &ct.poly_type)?;
+        check_level_range(ct.level, 0, params.max_level)?;
+
+        let ctx = params.poly_ctx(&PolyType::Q, ct.level);
+        let min_modulus_bits = ctx
+            .iter_moduli_ops()
+            .map(|modqi| 64 - modqi.modulus().leading_zeros() as usize)
+            .min()
+            .unwrap();
+        if smudging_bits >= min_modulus_bits - 1 {
+            return Err(BfvError::InvalidParameters(format!(
+                "smudging bits must be smaller than {}",
+                min_modulus_bits - 1
+            )));
+        }
+
+        // c1*s_i
+        let mut d = ct.c[1].clone();
+        ctx.change_representation(&mut d, Representation::Evaluation);
+        ctx.mul_assign(&mut d, &sk.to_poly(&ctx));
+
+        // c1*s_i + e_i
+        let bound = 1i64 << smudging_bits;
+        let e = rng
+            .sample_iter(Uniform::new(-bound + 1, bound))
+            .take(params.degree)
+            .collect_vec();
+        let mut e = ctx.try_convert_from_i64_small(&e, Representation::Coefficient);
+        ctx.change_representation(&mut e, Representation::Evaluation);
+        ctx.add_assign(&mut d, &e);
+
+        Ok(DecryptionShare { d, level: ct.level })
+    }
+
+    /// Combines decryption shares of all parties to plaintext of `ct`
+    pub fn aggregate(
+        params: &BfvParameters,
+        ct: &Ciphertext,
+        shares: &[DecryptionShare],
+    ) -> Result<Plaintext, BfvError> {
+        check_poly_count(2, ct.c.len())?;
+        check_poly_type(&PolyType::Q, &ct.poly_type)?;
+        check_level(ct.level, shares_level(shares.iter().map(|s| s.level))?)?;
+        check_level_range(ct.level, 0, params.max_level)?;
+
+        let ctx = params.poly_ctx(&PolyType::Q, ct.level);
+
+        // c0 + Σ (c1*s_i + e_i)
+        let mut m = ct.c[0].clone();
+        ctx.change_representation(&mut m, Representation::Evaluation);
+        shares.iter().for_each(|s| {
+            ctx.add_assign(&mut m, &s.d);
+        });
+        ctx.change_representation(&mut m, Representation::Coefficient);
+
+        Ok(Plaintext::from_decrypted_poly(&m, ct.level, params))
+    }
+}
+
+/// Returns level of shares. Fails if there are no shares or shares are at different levels.
+fn shares_level(mut levels: impl Iterator<Item = usize>) -> Result<usize, BfvError> {
+    let level = levels.next().ok_or(BfvError::SharesMissing)?;
+    levels.try_for_each(|l| check_level(level, l))?;
+    Ok(level)
+}
+
+/// Sums polynomials of shares element wise. Each share must have `count` polynomials.
+fn sum_shares<'a>(
+    ctx: &PolyContext<'_>,
+    mut shares: impl Iterator<Item = &'a [Poly]>,
+    count: usize,
+) -> Result<Vec<Poly>, BfvError> {
+    let first = shares.next().ok_or(BfvError::SharesMissing)?;
+    check_poly_count(count, first.len())?;
+
+    let mut sum = first.to_vec();
+    shares.try_for_each(|share| {
+        check_poly_count(count, share.len())?;
+        izip!(sum.iter_mut(), share.iter()).for_each(|(s, p)| {
+            ctx.add_assign(s, p);
+        });
+        Ok::<(), BfvError>(())
+    })?;
+    Ok(sum)
+}
+
+/// Keys generated from CRS
+#[derive(Debug, Clone, Copy)]
+enum CrsKey {
+    Public,
+    Relinearization,
+    /// Galois key for exponent
+    Galois(usize),
+}
+
+/// Derives seed of `a` for `key` at `level` from `crs`. Seed is the output of ChaCha8 keyed
+/// with `crs` on a stream unique to `key` and `level`.
+fn derive_crs(
+    crs: <ChaCha8Rng as SeedableRng>::Seed,
+    key: CrsKey,
+    level: usize,
+) -> <ChaCha8Rng as SeedableRng>::Seed {
+    // tag (8 bits) | level (24 bits) | exponent (32 bits)
+    let (tag, exponent) = match key {
+        CrsKey::Public => (0u64, 0),
+        CrsKey::Relinearization => (1, 0),
+        CrsKey::Galois(exponent) => (2, exponent as u64),
+    };
+    debug_assert!(level < 1 << 24 && exponent < 1 << 32);
+
+    let mut rng = ChaCha8Rng::from_seed(crs);
+    rng.set_stream((tag << 56) | ((level as u64) << 32) | exponent);
+    let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+    rng.fill_bytes(&mut seed);
+    seed
+}
+
+/// Returns polynomial `a` generated from `crs` in `Evaluation` representation
+fn crs_poly(ctx: &PolyContext<'_>, crs: <ChaCha8Rng as SeedableRng>::Seed) -> Poly {
+    let mut a = ctx.random_with_seed(crs);
+    ctx.change_representation(&mut a, Representation::Evaluation);
+    a
+}
+
+/// Returns polynomials `a_j` of key switching key generated from `crs` in `Evaluation`
+/// representation
+fn crs_ksk_polys(
+    count: usize,
+    qp_ctx: &PolyContext<'_>,
+    crs: <ChaCha8Rng as SeedableRng>::Seed,
+) -> Vec<Poly> {
+    let mut polys = HybridKeySwitchingKey::generate_c1(count, qp_ctx, crs);
+    polys.iter_mut().for_each(|p| {
+        qp_ctx.change_representation(p, Representation::Evaluation);
+    });
+    polys
+}
+
+fn gaussian_poly<R: CryptoRng + RngCore>(
+    ctx: &PolyContext<'_>,
+    variance: usize,
+    rng: &mut R,
+) -> Poly {
+    let mut e = ctx.random_gaussian(Representation::Coefficient, variance, rng);
+    ctx.change_representation(&mut e, Representation::Evaluation);
+    e
+}
+
+/// Returns g * poly in QP. g is a multiple of P, thus g * poly vanishes over special moduli.
+fn mul_gadget(qp_ctx: &PolyContext<'_>, poly: &Poly, g: &BigUint) -> Poly {
+    let mut p = poly.clone();
+    izip!(p.coefficients.outer_iter_mut(), qp_ctx.iter_moduli_ops()).for_each(
+        |(mut coeffs, modqi)| {
+            let g_u64 = (g % modqi.modulus()).to_u64().unwrap();
+            modqi.scalar_mul_mod_fast_vec(coeffs.as_slice_mut().unwrap(), g_u64);
+        },
+    );
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rot_to_galois_element, Encoding, EvaluationKey, Evaluator};
+    use rand::thread_rng;
+
+    #[test]
+    fn multiparty_keys_and_decryption() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let parties = 3;
+        let level = 0;
+
+        let sks = (0..parties)
+            .map(|_| SecretKey::random_with_params(&params, &mut rng))
+            .collect_vec();
+
+        let random_crs = || {
+            let mut crs = <ChaCha8Rng as SeedableRng>::Seed::default();
+            thread_rng().fill_bytes(&mut crs);
+            crs
+        };
+
+        // collective public key
+        let crs = random_crs();
+        let pk_shares = sks
+            .iter()
+            .map(|sk| PublicKeyShare::new(&params, sk, crs, level, &mut rng))
+            .collect_vec();
+        let pk = PublicKeyShare::aggregate(&params, &pk_shares, crs).unwrap();
+
+        // collective relinearization key
+        let crs = random_crs();
+        let ephemeral_sks = (0..parties)
+            .map(|_| SecretKey::random_with_params(&params, &mut rng))
+            .collect_vec();
+        let round1_shares = izip!(sks.iter(), ephemeral_sks.iter())
+            .map(|(sk, u)| RelinearizationKeyShareRound1::new(&params, sk, u, crs, level, &mut rng))
+            .collect_vec();
+        let round1 = RelinearizationKeyShareRound1::aggregate(&params, &round1_shares).unwrap();
+        let round2_shares = izip!(sks.iter(), ephemeral_sks.iter())
+            .map(|(sk, u)| RelinearizationKeyShareRound2::new(&params, sk, u, &round1, &mut rng))
+            .collect_vec();
+        let rlk =
+            RelinearizationKeyShareRound2::aggregate(&params, &round1, &round2_shares).unwrap();
+
+        // collective galois key
+        let crs = random_crs();
+        let exponent = rot_to_galois_element(1, params.degree);
+        let rtg_shares = sks
+            .iter()
+            .map(|sk| GaloisKeyShare::new(&params, sk, exponent, crs, level, &mut rng))
+            .collect_vec();
+        let rtg = GaloisKeyShare::aggregate(&params, &rtg_shares, crs).unwrap();
+
+        let ek = EvaluationKey::from_keys(vec![rlk], vec![(1, rtg)]);
+
+        let mut m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt_pk(
+            &pk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt_pk(
+            &pk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        let ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        let ct01_rotated = evaluator.rotate(&ct01, 1, &ek);
+        evaluator
+            .params()
+            .plaintext_modulus_op
+            .mul_mod_fast_vec(&mut m0, &m1);
+
+        let row = evaluator.params().degree / 2;
+        let m0_rotated = m0
+            .chunks(row)
+            .flat_map(|r| r.iter().cycle().skip(1).take(row).copied())
+            .collect_vec();
+
+        for (ct, expected_m) in [(&ct01, &m0), (&ct01_rotated, &m0_rotated)] {
+            let shares = sks
+                .iter()
+                .map(|sk| DecryptionShare::new(evaluator.params(), sk, ct, 30, &mut rng))
+                .collect_vec();
+            let pt = DecryptionShare::aggregate(evaluator.params(), ct, &shares).unwrap();
+            let rm = evaluator.plaintext_decode(&pt, Encoding::default());
+            assert_eq!(&rm, expected_m);
+        }
+
+        // fewer shares do not decrypt
+        let shares = sks
+            .iter()
+            .skip(1)
+            .map(|sk| DecryptionShare::new(evaluator.params(), sk, &ct01, 30, &mut rng))
+            .collect_vec();
+        let pt = DecryptionShare::aggregate(evaluator.params(), &ct01, &shares).unwrap();
+        assert_ne!(evaluator.plaintext_decode(&pt, Encoding::default()), m0);
+
+        assert!(matches!(
+            DecryptionShare::aggregate(evaluator.params(), &ct01, &[]),
+            Err(BfvError::SharesMissing)
+        ));
+    }
+
+    #[test]
+    fn crs_is_derived_per_key() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let mut crs = <ChaCha8Rng as SeedableRng>::Seed::default();
+        rng.fill_bytes(&mut crs);
+
+        let exponents = [3, 5, 2 * params.degree - 1];
+        let keys = [CrsKey::Public, CrsKey::Relinearization]
+            .into_iter()
+            .chain(exponents.map(CrsKey::Galois));
+        let seeds = keys
+            .cartesian_product(0..2)
+            .map(|(key, level)| derive_crs(crs, key, level))
+            .collect_vec();
+        assert_eq!(seeds.iter().unique().count(), seeds.len());
+        assert!(seeds.iter().all(|seed| *seed != crs));
+
+        // `a_j` of relinearization key and galois keys for different exponents at the same level
+        // differ
+        let level = 0;
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+        let dnum = params.hybrid_key_switching_params_at_level(level).dnum;
+        let a = [CrsKey::Relinearization]
+            .into_iter()
+            .chain(exponents.map(CrsKey::Galois))
+            .map(|key| crs_ksk_polys(dnum, &qp_ctx, derive_crs(crs, key, level)))
+            .collect_vec();
+        a.iter().tuple_combinations().for_each(|(a0, a1)| {
+            izip!(a0.iter(), a1.iter()).for_each(|(a0_j, a1_j)| assert_ne!(a0_j, a1_j));
+        });
+    }
+}
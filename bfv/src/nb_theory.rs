@@ -1,3 +1,4 @@
+use crate::error::BfvError;
 use crate::modulus::Modulus;
 use num_bigint_dig::{prime::probably_prime, BigUint};
 use rand::{thread_rng, Rng};
@@ -7,8 +8,18 @@ pub fn generate_primes_vec(
     polynomial_degree: usize,
     skip_list: &[u64],
 ) -> Vec<u64> {
+    try_generate_primes_vec(sizes, polynomial_degree, skip_list).expect("Not enough primes")
+}
+
+/// Same as `generate_primes_vec` but returns error instead of panicking if there are not enough
+/// primes of a size
+pub fn try_generate_primes_vec(
+    sizes: &[usize],
+    polynomial_degree: usize,
+    skip_list: &[u64],
+) -> Result<Vec<u64>, BfvError> {
     let mut primes = vec![];
-    sizes.iter().for_each(|s| {
+    for s in sizes {
         let mut upper_bound = 1u64 << s;
         loop {
             if let Some(p) = generate_prime(*s, (2 * polynomial_degree) as u64, upper_bound) {
@@ -19,11 +30,14 @@ pub fn generate_primes_vec(
                     upper_bound = p;
                 }
             } else {
-                panic!("Not enough primes");
+                return Err(BfvError::InvalidParameters(format!(
+                    "not enough {s} bit primes congruent to 1 mod {}",
+                    2 * polynomial_degree
+                )));
             }
         }
-    });
-    primes
+    }
+    Ok(primes)
 }
 
 /// Finds prime such that prime % n == 1
@@ -51,6 +65,28 @@ pub fn generate_prime(num_bits: usize, modulo: u64, upper_bound: u64) -> Option<
     }
 }
 
+/// Returns true if `p` is (probably) a prime
+pub fn is_prime(p: u64) -> bool {
+    probably_prime(&BigUint::from(p), 0)
+}
+
+/// Returns (p, r) such that `n` = p^r for prime p, if any
+pub fn prime_power(n: u64) -> Option<(u64, u32)> {
+    if n < 2 {
+        return None;
+    }
+    for r in (1..64).rev() {
+        let root = (n as f64).powf(1.0 / r as f64).round() as u64;
+        // account for floating point error in root
+        for p in root.saturating_sub(1)..=root + 1 {
+            if p >= 2 && p.checked_pow(r) == Some(n) && is_prime(p) {
+                return Some((p, r));
+            }
+        }
+    }
+    None
+}
+
 // Finds 2n_th primitive root of unity in field mod p
 pub fn primitive_element(p: u64, n: usize) -> Option<u64> {
     let mut rng = thread_rng();
@@ -80,4 +116,13 @@ mod tests {
         let prime = generate_prime(51, 1 << 15, 1 << 51);
         dbg!(prime);
     }
+
+    #[test]
+    fn prime_power_works() {
+        assert_eq!(prime_power(65537), Some((65537, 1)));
+        assert_eq!(prime_power(97 * 97 * 97), Some((97, 3)));
+        assert_eq!(prime_power(1 << 40), Some((2, 40)));
+        assert_eq!(prime_power(97 * 193), None);
+        assert_eq!(prime_power(1), None);
+    }
 }
@@ -0,0 +1,317 @@
+use crate::parameters::BfvParameters;
+use traits::Ntt;
+
+/// Heuristic upper bounds on noise of ciphertexts, in bits.
+///
+/// Noise of a ciphertext at level l is the smallest v such that
+/// c_0 + c_1*s + ... + c_k*s^k = floor(Q_l*m/t) + v mod Q_l (same as `SecretKey::measure_noise`).
+/// Ciphertext decrypts correctly as long as ||v|| < Q_l/(2t).
+///
+/// Norm of product of two polynomials is bounded with expansion factor δ = 2√N (as in `v_norm`),
+/// secret key is ternary, and error is bounded by 2 * variance since it is sampled from centered
+/// binomial distribution. Noise is in log scale to not overflow for large Q; -inf is no noise and
+/// +inf is unknown noise.
+impl<T> BfvParameters<T>
+where
+    T: Ntt,
+{
+    /// Maximum noise of ciphertext at `level` that decrypts correctly, ie log2(Q_l/(2t))
+    pub fn noise_bound(&self, level: usize) -> f64 {
+        self.log2_q(level) - 1.0 - self.noise_model().log2_t
+    }
+
+    /// Noise of ciphertext encrypted with secret key
+    pub fn noise_fresh_sk(&self) -> f64 {
+        self.noise_model().fresh_sk()
+    }
+
+    /// Noise of ciphertext encrypted with public key: e_pk*u + e_0 + e_1*s
+    pub fn noise_fresh_pk(&self) -> f64 {
+        self.noise_model().fresh_pk()
+    }
+
+    /// Noise of sum (or difference) of ciphertexts with noise `a` and `b`. Use -inf for `b` to
+    /// add a plaintext.
+    pub fn noise_add(&self, a: f64, b: f64) -> f64 {
+        self.noise_model().add(a, b)
+    }
+
+    /// Noise of product of ciphertext with noise `a` and plaintext (or polynomial) with
+    /// coefficients in [0, t)
+    pub fn noise_mul_plaintext(&self, a: f64) -> f64 {
+        self.noise_model().mul_plaintext(a)
+    }
+
+    /// Noise of product (scaled by t/Q and rounded) of ciphertexts with noise `a` and `b` at
+    /// `level`
+    pub fn noise_mul(&self, a: f64, b: f64, level: usize) -> f64 {
+        self.noise_model().mul(a, b, self.log2_q(level))
+    }
+
+    /// Noise of ciphertext with noise `a` at `level` after relinearization or rotation, ie
+    /// after hybrid key switching.
+    ///
+    /// Panics if hybrid key switching is not enabled.
+    pub fn noise_key_switch(&self, a: f64, level: usize) -> f64 {
+        let ksk_params = self.hybrid_key_switching_params_at_level(level);
+        let moduli_count = self.ciphertext_moduli.len() - level;
+        let log2_qj = self.ciphertext_moduli[..moduli_count]
+            .chunks(ksk_params.alpha)
+            .map(|qj| qj.iter().map(|qi| (*qi as f64).log2()).sum::<f64>())
+            .fold(0.0, f64::max);
+        let log2_p = self
+            .special_moduli
+            .iter()
+            .flatten()
+            .map(|pi| (*pi as f64).log2())
+            .sum::<f64>();
+
+        self.noise_model()
+            .key_switch(a, ksk_params.dnum, ksk_params.alpha, log2_qj, log2_p)
+    }
+
+    /// Noise of ciphertext with noise `a` after dropping the last modulus at `level`
+    pub fn noise_mod_down(&self, a: f64, level: usize) -> f64 {
+        let last_qi = self.ciphertext_moduli[self.ciphertext_moduli.len() - 1 - level];
+        self.noise_model().mod_down(a, (last_qi as f64).log2())
+    }
+
+    #[cfg(feature = "noise-estimate")]
+    pub(crate) fn expansion_factor(&self) -> f64 {
+        self.noise_model().expansion_factor()
+    }
+
+    fn log2_q(&self, level: usize) -> f64 {
+        self.ciphertext_moduli[..self.ciphertext_moduli.len() - level]
+            .iter()
+            .map(|qi| (*qi as f64).log2())
+            .sum()
+    }
+
+    fn noise_model(&self) -> NoiseModel {
+        NoiseModel {
+            degree: self.degree,
+            log2_t: (self.plaintext_modulus as f64).log2(),
+            variance: self.variance,
+        }
+    }
+}
+
+/// Noise formulas of `BfvParameters` in terms of quantities they depend on. Allows to estimate
+/// noise of parameters (for ex, in `CircuitProfile::plan`) without generating them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NoiseModel {
+    pub(crate) degree: usize,
+    pub(crate) log2_t: f64,
+    pub(crate) variance: usize,
+}
+
+impl NoiseModel {
+    pub(crate) fn fresh_sk(&self) -> f64 {
+        self.log2_error_bound()
+    }
+
+    pub(crate) fn fresh_pk(&self) -> f64 {
+        self.log2_error_bound() + (1.0 + 2.0 * self.expansion_factor()).log2()
+    }
+
+    pub(crate) fn add(&self, a: f64, b: f64) -> f64 {
+        // difference of floor(Q*m_0/t) + floor(Q*m_1/t) and floor(Q*[m_0 + m_1]_t/t) is at most 2
+        log2_sum(&[a, b, 1.0])
+    }
+
+    pub(crate) fn mul_plaintext(&self, a: f64) -> f64 {
+        // v*m + [Q*m_0]_t*m/t
+        log2_sum(&[
+            self.expansion_factor().log2() + self.log2_t + log2_sum(&[a, 0.0]),
+            0.0,
+        ])
+    }
+
+    pub(crate) fn mul(&self, a: f64, b: f64, log2_q: f64) -> f64 {
+        let delta = self.expansion_factor();
+
+        // c_i(s) = Q*m_i/t + v_i' + Q*k_i with ||v_i'|| <= ||v_i|| + 1 and ||k_i|| <= (3 + δ)/2.
+        // Scaled product has noise m_0*v_1' + m_1*v_0' + t*(v_0'*k_1 + v_1'*k_0) + t*v_0'*v_1'/Q
+        // plus rounding of c_0, c_1 and c_2.
+        let a = log2_sum(&[a, 0.0]);
+        let b = log2_sum(&[b, 0.0]);
+        log2_sum(&[
+            delta.log2() + self.log2_t + (2.0 + delta / 2.0).log2() + log2_sum(&[a, b]),
+            delta.log2() + self.log2_t + a + b - log2_q,
+            ((1.0 + delta + delta * delta) / 2.0).log2(),
+            0.0,
+        ])
+    }
+
+    /// `log2_qj` is bits in the largest of `dnum` digits of `alpha` moduli and `log2_p` is bits in
+    /// special moduli
+    pub(crate) fn key_switch(
+        &self,
+        a: f64,
+        dnum: usize,
+        alpha: usize,
+        log2_qj: f64,
+        log2_p: f64,
+    ) -> f64 {
+        let delta = self.expansion_factor();
+
+        // Σ d_j*e_j / P, where digits d_j approximately switched from Q_j to QP are bounded by
+        // (α + 1)*Q_j/2, plus rounding of mod down by P
+        let ks = log2_sum(&[
+            (dnum as f64).log2()
+                + delta.log2()
+                + ((alpha + 1) as f64 / 2.0).log2()
+                + log2_qj
+                + self.log2_error_bound()
+                - log2_p,
+            (1.0 + delta).log2(),
+        ]);
+        log2_sum(&[a, ks])
+    }
+
+    pub(crate) fn mod_down(&self, a: f64, log2_last_qi: f64) -> f64 {
+        // v/q plus flooring of c_0 + c_1*s and difference in scaling of m by Q/t and Q'/t
+        log2_sum(&[a - log2_last_qi, (3.0 + self.expansion_factor()).log2()])
+    }
+
+    fn log2_error_bound(&self) -> f64 {
+        (2.0 * self.variance as f64).log2()
+    }
+
+    fn expansion_factor(&self) -> f64 {
+        2.0 * (self.degree as f64).sqrt()
+    }
+}
+
+/// Returns log2(Σ 2^v)
+pub(crate) fn log2_sum(values: &[f64]) -> f64 {
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max.is_infinite() {
+        return max;
+    }
+    max + values.iter().map(|v| (v - max).exp2()).sum::<f64>().log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ciphertext, Encoding, EvaluationKey, Evaluator, PublicKey, SecretKey};
+    use rand::thread_rng;
+
+    #[test]
+    fn noise_bounds_hold() {
+        let mut rng = thread_rng();
+        let params = crate::BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let pk = PublicKey::new(&params, &sk, 0, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+        let evaluator = Evaluator::new(params);
+        let params = evaluator.params();
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let ct_sk = evaluator.encrypt(&sk, &pt, &mut rng);
+        let ct_pk = evaluator.encrypt_pk(&pk, &pt, &mut rng);
+        // `measure_noise` returns no. of bits of the noise
+        let within = |ct: &Ciphertext, noise: f64| {
+            #[cfg(feature = "noise-estimate")]
+            assert_eq!(ct.noise_estimate(), noise);
+            evaluator.measure_noise(&sk, ct) as f64 <= noise.floor() + 1.0
+        };
+
+        assert!(within(&ct_sk, params.noise_fresh_sk()));
+        assert!(within(&ct_pk, params.noise_fresh_pk()));
+
+        let ct = evaluator.add(&ct_sk, &ct_pk);
+        let noise = params.noise_add(params.noise_fresh_sk(), params.noise_fresh_pk());
+        assert!(within(&ct, noise));
+
+        let ct = evaluator.relinearize(&evaluator.mul(&ct, &ct_sk), &ek);
+        let noise = params.noise_key_switch(params.noise_mul(noise, params.noise_fresh_sk(), 0), 0);
+        assert!(within(&ct, noise));
+
+        let mut ct = evaluator.rotate(&ct, 1, &ek);
+        let noise = params.noise_key_switch(noise, 0);
+        assert!(within(&ct, noise));
+
+        evaluator.mod_down_next(&mut ct);
+        let noise = params.noise_mod_down(noise, 0);
+        assert!(within(&ct, noise));
+        assert!(noise < params.noise_bound(1));
+
+        assert_eq!(log2_sum(&[f64::NEG_INFINITY; 2]), f64::NEG_INFINITY);
+        assert_eq!(log2_sum(&[f64::INFINITY, 3.0]), f64::INFINITY);
+        assert_eq!(log2_sum(&[3.0, 3.0]), 4.0);
+    }
+
+    #[cfg(feature = "noise-estimate")]
+    #[test]
+    fn noise_budget_is_checked() {
+        use crate::{BfvError, PolyCache, PolyType, Representation};
+
+        let mut rng = thread_rng();
+        let params = crate::BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+        let evaluator = Evaluator::new(params);
+        let modt = evaluator.params().plaintext_modulus_op.clone();
+
+        let mut m = modt.random_vec(evaluator.params().degree, &mut rng);
+        let mut ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+
+        // plaintext multiplication
+        let pt = evaluator.plaintext_encode(&m, Encoding::simd(0, PolyCache::Mul(PolyType::Q)));
+        let mut ct_pt = ct.clone();
+        evaluator.ciphertext_change_representation(&mut ct_pt, Representation::Evaluation);
+        let ct_pt = evaluator.mul_plaintext(&ct_pt, &pt);
+        assert_eq!(
+            ct_pt.noise_estimate(),
+            evaluator.params().noise_mul_plaintext(ct.noise_estimate())
+        );
+        assert!(evaluator.measure_noise(&sk, &ct_pt) as f64 <= ct_pt.noise_estimate() + 1.0);
+
+        // noise of ciphertext created from polynomials is unknown until it is set
+        let mut ct_raw = Ciphertext::new(ct.c_ref().to_vec(), PolyType::Q, 0);
+        assert!(evaluator.check_noise_budget(&ct_raw).is_err());
+        assert!(evaluator
+            .check_noise_budget(&evaluator.add(&ct_raw, &ct))
+            .is_err());
+        ct_raw.set_noise_estimate(ct.noise_estimate());
+        assert!(evaluator.check_noise_budget(&ct_raw).is_ok());
+
+        // square until estimated noise budget is exhausted
+        let mut squarings = 0;
+        let err = loop {
+            assert!(evaluator.estimated_noise_budget(&ct) > 0.0);
+            assert!(evaluator.measure_noise(&sk, &ct) as f64 <= ct.noise_estimate() + 1.0);
+            assert!(
+                evaluator.noise_budget(&sk, &ct) as f64
+                    >= evaluator.estimated_noise_budget(&ct) - 1.0
+            );
+
+            let m_ct =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default());
+            assert_eq!(m_ct, m);
+
+            ct = evaluator.relinearize(&evaluator.mul(&ct, &ct), &ek);
+            let square = m.clone();
+            modt.mul_mod_fast_vec(&mut m, &square);
+            squarings += 1;
+            if let Err(err) = evaluator.check_noise_budget(&ct) {
+                break err;
+            }
+        };
+        assert!(squarings > 1);
+        assert!(matches!(
+            err,
+            BfvError::NoiseBudgetExhausted { level: 0, .. }
+        ));
+    }
+}
@@ -8,3 +8,6 @@ pub use concrete::NttOperator;
 mod hexl;
 #[cfg(feature = "hexl-ntt")]
 pub use hexl::NttOperator;
+
+mod plaintext;
+pub use plaintext::{PlaintextNttOperator, PrimePowerNttOperator};
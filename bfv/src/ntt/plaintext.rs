@@ -0,0 +1,180 @@
+use crate::modulus::Modulus;
+use crate::nb_theory::{is_prime, prime_power};
+use traits::Ntt;
+
+/// NTT operator for plaintext modulus t. Uses `T` if t is a prime, otherwise falls back to
+/// `PrimePowerNttOperator`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaintextNttOperator<T: Ntt> {
+    Prime(T),
+    PrimePower(PrimePowerNttOperator),
+}
+
+impl<T: Ntt> Ntt for PlaintextNttOperator<T> {
+    fn new(degree: usize, modulus: u64) -> Self {
+        if is_prime(modulus) {
+            PlaintextNttOperator::Prime(T::new(degree, modulus))
+        } else {
+            PlaintextNttOperator::PrimePower(PrimePowerNttOperator::new(degree, modulus))
+        }
+    }
+
+    fn forward(&self, a: &mut [u64]) {
+        match self {
+            PlaintextNttOperator::Prime(op) => op.forward(a),
+            PlaintextNttOperator::PrimePower(op) => op.forward(a),
+        }
+    }
+
+    fn forward_lazy(&self, a: &mut [u64]) {
+        match self {
+            PlaintextNttOperator::Prime(op) => op.forward_lazy(a),
+            PlaintextNttOperator::PrimePower(op) => op.forward(a),
+        }
+    }
+
+    fn backward(&self, a: &mut [u64]) {
+        match self {
+            PlaintextNttOperator::Prime(op) => op.backward(a),
+            PlaintextNttOperator::PrimePower(op) => op.backward(a),
+        }
+    }
+}
+
+/// Negacyclic NTT modulo p^r where prime p is congruent to 1 mod 2N.
+///
+/// Z_{p^r}[X]/(X^N + 1) splits into N slots since primitive 2N_th root of unity mod p lifts
+/// (Hensel) to p^r. Output is in bit-reversed order, same as `NttOperator`. Slow, only meant
+/// for plaintexts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimePowerNttOperator {
+    degree: usize,
+    modulus: Modulus,
+    psi_powers: Vec<u64>,
+    psi_inv_powers: Vec<u64>,
+    degree_inv: u64,
+}
+
+impl PrimePowerNttOperator {
+    /// Panics if `modulus` is not p^r for prime p congruent to 1 mod 2`degree`
+    pub fn new(degree: usize, modulus: u64) -> PrimePowerNttOperator {
+        let (p, r) = prime_power(modulus).expect("modulus must be a prime power");
+        let m = 2 * degree as u64;
+        assert!(p % m == 1, "prime {p} must be congruent to 1 mod {m}");
+
+        // order of multiplicative group mod p^r
+        let phi = p.pow(r - 1) * (p - 1);
+        let modulus = Modulus::new(modulus);
+
+        // g^(phi/2N) is primitive 2N_th root iff its N_th power is -1
+        let psi = (2..)
+            .map(|g| modulus.exp(g, (phi / m) as usize))
+            .find(|psi| modulus.exp(*psi, degree) == modulus.modulus() - 1)
+            .unwrap();
+        let psi_inv = modulus.exp(psi, (m - 1) as usize);
+
+        let log_degree = degree.trailing_zeros();
+        let mut psi_powers = vec![0; degree];
+        let mut psi_inv_powers = vec![0; degree];
+        for i in 0..degree {
+            let index = i.reverse_bits() >> (usize::BITS - log_degree);
+            psi_powers[index] = modulus.exp(psi, i);
+            psi_inv_powers[index] = modulus.exp(psi_inv, i);
+        }
+
+        PrimePowerNttOperator {
+            degree,
+            degree_inv: modulus.exp(degree as u64, (phi - 1) as usize),
+            modulus,
+            psi_powers,
+            psi_inv_powers,
+        }
+    }
+
+    /// Cooley-Tukey
+    pub fn forward(&self, a: &mut [u64]) {
+        debug_assert!(a.len() == self.degree);
+        let mut t = self.degree;
+        let mut m = 1;
+        while m < self.degree {
+            t >>= 1;
+            for i in 0..m {
+                let s = self.psi_powers[m + i];
+                for j in (2 * i * t)..(2 * i * t + t) {
+                    let u = a[j];
+                    let v = self.modulus.mul_mod_fast(a[j + t], s);
+                    a[j] = self.modulus.add_mod_fast(u, v);
+                    a[j + t] = self.modulus.sub_mod_fast(u, v);
+                }
+            }
+            m <<= 1;
+        }
+    }
+
+    /// Gentleman-Sande
+    pub fn backward(&self, a: &mut [u64]) {
+        debug_assert!(a.len() == self.degree);
+        let mut t = 1;
+        let mut m = self.degree;
+        while m > 1 {
+            let h = m >> 1;
+            for i in 0..h {
+                let s = self.psi_inv_powers[h + i];
+                for j in (2 * i * t)..(2 * i * t + t) {
+                    let u = a[j];
+                    let v = a[j + t];
+                    a[j] = self.modulus.add_mod_fast(u, v);
+                    a[j + t] = self
+                        .modulus
+                        .mul_mod_fast(self.modulus.sub_mod_fast(u, v), s);
+                }
+            }
+            t <<= 1;
+            m = h;
+        }
+        self.modulus.scalar_mul_mod_fast_vec(a, self.degree_inv);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn prime_power_ntt_works() {
+        let mut rng = thread_rng();
+        let degree = 16;
+        let t = 97 * 97 * 97;
+        let modt = Modulus::new(t);
+        let ntt = PrimePowerNttOperator::new(degree, t);
+
+        let a = modt.random_vec(degree, &mut rng);
+        let b = modt.random_vec(degree, &mut rng);
+
+        let mut a_ntt = a.clone();
+        ntt.forward(&mut a_ntt);
+        let mut a_back = a_ntt.clone();
+        ntt.backward(&mut a_back);
+        assert_eq!(a_back, a);
+
+        // negacyclic product
+        let mut b_ntt = b.clone();
+        ntt.forward(&mut b_ntt);
+        modt.mul_mod_fast_vec(&mut a_ntt, &b_ntt);
+        ntt.backward(&mut a_ntt);
+
+        let mut expected = vec![0; degree];
+        for i in 0..degree {
+            for j in 0..degree {
+                let prod = modt.mul_mod_fast(a[i], b[j]);
+                if i + j >= degree {
+                    expected[i + j - degree] = modt.sub_mod_fast(expected[i + j - degree], prod);
+                } else {
+                    expected[i + j] = modt.add_mod_fast(expected[i + j], prod);
+                }
+            }
+        }
+        assert_eq!(a_ntt, expected);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::modulus::Modulus;
-use crate::nb_theory::generate_primes_vec;
+use crate::nb_theory::{generate_primes_vec, is_prime, prime_power};
+use crate::ntt::PlaintextNttOperator;
 use crate::{mod_inverse_biguint, mod_inverse_biguint_u64};
 use crate::{poly::poly_context::PolyContext, Poly, Representation};
+use crate::{warn, BfvError, SecurityLevel};
 use itertools::Itertools;
 use ndarray::Array2;
 use num_bigint::BigUint;
@@ -9,6 +11,14 @@ use num_traits::{One, Pow, ToPrimitive};
 use std::vec;
 use traits::Ntt;
 
+/// Parameter of centered binomial distribution of errors
+pub(crate) const DEFAULT_VARIANCE: usize = 10;
+/// Bounds on sizes of primes selected by `BfvParameters::preset` and `CircuitProfile::plan`
+pub(crate) const MAX_PRIME_BITS: usize = 60;
+pub(crate) const MIN_PRIME_BITS: usize = 20;
+/// No. of special moduli (ie alpha) of hybrid key switching
+pub(crate) const SPECIAL_MODULI_COUNT: usize = 3;
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum PolyType {
     Q,
@@ -34,7 +44,7 @@ pub struct BfvParameters<T: Ntt> {
 
     pub plaintext_modulus: u64,
     pub plaintext_modulus_op: Modulus,
-    pub plaintext_ntt_op: T,
+    pub plaintext_ntt_op: PlaintextNttOperator<T>,
     pub degree: usize,
 
     // Convert Utils
@@ -115,8 +125,6 @@ where
 
         // expansion factor delta
         let delta = 2.0 * (n as f64).sqrt();
-        let f = (2.0_f64).pow(60);
-        dbg!(((delta * bound_error * f * 8.0) as f64 / 2.0).log2());
 
         (bound_error * (1.0 + 2.0 * delta * bound_key))
     }
@@ -149,6 +157,74 @@ where
         let extension_moduli =
             generate_primes_vec(ciphertext_moduli_sizes, degree, &ciphertext_moduli);
 
+        BfvParameters::with_moduli(
+            &ciphertext_moduli,
+            &extension_moduli,
+            plaintext_modulus,
+            degree,
+        )
+    }
+
+    /// Same as `with_moduli` but returns error if moduli, plaintext modulus or degree are
+    /// inconsistent instead of panicking (or silently producing incorrect parameters)
+    pub fn try_with_moduli(
+        ciphertext_moduli: &[u64],
+        extension_moduli: &[u64],
+        plaintext_modulus: u64,
+        degree: usize,
+    ) -> Result<BfvParameters<T>, BfvError> {
+        if !degree.is_power_of_two() || degree < 16 {
+            return Err(BfvError::InvalidParameters(format!(
+                "degree {degree} must be a power of 2 and >= 16"
+            )));
+        }
+        if ciphertext_moduli.is_empty() || ciphertext_moduli.len() != extension_moduli.len() {
+            return Err(BfvError::InvalidParameters(
+                "ciphertext and extension moduli must be non-empty and of equal length".to_string(),
+            ));
+        }
+        // plaintext modulus must support SIMD encoding
+        if prime_power(plaintext_modulus).is_none_or(|(p, _)| p % (2 * degree as u64) != 1) {
+            return Err(BfvError::InvalidParameters(format!(
+                "plaintext modulus {plaintext_modulus} must be a power of a prime congruent to 1 mod {}",
+                2 * degree
+            )));
+        }
+
+        let mut seen = vec![];
+        check_moduli(ciphertext_moduli, degree, &mut seen)?;
+        check_moduli(extension_moduli, degree, &mut seen)?;
+
+        if ciphertext_moduli.iter().any(|qi| *qi <= plaintext_modulus) {
+            return Err(BfvError::InvalidParameters(
+                "ciphertext moduli must be greater than plaintext modulus".to_string(),
+            ));
+        }
+
+        Ok(BfvParameters::with_moduli(
+            ciphertext_moduli,
+            extension_moduli,
+            plaintext_modulus,
+            degree,
+        ))
+    }
+
+    /// Creates new bfv parameters with given ciphertext (Q) and extension (P) moduli chains.
+    ///
+    /// Panics if moduli are not NTT friendly primes. Use `try_with_moduli` for untrusted inputs.
+    pub fn with_moduli(
+        ciphertext_moduli: &[u64],
+        extension_moduli: &[u64],
+        plaintext_modulus: u64,
+        degree: usize,
+    ) -> BfvParameters<T> {
+        let ciphertext_moduli = ciphertext_moduli.to_vec();
+        let extension_moduli = extension_moduli.to_vec();
+        let ciphertext_moduli_sizes = ciphertext_moduli
+            .iter()
+            .map(|qi| (64 - qi.leading_zeros()) as usize)
+            .collect_vec();
+
         // moduli ops
         let ciphertext_moduli_ops = ciphertext_moduli
             .iter()
@@ -513,7 +589,7 @@ where
         }
 
         let plaintext_modulus_op = Modulus::new(plaintext_modulus);
-        let plaintext_ntt_op = T::new(degree, plaintext_modulus);
+        let plaintext_ntt_op = PlaintextNttOperator::new(degree, plaintext_modulus);
 
         // Default to Hamming weight set to N/2.
         let hw = degree / 2;
@@ -530,7 +606,7 @@ where
             q_size,
             p_size,
 
-            variance: 10,
+            variance: DEFAULT_VARIANCE,
             hw,
 
             plaintext_modulus,
@@ -597,10 +673,52 @@ where
     }
 
     pub fn enable_hybrid_key_switching(&mut self, specialp_bits: &[usize; 3]) {
-        const ALPHA: usize = 3;
-        let dnum = (self.ciphertext_moduli.len() as f64 / ALPHA as f64).ceil() as usize;
         let special_moduli =
             generate_primes_vec(specialp_bits, self.degree, &self.ciphertext_moduli);
+        self.enable_hybrid_key_switching_with_moduli(&[
+            special_moduli[0],
+            special_moduli[1],
+            special_moduli[2],
+        ]);
+    }
+
+    /// Same as `enable_hybrid_key_switching_with_moduli` but returns error if special moduli
+    /// are inconsistent with parameters
+    pub fn try_enable_hybrid_key_switching_with_moduli(
+        &mut self,
+        special_moduli: &[u64; 3],
+    ) -> Result<(), BfvError> {
+        let mut seen = self.ciphertext_moduli.clone();
+        check_moduli(special_moduli, self.degree, &mut seen)?;
+
+        // Bits in special moduli P must be more or less equal to bits in max(Qj), otherwise key
+        // switching noise is not reduced by P
+        let specialp_bits = special_moduli
+            .iter()
+            .map(|pi| (64 - pi.leading_zeros()) as usize)
+            .sum::<usize>();
+        let max_qj_bits = self
+            .ciphertext_moduli_sizes
+            .chunks(special_moduli.len())
+            .map(|sizes| sizes.iter().sum::<usize>())
+            .max()
+            .unwrap();
+        if max_qj_bits / specialp_bits > 1 {
+            return Err(BfvError::InvalidParameters(format!(
+                "special moduli bits {specialp_bits} too small for max(Qj) bits {max_qj_bits}"
+            )));
+        }
+
+        self.enable_hybrid_key_switching_with_moduli(special_moduli);
+        Ok(())
+    }
+
+    /// Enables hybrid key switching with given special moduli. Number of special moduli (ie alpha)
+    /// is fixed to 3.
+    pub fn enable_hybrid_key_switching_with_moduli(&mut self, special_moduli: &[u64; 3]) {
+        const ALPHA: usize = SPECIAL_MODULI_COUNT;
+        let dnum = (self.ciphertext_moduli.len() as f64 / ALPHA as f64).ceil() as usize;
+        let special_moduli = special_moduli.to_vec();
         let special_moduli_ops = special_moduli
             .iter()
             .map(|pj| Modulus::new(*pj))
@@ -627,6 +745,13 @@ where
             .collect_vec();
 
         self.hybrid_ksk_parameters = Some(params);
+
+        warn!(
+            self.security_estimate().is_none(),
+            "Parameters with degree {} and log(QP) {} are insecure",
+            self.degree,
+            self.log_qp()
+        );
     }
 
     pub fn poly_ctx(&self, poly_type: &PolyType, level: usize) -> PolyContext<'_, T> {
@@ -710,6 +835,100 @@ where
         params.enable_hybrid_key_switching(&[50, 50, 50]);
         params
     }
+
+    /// Creates parameters with hybrid key switching enabled that satisfy `security_level` for
+    /// polynomial `degree`.
+    ///
+    /// All primes (ciphertext and special) are of equal size and their product fits within maximum
+    /// log(QP) of HE standard. Preset uses as many ciphertext moduli as required to keep primes
+    /// within 60 bits but no less than 2, since key switching is not supported at the last level.
+    pub fn preset(
+        security_level: SecurityLevel,
+        degree: usize,
+        plaintext_modulus: u64,
+    ) -> Result<BfvParameters<T>, BfvError> {
+        let max_log_qp = security_level.max_log_qp(degree).ok_or_else(|| {
+            BfvError::InvalidParameters(format!(
+                "HE standard does not specify polynomial degree {degree}"
+            ))
+        })?;
+
+        let q_count = std::cmp::max(
+            max_log_qp
+                .div_ceil(MAX_PRIME_BITS)
+                .saturating_sub(SPECIAL_MODULI_COUNT),
+            2,
+        );
+        let prime_bits = max_log_qp / (q_count + SPECIAL_MODULI_COUNT);
+
+        let t_bits = (64 - plaintext_modulus.leading_zeros()) as usize;
+        if prime_bits < MIN_PRIME_BITS || prime_bits <= t_bits {
+            return Err(BfvError::InvalidParameters(format!(
+                "{prime_bits} bit primes are too small for degree {degree} and plaintext modulus {plaintext_modulus}"
+            )));
+        }
+
+        let mut params = BfvParameters::new(&vec![prime_bits; q_count], plaintext_modulus, degree);
+        params.enable_hybrid_key_switching(&[prime_bits; SPECIAL_MODULI_COUNT]);
+        Ok(params)
+    }
+
+    /// Returns bits in QP, where Q is ciphertext modulus and P is product of special moduli
+    /// (if hybrid key switching is enabled)
+    pub fn log_qp(&self) -> usize {
+        let mut qp = BigUint::one();
+        self.ciphertext_moduli
+            .iter()
+            .chain(self.special_moduli.iter().flatten())
+            .for_each(|qi| {
+                qp *= *qi;
+            });
+        qp.bits() as usize
+    }
+
+    /// Returns highest classical security level satisfied by parameters according to HE standard.
+    ///
+    /// Returns `None` if parameters are insecure or polynomial degree is not specified by the
+    /// standard.
+    pub fn security_estimate(&self) -> Option<SecurityLevel> {
+        SecurityLevel::estimate(self.degree, self.log_qp())
+    }
+
+    /// Returns error if parameters do not satisfy `security_level`
+    pub fn check_security(&self, security_level: SecurityLevel) -> Result<(), BfvError> {
+        let log_qp = self.log_qp();
+        let max_log_qp = security_level.max_log_qp(self.degree);
+        match max_log_qp {
+            Some(max) if log_qp <= max => Ok(()),
+            _ => Err(BfvError::InsecureParameters { log_qp, max_log_qp }),
+        }
+    }
+}
+
+/// Checks that each modulus is an NTT friendly prime of atmost 60 bits that is not in `seen`.
+/// Appends moduli to `seen`.
+fn check_moduli(moduli: &[u64], degree: usize, seen: &mut Vec<u64>) -> Result<(), BfvError> {
+    for qi in moduli {
+        let bits = 64 - qi.leading_zeros();
+        if !(8..=60).contains(&bits) {
+            return Err(BfvError::InvalidParameters(format!(
+                "modulus {qi} must have between 8 and 60 bits"
+            )));
+        }
+        if !is_prime(*qi) || qi % (2 * degree as u64) != 1 {
+            return Err(BfvError::InvalidParameters(format!(
+                "modulus {qi} must be a prime congruent to 1 mod {}",
+                2 * degree
+            )));
+        }
+        if seen.contains(qi) {
+            return Err(BfvError::InvalidParameters(format!(
+                "modulus {qi} is repeated"
+            )));
+        }
+        seen.push(*qi);
+    }
+    Ok(())
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -879,7 +1098,76 @@ impl HybridKeySwitchingParameters {
 
 #[cfg(test)]
 mod tests {
-    use crate::BfvParameters;
+    use crate::{BfvParameters, Encoding, EvaluationKey, Evaluator, SecretKey, SecurityLevel};
+    use rand::thread_rng;
+
+    #[test]
+    fn preset_parameters() {
+        let params = BfvParameters::preset(SecurityLevel::Bits128, 1 << 13, 65537).unwrap();
+        assert!(params.log_qp() <= 218);
+        assert_eq!(params.security_estimate(), Some(SecurityLevel::Bits128));
+        assert!(params.check_security(SecurityLevel::Bits128).is_ok());
+        assert!(params.check_security(SecurityLevel::Bits192).is_err());
+
+        let params = BfvParameters::preset(SecurityLevel::Bits256, 1 << 15, 65537).unwrap();
+        assert_eq!(params.security_estimate(), Some(SecurityLevel::Bits256));
+
+        // not enough bits in QP
+        assert!(BfvParameters::preset(SecurityLevel::Bits128, 1 << 10, 65537).is_err());
+        // degree not specified by HE standard
+        assert!(BfvParameters::preset(SecurityLevel::Bits128, 1 << 4, 65537).is_err());
+
+        let params = BfvParameters::default(3, 1 << 4);
+        assert_eq!(params.security_estimate(), None);
+        assert!(params.check_security(SecurityLevel::Bits128).is_err());
+    }
+
+    #[test]
+    fn prime_power_plaintext_modulus() {
+        let mut rng = thread_rng();
+        // 97^2
+        let mut params = BfvParameters::new(&[60; 3], 9409, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+        let modt = params.plaintext_modulus_op.clone();
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+
+        let m0 = modt.random_vec(params.degree, &mut rng);
+        let m1 = modt.random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        let ct = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        let ct = evaluator.rotate(&ct, 1, &ek);
+        let res_m = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default());
+
+        let mut m = m0.clone();
+        modt.mul_mod_fast_vec(&mut m, &m1);
+        // rotate each row left by 1
+        let row = m.len() / 2;
+        m[..row].rotate_left(1);
+        m[row..].rotate_left(1);
+        assert_eq!(res_m, m);
+
+        // 97 * 193 is not a prime power
+        assert!(BfvParameters::try_with_moduli(
+            evaluator.params().ciphertext_moduli.as_slice(),
+            evaluator.params().extension_moduli.as_slice(),
+            97 * 193,
+            1 << 4
+        )
+        .is_err());
+    }
 
     #[test]
     fn trial() {
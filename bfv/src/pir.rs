@@ -0,0 +1,355 @@
+use crate::error::{check_level, check_poly_count, BfvError};
+use crate::{
+    BfvParameters, Ciphertext, Encoding, EvaluationKey, Evaluator, Plaintext, PolyCache, PolyType,
+    Representation, SecretKey,
+};
+use itertools::{izip, Itertools};
+use ndarray::Array2;
+use rand::{CryptoRng, RngCore};
+
+/// Database for single-server PIR. Each row of at most N values is encoded (SIMD) into a
+/// plaintext and rows are arranged as hypercube with `dimensions`, such that row
+/// i = i_1 + n_1 * (i_2 + n_2 * (i_3 + ...)).
+///
+/// Query is a single ciphertext with one-hot encoded index of each dimension in its
+/// coefficients that server expands with `Evaluator::expand_query`. First dimension is reduced
+/// with plaintext-ciphertext products and remaining dimensions with ciphertext-ciphertext
+/// products.
+pub struct PirDatabase {
+    pub(crate) rows: Vec<Plaintext>,
+    pub(crate) dimensions: Vec<usize>,
+}
+
+impl PirDatabase {
+    /// Encodes `rows` into database with `dimensions`.
+    ///
+    /// Panics if product of dimensions is smaller than no. of rows or sum of dimensions
+    /// exceeds N.
+    pub fn new(rows: &[Vec<u64>], dimensions: &[usize], params: &BfvParameters) -> PirDatabase {
+        PirDatabase::try_new(rows, dimensions, params).unwrap()
+    }
+
+    pub fn try_new(
+        rows: &[Vec<u64>],
+        dimensions: &[usize],
+        params: &BfvParameters,
+    ) -> Result<PirDatabase, BfvError> {
+        check_dimensions(dimensions, params.degree)?;
+        let capacity = dimensions.iter().product::<usize>();
+        if rows.is_empty() || rows.len() > capacity {
+            return Err(BfvError::InvalidParameters(format!(
+                "no. of rows {} not in range [1, {capacity}]",
+                rows.len()
+            )));
+        }
+
+        let rows = rows
+            .iter()
+            .map(|row| {
+                Plaintext::try_encode(row, params, Encoding::simd(0, PolyCache::Mul(PolyType::Q)))
+            })
+            .collect::<Result<Vec<_>, BfvError>>()?;
+
+        Ok(PirDatabase {
+            rows,
+            dimensions: dimensions.to_vec(),
+        })
+    }
+
+    pub fn dimensions(&self) -> &[usize] {
+        &self.dimensions
+    }
+
+    /// Computes encrypted row selected by `query`. Requires galois keys for
+    /// `PirClient::galois_elements` and, if database has more than one dimension,
+    /// relinearization key at level 0.
+    pub fn answer(
+        &self,
+        evaluator: &Evaluator,
+        query: &Ciphertext,
+        ek: &EvaluationKey,
+    ) -> Ciphertext {
+        self.try_answer(evaluator, query, ek).unwrap()
+    }
+
+    pub fn try_answer(
+        &self,
+        evaluator: &Evaluator,
+        query: &Ciphertext,
+        ek: &EvaluationKey,
+    ) -> Result<Ciphertext, BfvError> {
+        check_level(0, query.level)?;
+        let mut selectors =
+            evaluator.try_expand_query(query, expansion_log_count(&self.dimensions), ek)?;
+
+        // First dimension: Σ_i row_(i + n_1*r) * selector_i for each r
+        let n1 = self.dimensions[0];
+        let mut first = selectors.drain(..n1).collect_vec();
+        first.iter_mut().for_each(|ct| {
+            evaluator.ciphertext_change_representation(ct, Representation::Evaluation)
+        });
+        let mut cts = self
+            .rows
+            .chunks(n1)
+            .map(|rows| inner_product_lazy(evaluator, &first[..rows.len()], rows))
+            .collect::<Result<Vec<_>, BfvError>>()?;
+
+        // Remaining dimensions: Σ_i ct_(i + n_k*r) * selector_i for each r
+        for n in self.dimensions[1..].iter() {
+            // `mul_lazy` is cheaper with rhs in coefficient form
+            let mut dimension_selectors = selectors.drain(..*n).collect_vec();
+            dimension_selectors.iter_mut().for_each(|ct| {
+                evaluator.ciphertext_change_representation(ct, Representation::Coefficient)
+            });
+            cts = cts
+                .chunks(*n)
+                .map(|chunk| {
+                    let mut sum = evaluator.try_mul_lazy(&chunk[0], &dimension_selectors[0])?;
+                    for (ct, selector) in izip!(chunk.iter(), dimension_selectors.iter()).skip(1) {
+                        evaluator
+                            .try_add_assign(&mut sum, &evaluator.try_mul_lazy(ct, selector)?)?;
+                    }
+                    let ct = evaluator.try_scale_and_round(&mut sum)?;
+                    evaluator.try_relinearize(&ct, ek)
+                })
+                .collect::<Result<Vec<_>, BfvError>>()?;
+        }
+
+        Ok(cts.swap_remove(0))
+    }
+}
+
+/// Client of single-server PIR for database with `dimensions`
+pub struct PirClient {
+    pub(crate) dimensions: Vec<usize>,
+}
+
+impl PirClient {
+    pub fn new(dimensions: &[usize]) -> PirClient {
+        PirClient {
+            dimensions: dimensions.to_vec(),
+        }
+    }
+
+    /// Returns galois elements of keys required by server to expand the query
+    pub fn galois_elements(&self, degree: usize) -> Vec<usize> {
+        EvaluationKey::query_expansion_galois_elements(
+            degree,
+            expansion_log_count(&self.dimensions),
+        )
+    }
+
+    /// Generates query for row at `index`
+    pub fn query<R: CryptoRng + RngCore>(
+        &self,
+        index: usize,
+        evaluator: &Evaluator,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> Ciphertext {
+        self.try_query(index, evaluator, sk, rng).unwrap()
+    }
+
+    pub fn try_query<R: CryptoRng + RngCore>(
+        &self,
+        index: usize,
+        evaluator: &Evaluator,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> Result<Ciphertext, BfvError> {
+        let params = evaluator.params();
+        check_dimensions(&self.dimensions, params.degree)?;
+        let capacity = self.dimensions.iter().product::<usize>();
+        if index >= capacity {
+            return Err(BfvError::InvalidParameters(format!(
+                "index {index} exceeds database capacity {capacity}"
+            )));
+        }
+
+        // Expansion multiplies values by 2^l
+        let modt = &params.plaintext_modulus_op;
+        let value = modt.inv(1 << expansion_log_count(&self.dimensions));
+
+        let mut m = vec![0u64; params.degree];
+        let mut offset = 0;
+        let mut index = index;
+        for n in self.dimensions.iter() {
+            m[offset + index % n] = value;
+            index /= n;
+            offset += n;
+        }
+
+        evaluator.try_encrypt(
+            sk,
+            &evaluator.try_plaintext_encode(&m, Encoding::poly(0, PolyCache::None))?,
+            rng,
+        )
+    }
+
+    /// Decrypts and decodes row from server's answer
+    pub fn decode(&self, answer: &Ciphertext, evaluator: &Evaluator, sk: &SecretKey) -> Vec<u64> {
+        self.try_decode(answer, evaluator, sk).unwrap()
+    }
+
+    pub fn try_decode(
+        &self,
+        answer: &Ciphertext,
+        evaluator: &Evaluator,
+        sk: &SecretKey,
+    ) -> Result<Vec<u64>, BfvError> {
+        evaluator.try_plaintext_decode(
+            &evaluator.try_decrypt(sk, answer)?,
+            Encoding::simd(answer.level, PolyCache::None),
+        )
+    }
+}
+
+/// Returns l such that query with all dimensions expands into 2^l ciphertexts
+fn expansion_log_count(dimensions: &[usize]) -> usize {
+    dimensions.iter().sum::<usize>().next_power_of_two().ilog2() as usize
+}
+
+fn check_dimensions(dimensions: &[usize], degree: usize) -> Result<(), BfvError> {
+    if dimensions.is_empty() || dimensions.contains(&0) {
+        return Err(BfvError::InvalidParameters(
+            "PIR dimensions must be non-empty and non-zero".to_string(),
+        ));
+    }
+    let total = dimensions.iter().sum::<usize>();
+    if total > degree {
+        return Err(BfvError::InvalidMessageLength {
+            max: degree,
+            found: total,
+        });
+    }
+    Ok(())
+}
+
+/// Computes Σ ct_i * pt_i. Products are accumulated without modular reduction in u128 and
+/// reduced only when the next product may overflow.
+fn inner_product_lazy(
+    evaluator: &Evaluator,
+    cts: &[Ciphertext],
+    pts: &[Plaintext],
+) -> Result<Ciphertext, BfvError> {
+    let level = cts[0].level;
+    let ctx = evaluator.params().poly_ctx(&PolyType::Q, level);
+    let max_bits = ctx
+        .iter_moduli_ops()
+        .map(|modqi| 64 - modqi.modulus().leading_zeros() as usize)
+        .max()
+        .unwrap();
+    let max_lazy = 1usize << std::cmp::min(128 - 2 * max_bits, 32);
+
+    let mut acc = vec![Array2::<u128>::zeros((ctx.moduli_count(), ctx.degree())); 2];
+    for (count, (ct, pt)) in izip!(cts.iter(), pts.iter()).enumerate() {
+        check_poly_count(2, ct.c.len())?;
+        check_level(level, ct.level)?;
+        check_level(level, pt.try_level()?)?;
+        let pt_poly = pt.try_mul_poly_ref()?;
+
+        if count != 0 && count % max_lazy == 0 {
+            acc.iter_mut().for_each(|acc| {
+                izip!(acc.outer_iter_mut(), ctx.iter_moduli_ops()).for_each(|(mut row, modqi)| {
+                    row.iter_mut()
+                        .for_each(|v| *v = modqi.barret_reduction_u128(*v) as u128);
+                });
+            });
+        }
+
+        izip!(acc.iter_mut(), ct.c.iter()).for_each(|(acc, p)| {
+            izip!(
+                acc.iter_mut(),
+                p.coefficients.iter(),
+                pt_poly.coefficients.iter()
+            )
+            .for_each(|(acc, c, m)| {
+                *acc += *c as u128 * *m as u128;
+            });
+        });
+    }
+
+    let c = acc
+        .iter()
+        .map(|acc| {
+            let coefficients = Array2::from_shape_vec(
+                acc.dim(),
+                izip!(acc.outer_iter(), ctx.iter_moduli_ops())
+                    .flat_map(|(row, modqi)| {
+                        row.iter()
+                            .map(|v| modqi.barret_reduction_u128(*v))
+                            .collect_vec()
+                    })
+                    .collect_vec(),
+            )
+            .unwrap();
+            ctx.new(coefficients, Representation::Evaluation)
+        })
+        .collect_vec();
+
+    Ok(Ciphertext {
+        c,
+        poly_type: PolyType::Q,
+        level,
+        seed: None,
+        #[cfg(feature = "noise-estimate")]
+        noise: cts.iter().fold(f64::NEG_INFINITY, |noise, ct| {
+            let params = evaluator.params();
+            params.noise_add(noise, params.noise_mul_plaintext(ct.noise))
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn pir_retrieves_rows() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(4, 1 << 4);
+        let degree = params.degree;
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        // last chunk of first dimension is partial with rows_count 5 and dimensions [4, 4]
+        for (dimensions, rows_count) in [
+            (vec![16], 11),
+            (vec![4, 4], 13),
+            (vec![4, 4], 5),
+            (vec![2, 4, 2], 16),
+        ] {
+            let rows = (0..rows_count)
+                .map(|_| {
+                    evaluator
+                        .params()
+                        .plaintext_modulus_op
+                        .random_vec(degree, &mut rng)
+                })
+                .collect_vec();
+            let db = PirDatabase::new(&rows, &dimensions, evaluator.params());
+
+            let client = PirClient::new(&dimensions);
+            let exponents = client.galois_elements(degree);
+            let ek = EvaluationKey::new_with_galois_elements(
+                evaluator.params(),
+                &sk,
+                &[0],
+                &vec![0; exponents.len()],
+                &exponents,
+                &mut rng,
+            );
+
+            let indices = [0, rows_count - 1, rows_count / 2]
+                .into_iter()
+                .chain((0..3).map(|_| rng.gen_range(0..rows_count)))
+                .collect_vec();
+            for index in indices {
+                let query = client.query(index, &evaluator, &sk, &mut rng);
+                let answer = db.answer(&evaluator, &query, &ek);
+                assert_eq!(client.decode(&answer, &evaluator, &sk), rows[index]);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{check_level_range, BfvError};
 use crate::poly::{Poly, Representation};
 use crate::{BfvParameters, Ciphertext, PolyType};
 use itertools::Itertools;
@@ -39,6 +40,16 @@ impl Encoding {
             level,
         }
     }
+
+    /// Coefficient encoding. Message values are encoded as coefficients of plaintext polynomial,
+    /// thus plaintext multiplication is negacyclic convolution of messages.
+    pub fn poly(level: usize, poly_cache: PolyCache) -> Encoding {
+        Encoding {
+            encoding_type: EncodingType::Poly,
+            poly_cache,
+            level,
+        }
+    }
 }
 
 impl Default for Encoding {
@@ -60,11 +71,50 @@ pub struct Plaintext {
 }
 
 impl Plaintext {
+    /// Scales and rounds `m` = [c0 + c1*s + ...]_Q (in `Coefficient` representation) at `level`
+    /// by t/Q to plaintext
+    pub(crate) fn from_decrypted_poly(m: &Poly, level: usize, params: &BfvParameters) -> Plaintext {
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+        let m = ctx.scale_and_round_decryption(
+            m,
+            &params.plaintext_modulus_op,
+            params.max_bit_size_by2,
+            &params.t_ql_hat_inv_modql_divql_modt[level],
+            &params.t_bql_hat_inv_modql_divql_modt[level],
+            &params.t_ql_hat_inv_modql_divql_frac[level],
+            &params.t_bql_hat_inv_modql_divql_frac[level],
+        );
+        Plaintext {
+            m,
+            encoding: None,
+            mul_poly: None,
+            add_sub_poly: None,
+        }
+    }
+
     /// Encodes a given message `m` to plaintext using given `encoding`
     ///
     /// Panics if `m` values length is greater than polynomial degree
     pub fn encode(m: &[u64], params: &BfvParameters, encoding: Encoding) -> Plaintext {
-        assert!(m.len() <= params.degree);
+        Plaintext::try_encode(m, params, encoding).unwrap()
+    }
+
+    /// Encodes a given message `m` to plaintext using given `encoding`
+    ///
+    /// Returns error if `m` values length is greater than polynomial degree or encoding level
+    /// is greater than max level
+    pub fn try_encode(
+        m: &[u64],
+        params: &BfvParameters,
+        encoding: Encoding,
+    ) -> Result<Plaintext, BfvError> {
+        if m.len() > params.degree {
+            return Err(BfvError::InvalidMessageLength {
+                max: params.degree,
+                found: m.len(),
+            });
+        }
+        check_level_range(encoding.level, 0, params.max_level)?;
 
         let mut m1 = vec![0u64; params.degree];
         let mut m = m.to_vec();
@@ -111,12 +161,12 @@ impl Plaintext {
             }
         };
 
-        Plaintext {
+        Ok(Plaintext {
             m: m1,
             encoding: Some(encoding),
             mul_poly: mul_poly,
             add_sub_poly: add_sub_poly,
-        }
+        })
     }
 
     pub fn decode<T: Zero + Clone + FromPrimitive>(
@@ -124,7 +174,20 @@ impl Plaintext {
         encoding: Encoding,
         params: &BfvParameters,
     ) -> Vec<T> {
-        assert!(self.encoding.is_none());
+        self.try_decode(encoding, params).unwrap()
+    }
+
+    /// Decodes plaintext obtained by decryption using `encoding`
+    ///
+    /// Returns error if plaintext is not output of decryption
+    pub fn try_decode<T: Zero + Clone + FromPrimitive>(
+        &self,
+        encoding: Encoding,
+        params: &BfvParameters,
+    ) -> Result<Vec<T>, BfvError> {
+        if self.encoding.is_some() {
+            return Err(BfvError::EncodedPlaintext);
+        }
 
         let mut m1 = self.m.clone();
         if encoding.encoding_type == EncodingType::Simd {
@@ -140,7 +203,69 @@ impl Plaintext {
             }
         }
 
-        m
+        Ok(m)
+    }
+
+    /// Encodes signed message `m` to plaintext using given `encoding`. Values are mapped from
+    /// centered representation (-t/2, t/2] to [0, t).
+    ///
+    /// Panics if any value is outside of (-t/2, t/2] or `m` values length is greater than
+    /// polynomial degree
+    pub fn encode_signed(m: &[i64], params: &BfvParameters, encoding: Encoding) -> Plaintext {
+        Plaintext::try_encode_signed(m, params, encoding).unwrap()
+    }
+
+    pub fn try_encode_signed(
+        m: &[i64],
+        params: &BfvParameters,
+        encoding: Encoding,
+    ) -> Result<Plaintext, BfvError> {
+        let t = params.plaintext_modulus as i64;
+        let (min, max) = (-(t - 1) / 2, t / 2);
+
+        let m = m
+            .iter()
+            .map(|v| {
+                if *v < min || *v > max {
+                    Err(BfvError::ValueOutOfRange {
+                        value: *v,
+                        min,
+                        max,
+                    })
+                } else if *v < 0 {
+                    Ok((t + *v) as u64)
+                } else {
+                    Ok(*v as u64)
+                }
+            })
+            .collect::<Result<Vec<u64>, BfvError>>()?;
+
+        Plaintext::try_encode(&m, params, encoding)
+    }
+
+    /// Decodes plaintext obtained by decryption using `encoding` to signed values in centered
+    /// representation (-t/2, t/2]
+    pub fn decode_signed(&self, encoding: Encoding, params: &BfvParameters) -> Vec<i64> {
+        self.try_decode_signed(encoding, params).unwrap()
+    }
+
+    pub fn try_decode_signed(
+        &self,
+        encoding: Encoding,
+        params: &BfvParameters,
+    ) -> Result<Vec<i64>, BfvError> {
+        let t = params.plaintext_modulus;
+        Ok(self
+            .try_decode::<u64>(encoding, params)?
+            .into_iter()
+            .map(|v| {
+                if v > t / 2 {
+                    v as i64 - t as i64
+                } else {
+                    v as i64
+                }
+            })
+            .collect())
     }
 
     /// Returns message polynomial `m` scaled by Q/t
@@ -181,16 +306,31 @@ impl Plaintext {
     }
 
     pub fn mul_poly_type(&self) -> PolyType {
-        match &self.encoding.as_ref().unwrap().poly_cache {
-            PolyCache::Mul(poly_type) => poly_type.clone(),
-            _ => {
-                panic!("PolyCache not Mul")
-            }
+        self.try_mul_poly_type().unwrap()
+    }
+
+    pub fn try_mul_poly_type(&self) -> Result<PolyType, BfvError> {
+        match &self
+            .encoding
+            .as_ref()
+            .ok_or(BfvError::EncodingMissing)?
+            .poly_cache
+        {
+            PolyCache::Mul(poly_type) | PolyCache::All(poly_type, _) => Ok(poly_type.clone()),
+            _ => Err(BfvError::MulPolyMissing),
         }
     }
 
     pub fn level(&self) -> usize {
-        self.encoding.as_ref().unwrap().level
+        self.try_level().unwrap()
+    }
+
+    pub fn try_level(&self) -> Result<usize, BfvError> {
+        Ok(self
+            .encoding
+            .as_ref()
+            .ok_or(BfvError::EncodingMissing)?
+            .level)
     }
 
     pub fn supports_mul_poly(&self) -> bool {
@@ -201,10 +341,20 @@ impl Plaintext {
         self.add_sub_poly.as_ref().expect("Missing add_sub poly")
     }
 
+    pub fn try_add_sub_poly_ref(&self) -> Result<&Poly, BfvError> {
+        self.add_sub_poly
+            .as_ref()
+            .ok_or(BfvError::AddSubPolyMissing)
+    }
+
     pub fn mul_poly_ref(&self) -> &Poly {
         self.mul_poly.as_ref().expect("Missing mul poly")
     }
 
+    pub fn try_mul_poly_ref(&self) -> Result<&Poly, BfvError> {
+        self.mul_poly.as_ref().ok_or(BfvError::MulPolyMissing)
+    }
+
     pub fn move_mul_poly(self) -> Poly {
         self.mul_poly.expect("Missing mul poly")
     }
@@ -217,43 +367,129 @@ impl Plaintext {
 impl TryEncodingWithParameters<&[u32]> for Plaintext {
     type Encoding = Encoding;
     type Parameters = BfvParameters;
+    type Error = BfvError;
 
     fn try_encoding_with_parameters(
         value: &[u32],
         parameters: &Self::Parameters,
         encoding: Self::Encoding,
-    ) -> Self {
+    ) -> Result<Self, Self::Error> {
         let value_u64 = value.iter().map(|v| *v as u64).collect_vec();
-        Self::encode(&value_u64, parameters, encoding)
+        Self::try_encode(&value_u64, parameters, encoding)
     }
 }
 
 impl<'a> TryEncodingWithParameters<ArrayView1<'a, u32>> for Plaintext {
     type Encoding = Encoding;
     type Parameters = BfvParameters;
+    type Error = BfvError;
 
     fn try_encoding_with_parameters(
         value: ArrayView1<'a, u32>,
         parameters: &Self::Parameters,
         encoding: Self::Encoding,
-    ) -> Self {
+    ) -> Result<Self, Self::Error> {
         let value_u64 = value.iter().map(|v| *v as u64).collect_vec();
-        Self::encode(&value_u64, parameters, encoding)
+        Self::try_encode(&value_u64, parameters, encoding)
     }
 }
 
 impl<'a> TryDecodingWithParameters<&'a Plaintext> for Vec<u32> {
     type Encoding = Encoding;
     type Parameters = &'a BfvParameters;
+    type Error = BfvError;
+
+    fn try_decoding_with_parameters(
+        value: &'a Plaintext,
+        parameters: Self::Parameters,
+        encoding: Self::Encoding,
+    ) -> Result<Vec<u32>, Self::Error> {
+        value.try_decode(encoding, parameters)
+    }
+}
+
+impl TryEncodingWithParameters<&[i64]> for Plaintext {
+    type Encoding = Encoding;
+    type Parameters = BfvParameters;
+    type Error = BfvError;
+
+    fn try_encoding_with_parameters(
+        value: &[i64],
+        parameters: &Self::Parameters,
+        encoding: Self::Encoding,
+    ) -> Result<Self, Self::Error> {
+        Self::try_encode_signed(value, parameters, encoding)
+    }
+}
+
+impl<'a> TryDecodingWithParameters<&'a Plaintext> for Vec<i64> {
+    type Encoding = Encoding;
+    type Parameters = &'a BfvParameters;
+    type Error = BfvError;
 
     fn try_decoding_with_parameters(
         value: &'a Plaintext,
         parameters: Self::Parameters,
         encoding: Self::Encoding,
-    ) -> Vec<u32> {
-        value.decode(encoding, parameters)
+    ) -> Result<Vec<i64>, Self::Error> {
+        value.try_decode_signed(encoding, parameters)
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::{Evaluator, SecretKey};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn signed_encoding_decoding() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let t = params.plaintext_modulus as i64;
+        let (min, max) = (-(t - 1) / 2, t / 2);
+
+        for encoding in [
+            Encoding::simd(0, PolyCache::None),
+            Encoding::poly(0, PolyCache::None),
+        ] {
+            let mut m = (0..params.degree - 2)
+                .map(|_| rng.gen_range(min..=max))
+                .collect_vec();
+            m.extend_from_slice(&[min, max]);
+
+            let pt = Plaintext::encode_signed(&m, &params, encoding.clone());
+            let ct = sk.encrypt(&params, &pt, &mut rng);
+            let rm = sk.decrypt(&ct, &params).decode_signed(encoding, &params);
+            assert_eq!(rm, m);
+        }
+
+        // since t is odd, negated values are in range too
+        let m = (0..params.degree)
+            .map(|_| rng.gen_range(min..=max))
+            .collect_vec();
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode_signed(&m, Encoding::default()),
+            &mut rng,
+        );
+        let rm = evaluator.plaintext_decode_signed(
+            &evaluator.decrypt(&sk, &evaluator.negate(&ct)),
+            Encoding::default(),
+        );
+        assert_eq!(rm, m.iter().map(|v| -v).collect_vec());
+
+        // overflow
+        assert!(matches!(
+            Plaintext::try_encode_signed(&[max + 1], evaluator.params(), Encoding::default()),
+            Err(BfvError::ValueOutOfRange { .. })
+        ));
+        assert!(matches!(
+            Plaintext::try_encode_signed(&[min - 1], evaluator.params(), Encoding::default()),
+            Err(BfvError::ValueOutOfRange { .. })
+        ));
+    }
+}
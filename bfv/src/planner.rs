@@ -0,0 +1,342 @@
+use crate::error::BfvError;
+use crate::nb_theory::{generate_prime, try_generate_primes_vec};
+use crate::noise::NoiseModel;
+use crate::parameters::{DEFAULT_VARIANCE, MAX_PRIME_BITS, MIN_PRIME_BITS, SPECIAL_MODULI_COUNT};
+use crate::{BfvParameters, SecurityLevel};
+
+/// Polynomial degrees specified by HE standard
+const DEGREES: [usize; 6] = [1 << 10, 1 << 11, 1 << 12, 1 << 13, 1 << 14, 1 << 15];
+/// Ciphertext moduli count must be at least 2 since key switching is not supported at the last
+/// level
+const MIN_MODULI_COUNT: usize = 2;
+
+/// Description of a circuit used to select parameters with `CircuitProfile::plan`.
+///
+/// Circuit starts with ciphertexts encrypted with public key and consists of `depth + 1` levels
+/// separated by a ciphertext multiplication followed by relinearization. At each level the
+/// ciphertext is multiplied with `plaintext_muls_per_level` plaintexts (products are summed) and
+/// then rotated `rotations_per_level` times (each rotation is added to the ciphertext, as in
+/// `inner_sum`). All operations are at level 0, ie modulus switching is not assumed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitProfile {
+    /// No. of sequential ciphertext multiplications
+    pub depth: usize,
+    pub rotations_per_level: usize,
+    pub plaintext_muls_per_level: usize,
+    pub plaintext_modulus_bits: usize,
+    pub security_level: SecurityLevel,
+}
+
+/// Parameters selected by `CircuitProfile::plan`
+#[derive(Debug)]
+pub struct ParameterPlan {
+    /// Parameters with hybrid key switching enabled
+    pub params: BfvParameters,
+    /// Expected noise at the end of each level of the circuit, from first to last
+    pub levels: Vec<LevelNoiseReport>,
+}
+
+impl ParameterPlan {
+    /// Smallest noise margin across levels of the circuit
+    pub fn min_margin(&self) -> f64 {
+        self.levels
+            .iter()
+            .map(|report| report.margin())
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Estimated noise of the circuit after a level, in bits
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelNoiseReport {
+    /// No. of ciphertext multiplications before the level
+    pub depth: usize,
+    /// Estimated noise (see `BfvParameters::noise_bound`)
+    pub noise: f64,
+    /// Maximum noise that decrypts correctly
+    pub bound: f64,
+}
+
+impl LevelNoiseReport {
+    /// Bits of noise that can still be added before decryption fails
+    pub fn margin(&self) -> f64 {
+        self.bound - self.noise
+    }
+}
+
+/// Bits in moduli that noise formulas depend on
+struct ModuliBits {
+    log2_q: f64,
+    /// Bits in the largest digit Q_j of hybrid key switching
+    log2_qj: f64,
+    log2_p: f64,
+    dnum: usize,
+}
+
+impl CircuitProfile {
+    /// Returns parameters of the smallest degree, and for it the smallest log(QP), that satisfy
+    /// security level and for which estimated noise of the circuit is within the bound at every
+    /// level.
+    ///
+    /// Ciphertext and special moduli are primes of equal size (as in `BfvParameters::preset`)
+    /// and plaintext modulus is the largest prime of `plaintext_modulus_bits` bits that supports
+    /// SIMD encoding. Returns error if no such parameters exist.
+    pub fn plan(&self) -> Result<ParameterPlan, BfvError> {
+        if self.plaintext_modulus_bits < 2 || self.plaintext_modulus_bits >= MAX_PRIME_BITS {
+            return Err(BfvError::InvalidParameters(format!(
+                "plaintext modulus bits {} not in range [2, {})",
+                self.plaintext_modulus_bits, MAX_PRIME_BITS
+            )));
+        }
+
+        for degree in DEGREES {
+            let Some(max_log_qp) = self.security_level.max_log_qp(degree) else {
+                continue;
+            };
+            // plaintext modulus must be congruent to 1 mod 2N for SIMD encoding
+            let Some(plaintext_modulus) = generate_prime(
+                self.plaintext_modulus_bits,
+                2 * degree as u64,
+                1 << self.plaintext_modulus_bits,
+            ) else {
+                continue;
+            };
+            let model = NoiseModel {
+                degree,
+                log2_t: (plaintext_modulus as f64).log2(),
+                variance: DEFAULT_VARIANCE,
+            };
+
+            let mut candidates = (MIN_MODULI_COUNT..=max_log_qp / MIN_PRIME_BITS)
+                .flat_map(|count| {
+                    (MIN_PRIME_BITS.max(self.plaintext_modulus_bits + 1)..=MAX_PRIME_BITS)
+                        .map(move |bits| (count, bits))
+                })
+                .filter(|(count, bits)| (count + SPECIAL_MODULI_COUNT) * bits <= max_log_qp)
+                .filter(|(count, bits)| {
+                    // primes of `bits` bits are at least 2^(bits - 1)
+                    let moduli = ModuliBits {
+                        log2_q: (count * (bits - 1)) as f64,
+                        log2_qj: (count.min(&SPECIAL_MODULI_COUNT) * bits) as f64,
+                        log2_p: (SPECIAL_MODULI_COUNT * (bits - 1)) as f64,
+                        dnum: count.div_ceil(SPECIAL_MODULI_COUNT),
+                    };
+                    self.fits(&self.noise_report(&model, &moduli))
+                })
+                .collect::<Vec<_>>();
+            candidates.sort_by_key(|(count, bits)| ((count + SPECIAL_MODULI_COUNT) * bits, *count));
+
+            for (count, bits) in candidates {
+                // there may not be enough small primes for large degrees
+                let Ok(params) = generate_params(degree, plaintext_modulus, count, bits) else {
+                    continue;
+                };
+                if params.check_security(self.security_level).is_err() {
+                    continue;
+                }
+
+                // estimate again with generated moduli
+                let levels = self.noise_report(&model, &ModuliBits::new(&params));
+                if self.fits(&levels) {
+                    return Ok(ParameterPlan { params, levels });
+                }
+            }
+        }
+
+        Err(BfvError::InvalidParameters(format!(
+            "no parameters satisfy {:?} for circuit of depth {} with {} bit plaintext modulus",
+            self.security_level, self.depth, self.plaintext_modulus_bits
+        )))
+    }
+
+    /// Estimates noise of the circuit after each level. Same as evaluating noise with
+    /// `BfvParameters::noise_*` methods at level 0.
+    fn noise_report(&self, model: &NoiseModel, moduli: &ModuliBits) -> Vec<LevelNoiseReport> {
+        let bound = moduli.log2_q - 1.0 - model.log2_t;
+        let key_switch = |noise: f64| {
+            model.key_switch(
+                noise,
+                moduli.dnum,
+                SPECIAL_MODULI_COUNT,
+                moduli.log2_qj,
+                moduli.log2_p,
+            )
+        };
+
+        let mut noise = model.fresh_pk();
+        (0..=self.depth)
+            .map(|depth| {
+                if depth != 0 {
+                    noise = key_switch(model.mul(noise, noise, moduli.log2_q));
+                }
+                if self.plaintext_muls_per_level != 0 {
+                    let product = model.mul_plaintext(noise);
+                    noise = (1..self.plaintext_muls_per_level)
+                        .fold(product, |sum, _| model.add(sum, product));
+                }
+                for _ in 0..self.rotations_per_level {
+                    noise = model.add(noise, key_switch(noise));
+                }
+                LevelNoiseReport {
+                    depth,
+                    noise,
+                    bound,
+                }
+            })
+            .collect()
+    }
+
+    fn fits(&self, levels: &[LevelNoiseReport]) -> bool {
+        levels.iter().all(|report| report.margin() > 0.0)
+    }
+}
+
+/// Same as `BfvParameters::new` with `count` ciphertext moduli of `bits` bits followed by
+/// `enable_hybrid_key_switching` with special moduli of `bits` bits
+fn generate_params(
+    degree: usize,
+    plaintext_modulus: u64,
+    count: usize,
+    bits: usize,
+) -> Result<BfvParameters, BfvError> {
+    let ciphertext_moduli = try_generate_primes_vec(&vec![bits; count], degree, &[])?;
+    let extension_moduli = try_generate_primes_vec(&vec![bits; count], degree, &ciphertext_moduli)?;
+    let special_moduli =
+        try_generate_primes_vec(&[bits; SPECIAL_MODULI_COUNT], degree, &ciphertext_moduli)?;
+
+    let mut params = BfvParameters::try_with_moduli(
+        &ciphertext_moduli,
+        &extension_moduli,
+        plaintext_modulus,
+        degree,
+    )?;
+    params.try_enable_hybrid_key_switching_with_moduli(&[
+        special_moduli[0],
+        special_moduli[1],
+        special_moduli[2],
+    ])?;
+    Ok(params)
+}
+
+impl ModuliBits {
+    fn new(params: &BfvParameters) -> ModuliBits {
+        let log2 = |moduli: &[u64]| moduli.iter().map(|qi| (*qi as f64).log2()).sum::<f64>();
+        let ksk_params = params.hybrid_key_switching_params_at_level(0);
+        ModuliBits {
+            log2_q: log2(&params.ciphertext_moduli),
+            log2_qj: params
+                .ciphertext_moduli
+                .chunks(ksk_params.alpha)
+                .map(log2)
+                .fold(0.0, f64::max),
+            log2_p: log2(params.special_moduli.as_ref().unwrap()),
+            dnum: ksk_params.dnum,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Encoding, EvaluationKey, Evaluator, PolyCache, PolyType, PublicKey, Representation,
+        SecretKey,
+    };
+    use rand::thread_rng;
+
+    #[test]
+    fn planned_parameters_evaluate_circuit() {
+        let profile = CircuitProfile {
+            depth: 1,
+            rotations_per_level: 1,
+            plaintext_muls_per_level: 1,
+            plaintext_modulus_bits: 17,
+            security_level: SecurityLevel::Bits128,
+        };
+        let plan = profile.plan().unwrap();
+        let params = &plan.params;
+        assert_eq!(plan.levels.len(), 2);
+        assert!(plan.min_margin() > 0.0);
+        assert_eq!(params.security_estimate(), Some(SecurityLevel::Bits128));
+        assert_eq!(64 - params.plaintext_modulus.leading_zeros(), 17);
+
+        // report matches noise formulas of parameters
+        let mut noise = params.noise_mul_plaintext(params.noise_fresh_pk());
+        noise = params.noise_add(noise, params.noise_key_switch(noise, 0));
+        assert!((plan.levels[0].noise - noise).abs() < 1e-9);
+        assert_eq!(plan.levels[0].bound, params.noise_bound(0));
+
+        let mut rng = thread_rng();
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let pk = PublicKey::new(params, &sk, 0, &mut rng);
+        let ek = EvaluationKey::new(params, &sk, &[0], &[0], &[1], &mut rng);
+        let evaluator = Evaluator::new(plan.params);
+        let params = evaluator.params();
+        let modt = &params.plaintext_modulus_op;
+
+        let m = modt.random_vec(params.degree, &mut rng);
+        let pt = evaluator.plaintext_encode(&m, Encoding::simd(0, PolyCache::Mul(PolyType::Q)));
+        let mut ct = evaluator.encrypt_pk(
+            &pk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        for depth in 0..=profile.depth {
+            if depth != 0 {
+                ct = evaluator.relinearize(&evaluator.mul(&ct, &ct), &ek);
+            }
+            evaluator.ciphertext_change_representation(&mut ct, Representation::Evaluation);
+            ct = evaluator.mul_plaintext(&ct, &pt);
+            evaluator.ciphertext_change_representation(&mut ct, Representation::Coefficient);
+            ct = evaluator.add(&ct, &evaluator.rotate(&ct, 1, &ek));
+
+            let budget = evaluator.noise_budget(&sk, &ct) as f64;
+            assert!(budget >= plan.levels[depth].margin() - 1.0);
+        }
+        assert!(evaluator.noise_budget(&sk, &ct) > 0);
+
+        // expected: ((m*m + rot(m*m)) ^ 2 * m + rot(..)
+        let rotate = |v: &[u64]| {
+            let half = v.len() / 2;
+            let mut rotated = v.to_vec();
+            rotated[..half].rotate_left(1);
+            rotated[half..].rotate_left(1);
+            rotated
+        };
+        let mut expected = m.clone();
+        for depth in 0..=profile.depth {
+            if depth != 0 {
+                let e = expected.clone();
+                modt.mul_mod_fast_vec(&mut expected, &e);
+            }
+            modt.mul_mod_fast_vec(&mut expected, &m);
+            let rotated = rotate(&expected);
+            modt.add_mod_fast_vec(&mut expected, &rotated);
+        }
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default()),
+            expected
+        );
+    }
+
+    #[test]
+    fn plan_fails_for_infeasible_circuit() {
+        let profile = CircuitProfile {
+            depth: 30,
+            rotations_per_level: 0,
+            plaintext_muls_per_level: 0,
+            plaintext_modulus_bits: 20,
+            security_level: SecurityLevel::Bits256,
+        };
+        assert!(matches!(
+            profile.plan(),
+            Err(BfvError::InvalidParameters(_))
+        ));
+
+        let profile = CircuitProfile {
+            plaintext_modulus_bits: 64,
+            ..profile
+        };
+        assert!(profile.plan().is_err());
+    }
+}
@@ -1,20 +1,60 @@
-use crate::Poly;
 use crate::{
     modulus::Modulus,
     utils::{mod_inverse_biguint_u64, sample_vec_cbd},
     Representation, Substitution,
 };
+use crate::{Poly, Workspace};
 use crypto_bigint::U192;
 use itertools::{izip, Itertools};
-use ndarray::{azip, s, Array2, ArrayView2, Axis, IntoNdProducer};
+use ndarray::{azip, s, Array2, ArrayView2, ArrayViewMut2, Axis, IntoNdProducer};
 use num_bigint::BigUint;
 use num_traits::{identities::One, ToPrimitive, Zero};
 use rand::{CryptoRng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use seq_macro::seq;
 use std::mem::{self, MaybeUninit};
 use traits::Ntt;
 
+/// No. of coefficients processed by a single task in loops over coefficients
+const COEFFICIENT_CHUNK_SIZE: usize = 1024;
+
+/// Max. no. of moduli for which scratch space of basis extensions is allocated on stack
+const STACK_SCRATCH_MODULI_COUNT: usize = 32;
+
+/// Runs `izip!(iters).for_each(f)` over RNS limbs. With `parallel` feature limbs are processed
+/// in parallel, in which case iterators must also implement rayon's `IntoParallelIterator` (use
+/// `limb_moduli_ops` and `limb_ntt_ops` instead of `iter_moduli_ops` and `iter_ntt_ops`).
+macro_rules! for_each_limb {
+    ($f:expr, $($iter:expr),+ $(,)?) => {{
+        #[cfg(not(feature = "parallel"))]
+        izip!($($iter),+).for_each($f);
+        #[cfg(feature = "parallel")]
+        ($($iter),+).into_par_iter().for_each($f);
+    }};
+}
+
+/// Calls `f` with index of the first column and view of each chunk of columns of `coefficients`.
+/// With `parallel` feature chunks are processed in parallel.
+fn for_each_coefficient_chunk<A, F>(mut coefficients: ArrayViewMut2<'_, A>, f: F)
+where
+    A: Send + Sync,
+    F: Fn(usize, ArrayViewMut2<'_, A>) + Send + Sync,
+{
+    #[cfg(not(feature = "parallel"))]
+    coefficients
+        .axis_chunks_iter_mut(Axis(1), COEFFICIENT_CHUNK_SIZE)
+        .enumerate()
+        .for_each(|(i, chunk)| f(i * COEFFICIENT_CHUNK_SIZE, chunk));
+    #[cfg(feature = "parallel")]
+    coefficients
+        .axis_chunks_iter_mut(Axis(1), COEFFICIENT_CHUNK_SIZE)
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(i, chunk)| f(i * COEFFICIENT_CHUNK_SIZE, chunk));
+}
+
 #[derive(PartialEq)]
 pub struct PolyContext<'a, T: Ntt> {
     pub(crate) moduli_ops: (&'a [Modulus], &'a [Modulus]),
@@ -35,6 +75,31 @@ where
         self.ntt_ops.0.iter().chain(self.ntt_ops.1.iter())
     }
 
+    /// Same as `iter_moduli_ops`. Parallel iterator with `parallel` feature.
+    #[cfg(not(feature = "parallel"))]
+    fn limb_moduli_ops(&self) -> impl Iterator<Item = &Modulus> {
+        self.iter_moduli_ops()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn limb_moduli_ops(&self) -> impl IndexedParallelIterator<Item = &Modulus> {
+        self.moduli_ops
+            .0
+            .par_iter()
+            .chain(self.moduli_ops.1.par_iter())
+    }
+
+    /// Same as `iter_ntt_ops`. Parallel iterator with `parallel` feature.
+    #[cfg(not(feature = "parallel"))]
+    fn limb_ntt_ops(&self) -> impl Iterator<Item = &T> {
+        self.iter_ntt_ops()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn limb_ntt_ops(&self) -> impl IndexedParallelIterator<Item = &T> {
+        self.ntt_ops.0.par_iter().chain(self.ntt_ops.1.par_iter())
+    }
+
     pub fn big_q(&self) -> BigUint {
         let mut q = BigUint::one();
         self.iter_moduli_ops().for_each(|qi| {
@@ -135,16 +200,20 @@ where
     pub fn change_representation(&self, poly: &mut Poly, to: Representation) {
         if poly.representation == Representation::Evaluation {
             if to == Representation::Coefficient {
-                izip!(poly.coefficients.outer_iter_mut(), self.iter_ntt_ops()).for_each(
+                for_each_limb!(
                     |(mut coefficients, ntt)| ntt.backward(coefficients.as_slice_mut().unwrap()),
+                    poly.coefficients.outer_iter_mut(),
+                    self.limb_ntt_ops()
                 );
                 poly.representation = Representation::Coefficient;
             } else {
             }
         } else if poly.representation == Representation::Coefficient {
             if to == Representation::Evaluation {
-                izip!(poly.coefficients.outer_iter_mut(), self.iter_ntt_ops()).for_each(
+                for_each_limb!(
                     |(mut coefficients, ntt)| ntt.forward(coefficients.as_slice_mut().unwrap()),
+                    poly.coefficients.outer_iter_mut(),
+                    self.limb_ntt_ops()
                 );
                 poly.representation = Representation::Evaluation;
             } else {
@@ -158,9 +227,18 @@ where
     /// In Evaluation form i must be an odd integer not a multiple of 2*degree.
     /// In Coefficient form i must be an integer not a multiple of 2*degree.
     pub fn substitute(&self, poly: &Poly, subs: &Substitution) -> Poly {
+        let mut p = self.zero(poly.representation.clone());
+        self.substitute_into(poly, subs, &mut p);
+        p
+    }
+
+    /// Same as `substitute` but writes the result into `p`, which must be in the same context as
+    /// `poly`.
+    pub fn substitute_into(&self, poly: &Poly, subs: &Substitution, p: &mut Poly) {
         debug_assert!(subs.exponent % (self.degree * 2) != 0);
         debug_assert!(self.degree == subs.degree);
-        let mut p = self.zero(poly.representation.clone());
+        debug_assert!(p.coefficients.dim() == poly.coefficients.dim());
+        p.representation = poly.representation.clone();
         if poly.representation == Representation::Evaluation {
             debug_assert!(subs.exponent & 1 == 1);
             izip!(
@@ -173,6 +251,7 @@ where
                 });
             });
         } else if poly.representation == Representation::Coefficient {
+            p.coefficients.fill(0);
             let mut exponent = 0;
             let mask = self.degree - 1;
             for j in 0..self.degree {
@@ -194,8 +273,6 @@ where
         } else {
             panic!("Unknown polynomial representation!");
         }
-
-        p
     }
 
     pub fn scale_and_round_decryption(
@@ -217,7 +294,8 @@ where
             .coefficients
             .axis_iter(Axis(1))
             .map(|rests| {
-                let mut rational_sum = 0u64;
+                // accumulate without reduction since xi_lo and xi_hi are not smaller than t
+                let mut rational_sum = 0u128;
                 let mut fractional_sum = 0f64;
 
                 izip!(
@@ -231,15 +309,14 @@ where
                     let xi_hi = xi >> b;
                     let xi_lo = xi - (xi_hi << b);
 
-                    // FIXME: this will fail in debug mode since xi_lo and xi_hi are almost always greater than t (ie plaintext modulus)
-                    rational_sum = t.add_mod_fast(rational_sum, t.mul_mod_fast(xi_lo, *rational));
-                    rational_sum = t.add_mod_fast(rational_sum, t.mul_mod_fast(xi_hi, *brational));
+                    rational_sum += xi_lo as u128 * *rational as u128;
+                    rational_sum += xi_hi as u128 * *brational as u128;
 
                     fractional_sum += xi_lo.to_f64().unwrap() * fractional;
                     fractional_sum += xi_hi.to_f64().unwrap() * bfractional;
                 });
 
-                fractional_sum += rational_sum.to_f64().unwrap();
+                fractional_sum += t.barret_reduction_u128(rational_sum).to_f64().unwrap();
 
                 // round
                 fractional_sum += 0.5;
@@ -265,6 +342,34 @@ where
         to_s_hat_inv_mods_divs_frachi: &[u64],
         to_s_hat_inv_mods_divs_fraclo: &[u64],
     ) -> Poly {
+        let mut o_poly = out_context.zero(Representation::Coefficient);
+        self.scale_and_round_into(
+            pq_poly,
+            out_context,
+            p_context,
+            q_context,
+            to_s_hat_inv_mods_divs_modo,
+            to_s_hat_inv_mods_divs_frachi,
+            to_s_hat_inv_mods_divs_fraclo,
+            &mut o_poly,
+        );
+        o_poly
+    }
+
+    /// Same as `scale_and_round` but writes the result into `o_poly`, which must be in `out_context`
+    // precomputed constants are passed as in `scale_and_round`, plus the output
+    #[allow(clippy::too_many_arguments)]
+    pub fn scale_and_round_into(
+        &self,
+        pq_poly: &Poly,
+        out_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        q_context: &PolyContext<'_, T>,
+        to_s_hat_inv_mods_divs_modo: &Array2<u64>,
+        to_s_hat_inv_mods_divs_frachi: &[u64],
+        to_s_hat_inv_mods_divs_fraclo: &[u64],
+        o_poly: &mut Poly,
+    ) {
         debug_assert!(pq_poly.representation == Representation::Coefficient);
         let mut input_offset = 0;
         let mut output_offset = 0;
@@ -280,66 +385,64 @@ where
             output_size = q_context.moduli_count;
         }
 
-        let degree = out_context.degree;
+        debug_assert!(o_poly.coefficients.dim() == (output_size, out_context.degree));
         let modos = out_context.moduli_ops();
 
-        let mut o_coeffs = Array2::<u64>::uninit((output_size, degree));
-        unsafe {
-            for ri in (0..degree).step_by(8) {
-                seq!(N in 0..8 {
-                    let mut frac~N = U192::ZERO;
-                });
-
-                for i in 0..input_size {
-                    let fhi = *to_s_hat_inv_mods_divs_frachi.get_unchecked(i) as u128;
-                    let flo = *to_s_hat_inv_mods_divs_fraclo.get_unchecked(i) as u128;
-
+        for_each_coefficient_chunk(
+            o_poly.coefficients.view_mut(),
+            |offset, mut o_coeffs| unsafe {
+                for ri in (0..o_coeffs.ncols()).step_by(8) {
+                    let ci = offset + ri;
                     seq!(N in 0..8 {
-                        let xi = *pq_poly.coefficients.uget((i + input_offset, ri+N));
-                        let lo = xi as u128 * flo;
-                        let hi = xi as u128 * fhi + (lo >> 64);
-                        frac~N = frac~N.wrapping_add(&U192::from_words([
-                            lo as u64,
-                            hi as u64,
-                            (hi >> 64) as u64,
-                        ]));
+                        let mut frac~N = U192::ZERO;
                     });
-                }
 
-                seq!(N in 0..8 {
-                    let frac~N = frac~N.shr_vartime(127).as_words()[0] as u128;
-                });
+                    for i in 0..input_size {
+                        let fhi = *to_s_hat_inv_mods_divs_frachi.get_unchecked(i) as u128;
+                        let flo = *to_s_hat_inv_mods_divs_fraclo.get_unchecked(i) as u128;
+
+                        seq!(N in 0..8 {
+                            let xi = *pq_poly.coefficients.uget((i + input_offset, ci+N));
+                            let lo = xi as u128 * flo;
+                            let hi = xi as u128 * fhi + (lo >> 64);
+                            frac~N = frac~N.wrapping_add(&U192::from_words([
+                                lo as u64,
+                                hi as u64,
+                                (hi >> 64) as u64,
+                            ]));
+                        });
+                    }
 
-                for j in 0..output_size {
                     seq!(N in 0..8 {
-                        let mut tmp~N = frac~N;
+                        let frac~N = frac~N.shr_vartime(127).as_words()[0] as u128;
                     });
 
-                    for i in 0..input_size {
-                        let op = *to_s_hat_inv_mods_divs_modo.uget((j, i)) as u128;
-
+                    for j in 0..output_size {
                         seq!(N in 0..8 {
-                            tmp~N += *pq_poly.coefficients.uget((i + input_offset, ri+N)) as u128 * op;
+                            let mut tmp~N = frac~N;
                         });
-                    }
 
-                    let modoj = modos.get_unchecked(j);
+                        for i in 0..input_size {
+                            let op = *to_s_hat_inv_mods_divs_modo.uget((j, i)) as u128;
 
-                    let op = *to_s_hat_inv_mods_divs_modo.uget((j, input_size)) as u128;
+                            seq!(N in 0..8 {
+                                tmp~N += *pq_poly.coefficients.uget((i + input_offset, ci+N)) as u128 * op;
+                            });
+                        }
 
-                    seq!(N in 0..8 {
-                        tmp~N += *pq_poly.coefficients.uget((j + output_offset, ri+N)) as u128 * op;
-                        let pxj = modoj.barret_reduction_u128(tmp~N);
-                        o_coeffs.uget_mut((j, ri+N)).write(pxj);
-                    });
-                }
-            }
-        }
+                        let modoj = modos.get_unchecked(j);
 
-        unsafe {
-            let o_coeffs = o_coeffs.assume_init();
-            return self.new(o_coeffs, Representation::Coefficient);
-        }
+                        let op = *to_s_hat_inv_mods_divs_modo.uget((j, input_size)) as u128;
+
+                        seq!(N in 0..8 {
+                            tmp~N += *pq_poly.coefficients.uget((j + output_offset, ci+N)) as u128 * op;
+                            *o_coeffs.uget_mut((j, ri+N)) = modoj.barret_reduction_u128(tmp~N);
+                        });
+                    }
+                }
+            },
+        );
+        o_poly.representation = Representation::Coefficient;
     }
 
     /// Given a polynomial in context with moduli Q returns a polynomial in context with moduli P by calculating [round(P/Q([poly]_Q))]_P
@@ -361,20 +464,54 @@ where
     ) -> Poly {
         debug_assert!(q_poly.representation == Representation::Coefficient);
 
+        let mut p_poly = p_context.zero(Representation::Coefficient);
+        self.fast_conv_p_over_q_into(
+            &q_poly.coefficients.view(),
+            p_context,
+            neg_pq_hat_inv_modq,
+            neg_pq_hat_inv_modq_shoup,
+            q_inv,
+            q_inv_modp,
+            p_poly.coefficients.view_mut(),
+        );
+        p_poly
+    }
+
+    /// Same as `fast_conv_p_over_q` but takes coefficients of polynomial in `Coefficient`
+    /// representation and writes the result into `p_coefficients`.
+    // precomputed constants are passed as in `fast_conv_p_over_q`, plus the output
+    #[allow(clippy::too_many_arguments)]
+    pub fn fast_conv_p_over_q_into(
+        &self,
+        q_coefficients: &ArrayView2<u64>,
+        p_context: &PolyContext<'_, T>,
+        neg_pq_hat_inv_modq: &[u64],
+        neg_pq_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        q_inv_modp: &Array2<u64>,
+        mut p_coefficients: ArrayViewMut2<'_, u64>,
+    ) {
         let q_size = self.moduli_count;
         let p_size = p_context.moduli_count;
         let degree = self.degree;
+        debug_assert!(q_coefficients.dim() == (q_size, degree));
+        debug_assert!(p_coefficients.dim() == (p_size, degree));
 
         let modqs = self.moduli_ops();
         let modps = p_context.moduli_ops();
 
-        let mut p_coeffs = Array2::<u64>::uninit((p_size, degree));
+        let mut stack_scratch = [0u64; STACK_SCRATCH_MODULI_COUNT * 8];
+        let mut heap_scratch = vec![];
+        let xiv = if q_size <= STACK_SCRATCH_MODULI_COUNT {
+            &mut stack_scratch[..q_size * 8]
+        } else {
+            heap_scratch.resize(q_size * 8, 0u64);
+            &mut heap_scratch[..]
+        };
+
         unsafe {
             //
             for ri in (0..degree).step_by(8) {
-                let mut xiv = Vec::with_capacity(q_size * 8);
-                let uninit_xiv = xiv.spare_capacity_mut();
-
                 seq!(N in 0..8 {
                     let mut nu~N = 0.5f64;
                 });
@@ -385,13 +522,12 @@ where
                     let op_shoup = *neg_pq_hat_inv_modq_shoup.get_unchecked(i);
                     let qi_inv = q_inv.get_unchecked(i);
                     seq!(N in 0..8 {
-                        let tmp~N = modqi.mul_mod_shoup(*q_poly.coefficients.uget((i, ri+N)), op, op_shoup);
+                        let tmp~N = modqi.mul_mod_shoup(*q_coefficients.uget((i, ri+N)), op, op_shoup);
                         nu~N += tmp~N as f64 * qi_inv;
-                        uninit_xiv.get_unchecked_mut(i*8+N).write(tmp~N);
+                        *xiv.get_unchecked_mut(i*8+N) = tmp~N;
                     });
                 }
 
-                xiv.set_len(q_size * 8);
                 seq!(N in 0..8 {
                     let nu~N = nu~N as u64;
                 });
@@ -410,17 +546,11 @@ where
 
                     let modpj = modps.get_unchecked(j);
                     seq!(N in 0..8 {
-                        let pxj = p_coeffs.uget_mut((j, ri+N)).write(modpj.barret_reduction_u128(tmp~N));
-                        *pxj = modpj.sub_mod_fast(*pxj, nu~N);
+                        *p_coefficients.uget_mut((j, ri+N)) = modpj.sub_mod_fast(modpj.barret_reduction_u128(tmp~N), nu~N);
                     });
                 }
             }
         }
-
-        unsafe {
-            let p_coeffs = p_coeffs.assume_init();
-            return p_context.new(p_coeffs, Representation::Coefficient);
-        }
     }
 
     pub fn switch_crt_basis(
@@ -435,22 +565,57 @@ where
     ) -> Poly {
         debug_assert!(q_poly.representation == Representation::Coefficient);
 
+        let mut p_poly = p_context.zero(Representation::Coefficient);
+        self.switch_crt_basis_into(
+            &q_poly.coefficients.view(),
+            p_context,
+            q_hat_modp,
+            q_hat_inv_modq,
+            q_hat_inv_modq_shoup,
+            q_inv,
+            alpha_modp,
+            p_poly.coefficients.view_mut(),
+        );
+        p_poly
+    }
+
+    /// Same as `switch_crt_basis` but takes coefficients of polynomial in `Coefficient`
+    /// representation and writes the result into `p_coefficients`.
+    // precomputed constants are passed as in `switch_crt_basis`, plus the output
+    #[allow(clippy::too_many_arguments)]
+    pub fn switch_crt_basis_into(
+        &self,
+        q_coefficients: &ArrayView2<u64>,
+        p_context: &PolyContext<'_, T>,
+        q_hat_modp: &Array2<u64>,
+        q_hat_inv_modq: &[u64],
+        q_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        alpha_modp: &Array2<u64>,
+        p_coefficients: ArrayViewMut2<'_, u64>,
+    ) {
         let q_size = self.moduli_count;
         let p_size = p_context.moduli_count;
-        let degree = self.degree;
+        debug_assert!(q_coefficients.dim() == (q_size, self.degree));
+        debug_assert!(p_coefficients.dim() == (p_size, self.degree));
 
         let modq_ops = self.moduli_ops();
         let modp_ops = p_context.moduli_ops();
 
-        let mut p_coeffs = Array2::uninit((p_size, degree));
+        for_each_coefficient_chunk(p_coefficients, |offset, mut p_coeffs| unsafe {
+            let mut stack_scratch = [0u64; STACK_SCRATCH_MODULI_COUNT * 8];
+            let mut heap_scratch = vec![];
+            let xiq = if q_size <= STACK_SCRATCH_MODULI_COUNT {
+                &mut stack_scratch[..q_size * 8]
+            } else {
+                heap_scratch.resize(q_size * 8, 0u64);
+                &mut heap_scratch[..]
+            };
 
-        unsafe {
-            for ri in (0..degree).step_by(8) {
-                let mut xiq = Vec::with_capacity(q_size * 8);
-                let uninit = xiq.spare_capacity_mut();
+            for ri in (0..p_coeffs.ncols()).step_by(8) {
+                let ci = offset + ri;
 
                 seq!(N in 0..8{
-                    // let mut xiq~N = Vec::with_capacity(q_size);
                     let mut nu~N = 0.5f64;
                 });
 
@@ -461,17 +626,15 @@ where
                     let q_invi = q_inv.get_unchecked(i);
                     seq!(N in 0..8{
                         let tmp~N = mod_ref.mul_mod_shoup(
-                            *q_poly.coefficients.uget((i,ri + N)),
+                            *q_coefficients.uget((i, ci + N)),
                             *op,
                             *op_shoup
                         );
                         nu~N += tmp~N as f64 * *q_invi;
-                        uninit.get_unchecked_mut(i*8+N).write(tmp~N);
+                        *xiq.get_unchecked_mut(i*8+N) = tmp~N;
                     });
                 }
 
-                xiq.set_len(q_size * 8);
-
                 for j in 0..p_size {
                     // Why not set `tmp` as a vec of u128? Apparently calling `drop_in_place` afterwards on
                     // `tmp` if it were a vec of u128s is more expensive than using tmp as 8 different variables.
@@ -491,17 +654,11 @@ where
                     let modpj = modp_ops.get_unchecked(j);
 
                     seq!(N in 0..8 {
-                        let tmp = modpj.sub_mod_fast(modpj.barret_reduction_u128(tmp~N), *alpha_modp.uget((j,nu~N as usize)));
-                        p_coeffs.uget_mut((j, ri + N)).write(tmp);
+                        *p_coeffs.uget_mut((j, ri + N)) = modpj.sub_mod_fast(modpj.barret_reduction_u128(tmp~N), *alpha_modp.uget((j,nu~N as usize)));
                     });
                 }
             }
-        }
-
-        unsafe {
-            let p_coeffs = p_coeffs.assume_init();
-            return p_context.new(p_coeffs, Representation::Coefficient);
-        }
+        });
     }
 
     pub fn fast_expand_crt_basis_p_over_q(
@@ -519,51 +676,95 @@ where
         p_inv: &[f64],
         alpha_modq: &Array2<u64>,
     ) -> Poly {
+        let mut pq_poly = pq_context.zero(Representation::Coefficient);
+        self.fast_expand_crt_basis_p_over_q_into(
+            q_poly,
+            p_context,
+            pq_context,
+            neg_pq_hat_inv_modq,
+            neg_pq_hat_inv_modq_shoup,
+            q_inv,
+            q_inv_modp,
+            p_hat_modq,
+            p_hat_inv_modp,
+            p_hat_inv_modp_shoup,
+            p_inv,
+            alpha_modq,
+            &mut pq_poly,
+            &mut Workspace::new(),
+        );
+        pq_poly
+    }
+
+    /// Same as `fast_expand_crt_basis_p_over_q` but writes the result into `pq_poly`, which must
+    /// be in `pq_context`. If `q_poly` is in `Evaluation` representation its copy in `Coefficient`
+    /// representation is taken from `ws`.
+    // precomputed constants are passed as in `fast_expand_crt_basis_p_over_q`, plus the output and workspace
+    #[allow(clippy::too_many_arguments)]
+    pub fn fast_expand_crt_basis_p_over_q_into(
+        &self,
+        q_poly: &Poly,
+        p_context: &PolyContext<'_, T>,
+        pq_context: &PolyContext<'_, T>,
+        neg_pq_hat_inv_modq: &[u64],
+        neg_pq_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        q_inv_modp: &Array2<u64>,
+        p_hat_modq: &Array2<u64>,
+        p_hat_inv_modp: &[u64],
+        p_hat_inv_modp_shoup: &[u64],
+        p_inv: &[f64],
+        alpha_modq: &Array2<u64>,
+        pq_poly: &mut Poly,
+        ws: &mut Workspace,
+    ) {
+        debug_assert!(pq_poly.coefficients.dim() == (pq_context.moduli_count, pq_context.degree));
+
+        let p_size = p_context.moduli_count;
+        let (mut p_coefficients, q_coefficients) =
+            pq_poly.coefficients.view_mut().split_at(Axis(0), p_size);
+
         // if self is not in coefficient, then convert it
-        let p = if q_poly.representation == Representation::Coefficient {
-            self.fast_conv_p_over_q(
-                q_poly,
+        if q_poly.representation == Representation::Coefficient {
+            self.fast_conv_p_over_q_into(
+                &q_poly.coefficients.view(),
                 p_context,
                 neg_pq_hat_inv_modq,
                 neg_pq_hat_inv_modq_shoup,
                 q_inv,
                 q_inv_modp,
-            )
+                p_coefficients.view_mut(),
+            );
         } else {
-            let mut q = q_poly.clone();
+            let mut q = ws.take_poly(self, q_poly.representation.clone());
+            q.coefficients.assign(&q_poly.coefficients);
             self.change_representation(&mut q, Representation::Coefficient);
-            self.fast_conv_p_over_q(
-                &q,
+            self.fast_conv_p_over_q_into(
+                &q.coefficients.view(),
                 p_context,
                 neg_pq_hat_inv_modq,
                 neg_pq_hat_inv_modq_shoup,
                 q_inv,
                 q_inv_modp,
-            )
-        };
+                p_coefficients.view_mut(),
+            );
+            ws.give_poly(q);
+        }
 
         // switch p to q
-        let q = p_context.switch_crt_basis(
-            &p,
-            &self,
+        p_context.switch_crt_basis_into(
+            &p_coefficients.view(),
+            self,
             p_hat_modq,
             p_hat_inv_modp,
             p_hat_inv_modp_shoup,
             p_inv,
             alpha_modq,
+            q_coefficients,
         );
 
-        let p_size = p_context.moduli_count;
         // output should always be in coefficient form
-        let mut pq_coeffs = Array2::zeros((pq_context.moduli_count, pq_context.degree));
-        pq_coeffs
-            .slice_mut(s![..p_size, ..])
-            .assign(&p.coefficients);
-        pq_coeffs
-            .slice_mut(s![p_size.., ..])
-            .assign(&q.coefficients);
-
-        pq_context.new(pq_coeffs, Representation::Coefficient)
+        pq_poly.representation = Representation::Coefficient;
     }
 
     pub fn expand_crt_basis(
@@ -577,41 +778,85 @@ where
         q_inv: &[f64],
         alpha_modp: &Array2<u64>,
     ) -> Poly {
-        let mut p = if q_poly.representation == Representation::Coefficient {
-            self.switch_crt_basis(
-                q_poly,
+        let mut pq_poly = pq_context.zero(q_poly.representation.clone());
+        self.expand_crt_basis_into(
+            q_poly,
+            pq_context,
+            p_context,
+            q_hat_modp,
+            q_hat_inv_modq,
+            q_hat_inv_modq_shoup,
+            q_inv,
+            alpha_modp,
+            &mut pq_poly,
+            &mut Workspace::new(),
+        );
+        pq_poly
+    }
+
+    /// Same as `expand_crt_basis` but writes the result into `pq_poly`, which must be in
+    /// `pq_context`. If `q_poly` is in `Evaluation` representation its copy in `Coefficient`
+    /// representation is taken from `ws`.
+    // precomputed constants are passed as in `expand_crt_basis`, plus the output and workspace
+    #[allow(clippy::too_many_arguments)]
+    pub fn expand_crt_basis_into(
+        &self,
+        q_poly: &Poly,
+        pq_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        q_hat_modp: &Array2<u64>,
+        q_hat_inv_modq: &[u64],
+        q_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        alpha_modp: &Array2<u64>,
+        pq_poly: &mut Poly,
+        ws: &mut Workspace,
+    ) {
+        debug_assert!(pq_poly.coefficients.dim() == (pq_context.moduli_count, pq_context.degree));
+
+        let p_size = p_context.moduli_count;
+        let (mut p_coefficients, mut q_coefficients) =
+            pq_poly.coefficients.view_mut().split_at(Axis(0), p_size);
+        q_coefficients.assign(&q_poly.coefficients);
+
+        if q_poly.representation == Representation::Coefficient {
+            self.switch_crt_basis_into(
+                &q_coefficients.view(),
                 p_context,
                 q_hat_modp,
                 q_hat_inv_modq,
                 q_hat_inv_modq_shoup,
                 q_inv,
                 alpha_modp,
-            )
+                p_coefficients,
+            );
         } else {
-            let mut q = q_poly.clone();
+            let mut q = ws.take_poly(self, q_poly.representation.clone());
+            q.coefficients.assign(&q_poly.coefficients);
             self.change_representation(&mut q, Representation::Coefficient);
-            self.switch_crt_basis(
-                &q,
+            self.switch_crt_basis_into(
+                &q.coefficients.view(),
                 p_context,
                 q_hat_modp,
                 q_hat_inv_modq,
                 q_hat_inv_modq_shoup,
                 q_inv,
                 alpha_modp,
-            )
-        };
-        p_context.change_representation(&mut p, q_poly.representation.clone());
+                p_coefficients.view_mut(),
+            );
+            ws.give_poly(q);
+
+            // P part must be in same representation as Q part
+            for_each_limb!(
+                |(mut v, ntt_op)| {
+                    ntt_op.forward(v.as_slice_mut().unwrap());
+                },
+                p_coefficients.outer_iter_mut(),
+                p_context.limb_ntt_ops()
+            );
+        }
 
-        let p_size = p_context.moduli_count;
-        let mut pq_coeffs = Array2::zeros((pq_context.moduli_count, pq_context.degree));
-        pq_coeffs
-            .slice_mut(s![..p_size, ..])
-            .assign(&p.coefficients);
-        pq_coeffs
-            .slice_mut(s![p_size.., ..])
-            .assign(&q_poly.coefficients);
-
-        pq_context.new(pq_coeffs, q_poly.representation.clone())
+        pq_poly.representation = q_poly.representation.clone();
     }
 
     /// Switches CRT basis from Q to P approximately.
@@ -625,15 +870,36 @@ where
         q_hat_modp: &Array2<u64>,
         p_moduli_ops: &[Modulus],
     ) -> Array2<u64> {
+        let mut p_coefficients = Array2::zeros((p_moduli_ops.len(), degree));
+        PolyContext::<T>::approx_switch_crt_basis_into(
+            q_coefficients,
+            q_moduli_ops,
+            q_hat_inv_modq,
+            q_hat_modp,
+            p_moduli_ops,
+            p_coefficients.view_mut(),
+        );
+        p_coefficients
+    }
+
+    /// Same as `approx_switch_crt_basis` but writes the result into `p_coefficients`
+    pub fn approx_switch_crt_basis_into(
+        q_coefficients: &ArrayView2<u64>,
+        q_moduli_ops: &[Modulus],
+        q_hat_inv_modq: &[u64],
+        q_hat_modp: &Array2<u64>,
+        p_moduli_ops: &[Modulus],
+        p_coefficients: ArrayViewMut2<'_, u64>,
+    ) {
         debug_assert!(q_moduli_ops.len() == q_coefficients.shape()[0]);
         debug_assert!(q_moduli_ops.len() <= 3);
-
-        let mut p_coeffs = Array2::<u64>::uninit((p_moduli_ops.len(), degree));
+        debug_assert!(p_moduli_ops.len() == p_coefficients.shape()[0]);
 
         let p_size = p_moduli_ops.len();
         let q_size = q_coefficients.shape()[0];
-        unsafe {
-            for ri in (0..degree).step_by(8) {
+        for_each_coefficient_chunk(p_coefficients, |offset, mut p_coeffs| unsafe {
+            for ri in (0..p_coeffs.ncols()).step_by(8) {
+                let ci = offset + ri;
                 let mut tmp: [MaybeUninit<u64>; 3 * 8] = MaybeUninit::uninit().assume_init();
 
                 for i in 0..q_size {
@@ -642,7 +908,7 @@ where
 
                     seq!(N in 0..8 {
                         tmp.get_unchecked_mut(i*8+N)
-                        .write(modq.mul_mod_fast(*q_coefficients.uget((i, ri+N)), op));
+                        .write(modq.mul_mod_fast(*q_coefficients.uget((i, ci+N)), op));
                     });
                 }
                 // tmp.set_len(q_size);
@@ -662,17 +928,11 @@ where
 
                     let modpj = p_moduli_ops.get_unchecked(j);
                     seq!(N in 0..8 {
-                        p_coeffs
-                        .uget_mut((j, ri+N))
-                        .write(modpj.barret_reduction_u128(s~N));
+                        *p_coeffs.uget_mut((j, ri+N)) = modpj.barret_reduction_u128(s~N);
                     });
                 }
             }
-        }
-
-        unsafe {
-            return p_coeffs.assume_init();
-        }
+        });
     }
 
     /// Approx mod down
@@ -689,54 +949,85 @@ where
         p_hat_modq: &Array2<u64>,
         p_inv_modq: &[u64],
     ) -> Poly {
+        let mut q_poly = q_context.zero(Representation::Evaluation);
+        self.approx_mod_down_into(
+            &mut qp_poly,
+            q_context,
+            p_context,
+            p_hat_inv_modp,
+            p_hat_modq,
+            p_inv_modq,
+            &mut q_poly,
+        );
+        q_poly
+    }
+
+    /// Same as `approx_mod_down` but writes the result into `q_poly`, which must be in
+    /// `q_context`. P part of `qp_poly` is changed to `Coefficient` representation in place.
+    // precomputed constants are passed as in `approx_mod_down`, plus the output
+    #[allow(clippy::too_many_arguments)]
+    pub fn approx_mod_down_into(
+        &self,
+        qp_poly: &mut Poly,
+        q_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        p_hat_inv_modp: &[u64],
+        p_hat_modq: &Array2<u64>,
+        p_inv_modq: &[u64],
+        q_poly: &mut Poly,
+    ) {
         debug_assert!(q_context.moduli_count + p_context.moduli_count == self.moduli_count);
         debug_assert!(qp_poly.representation == Representation::Evaluation);
+        debug_assert!(q_poly.coefficients.dim() == (q_context.moduli_count, q_context.degree));
 
         let q_size = q_context.moduli_count;
+        let (q_coefficients, mut p_coefficients) =
+            qp_poly.coefficients.view_mut().split_at(Axis(0), q_size);
 
         // Change P part of QP from `Evaluation` to `Coefficient` representation
-        let mut p_coefficients = qp_poly.coefficients.slice_mut(s![q_size.., ..]);
         debug_assert!(p_coefficients.shape()[0] == p_context.moduli_count);
-        izip!(p_coefficients.outer_iter_mut(), p_context.iter_ntt_ops()).for_each(
+        for_each_limb!(
             |(mut v, ntt_op)| {
                 ntt_op.backward(v.as_slice_mut().unwrap());
             },
+            p_coefficients.outer_iter_mut(),
+            p_context.limb_ntt_ops()
         );
 
-        let mut p_to_q_coefficients = PolyContext::<T>::approx_switch_crt_basis(
+        PolyContext::<T>::approx_switch_crt_basis_into(
             &p_coefficients.view(),
             p_context.moduli_ops(),
-            self.degree,
             p_hat_inv_modp,
             p_hat_modq,
             q_context.moduli_ops(),
+            q_poly.coefficients.view_mut(),
         );
 
         // Change P switched to Q part from `Coefficient` to `Evaluation` representation
         // Reason to switch from coefficient to evaluation form becomes apparent in next step when we multiply all values by 1/P
-        izip!(
-            p_to_q_coefficients.outer_iter_mut(),
-            q_context.iter_ntt_ops()
-        )
-        .for_each(|(mut v, ntt_op)| {
-            ntt_op.forward(v.as_slice_mut().unwrap());
-        });
-
-        let mut q_poly = qp_poly.coefficients.slice_move(s![..q_size, ..]);
-        debug_assert!(q_poly.shape()[0] == q_size);
+        for_each_limb!(
+            |(mut v, ntt_op)| {
+                ntt_op.forward(v.as_slice_mut().unwrap());
+            },
+            q_poly.coefficients.outer_iter_mut(),
+            q_context.limb_ntt_ops()
+        );
 
-        izip!(
-            q_poly.outer_iter_mut(),
-            p_to_q_coefficients.outer_iter(),
-            q_context.iter_moduli_ops(),
-            p_inv_modq.iter(),
-        )
-        .for_each(|(mut v, switched_v, modqi, p_inv_modqi)| {
-            modqi.sub_mod_fast_vec(v.as_slice_mut().unwrap(), switched_v.as_slice().unwrap());
-            modqi.scalar_mul_mod_fast_vec(v.as_slice_mut().unwrap(), *p_inv_modqi);
-        });
+        for_each_limb!(
+            |(mut switched_v, v, modqi, p_inv_modqi): (_, _, &Modulus, _)| {
+                modqi.sub_mod_fast_vec_reversed(
+                    switched_v.as_slice_mut().unwrap(),
+                    v.as_slice().unwrap(),
+                );
+                modqi.scalar_mul_mod_fast_vec(switched_v.as_slice_mut().unwrap(), *p_inv_modqi);
+            },
+            q_poly.coefficients.outer_iter_mut(),
+            q_coefficients.outer_iter(),
+            q_context.limb_moduli_ops(),
+            p_inv_modq,
+        );
 
-        Poly::new(q_poly, Representation::Evaluation)
+        q_poly.representation = Representation::Evaluation;
     }
 
     /// Switches polynomial from Q to Q' and scales by 1/qn where Q = q0*q1*q2...*qn and Q' = q0*q1*q2...*q(n-1).
@@ -831,14 +1122,14 @@ where
     pub fn add_assign(&self, lhs: &mut Poly, rhs: &Poly) {
         debug_assert!(lhs.representation == rhs.representation);
 
-        izip!(
+        for_each_limb!(
+            |(mut p1, p2, q): (_, _, &Modulus)| {
+                q.add_mod_fast_vec(p1.as_slice_mut().unwrap(), p2.as_slice().unwrap())
+            },
             lhs.coefficients.outer_iter_mut(),
             rhs.coefficients.outer_iter(),
-            self.iter_moduli_ops()
-        )
-        .for_each(|(mut p1, p2, q)| {
-            q.add_mod_fast_vec(p1.as_slice_mut().unwrap(), p2.as_slice().unwrap())
-        });
+            self.limb_moduli_ops()
+        );
     }
 
     pub fn add(&self, lhs: &Poly, rhs: &Poly) -> Poly {
@@ -909,16 +1200,14 @@ where
         assert!(lhs.representation == rhs.representation);
         assert!(lhs.representation == Representation::Evaluation);
 
-        izip!(
+        for_each_limb!(
+            |(mut p, p2, modqi): (_, _, &Modulus)| {
+                modqi.mul_mod_fast_vec(p.as_slice_mut().unwrap(), p2.as_slice().unwrap());
+            },
             lhs.coefficients.outer_iter_mut(),
             rhs.coefficients.outer_iter(),
-            self.iter_moduli_ops()
-        )
-        .for_each(|(mut p, p2, modqi)| {
-            // let now = std::time::Instant::now();
-            modqi.mul_mod_fast_vec(p.as_slice_mut().unwrap(), p2.as_slice().unwrap());
-            // println!("Time: {:?}", now.elapsed());
-        });
+            self.limb_moduli_ops()
+        );
     }
 
     pub fn mul(&self, lhs: &Poly, rhs: &Poly) -> Poly {
@@ -946,6 +1235,23 @@ where
             representation: lhs.representation.clone(),
         }
     }
+
+    /// Same as `mul` but writes the result into `out`
+    pub fn mul_into(&self, lhs: &Poly, rhs: &Poly, out: &mut Poly) {
+        assert!(lhs.representation == rhs.representation);
+        debug_assert!(out.coefficients.dim() == lhs.coefficients.dim());
+
+        out.coefficients.assign(&lhs.coefficients);
+        out.representation = lhs.representation.clone();
+        for_each_limb!(
+            |(mut p, p2, modqi): (_, _, &Modulus)| {
+                modqi.mul_mod_fast_vec(p.as_slice_mut().unwrap(), p2.as_slice().unwrap());
+            },
+            out.coefficients.outer_iter_mut(),
+            rhs.coefficients.outer_iter(),
+            self.limb_moduli_ops()
+        );
+    }
 }
 
 impl<T> PolyContext<'_, T>
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
+use crate::error::check_representation;
 use crate::{
     convert_bytes_to_ternary, convert_from_bytes, convert_ternary_to_bytes, convert_to_bytes,
-    BfvParameters, Ciphertext, EvaluationKey, GaloisKey, HybridKeySwitchingKey, Poly, PolyContext,
-    PolyType, RelinearizationKey, Representation, SecretKey, Substitution,
+    BfvError, BfvParameters, Ciphertext, EvaluationKey, GaloisKey, HybridKeySwitchingKey, Poly,
+    PolyContext, PolyType, PublicKey, RelinearizationKey, Representation, SecretKey, Substitution,
 };
 use itertools::{izip, Itertools};
 use ndarray::Array2;
@@ -16,43 +17,181 @@ pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/_.rs"));
 }
 
+fn deserialization_error<T>(reason: &str) -> Result<T, BfvError> {
+    Err(BfvError::Deserialization(reason.to_string()))
+}
+
+fn seed_from_bytes(bytes: &[u8]) -> Result<<ChaCha8Rng as SeedableRng>::Seed, BfvError> {
+    let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+    if bytes.len() != seed.len() {
+        return deserialization_error("invalid seed length");
+    }
+    seed.copy_from_slice(bytes);
+    Ok(seed)
+}
+
+fn check_level_in_range(level: usize, max_level: usize) -> Result<(), BfvError> {
+    if level > max_level {
+        return deserialization_error(&format!("level {level} exceeds max level {max_level}"));
+    }
+    Ok(())
+}
+
+/// Key switching keys only exist at levels smaller than `max_level`
+fn check_ksk_params(
+    parameters: &BfvParameters,
+    level: usize,
+    ksk: &HybridKeySwitchingKey,
+) -> Result<(), BfvError> {
+    if level >= parameters.max_level {
+        return deserialization_error(&format!("key switching key unsupported at level {level}"));
+    }
+    match parameters.hybrid_ksk_parameters.as_ref() {
+        Some(ksk_params) => {
+            if ksk.c0s.len() != ksk_params[level].dnum {
+                return deserialization_error("invalid no. of key switching key polynomials");
+            }
+        }
+        None => return deserialization_error("hybrid key switching not enabled"),
+    }
+    Ok(())
+}
+
+// BfvParameters //
+impl From<&BfvParameters> for proto::BfvParameters {
+    fn from(value: &BfvParameters) -> Self {
+        proto::BfvParameters {
+            ciphertext_moduli: value.ciphertext_moduli.clone(),
+            extension_moduli: value.extension_moduli.clone(),
+            special_moduli: value.special_moduli.clone().unwrap_or_default(),
+            plaintext_modulus: value.plaintext_modulus,
+            degree: value.degree as u32,
+            variance: value.variance as u32,
+            hw: value.hw as u32,
+            alpha: value.alpha.map(|v| v as u32),
+            dnum: value.dnum.map(|v| v as u32),
+        }
+    }
+}
+
+impl TryFrom<&proto::BfvParameters> for BfvParameters {
+    type Error = BfvError;
+
+    /// Rebuilds parameters (including all precomputed values) after checking that values are
+    /// consistent
+    fn try_from(value: &proto::BfvParameters) -> Result<Self, BfvError> {
+        let degree = value.degree as usize;
+        let mut params = BfvParameters::try_with_moduli(
+            &value.ciphertext_moduli,
+            &value.extension_moduli,
+            value.plaintext_modulus,
+            degree,
+        )?;
+
+        // `sample_vec_cbd` only supports variance in [1, 16]
+        if !(1..=16).contains(&value.variance) {
+            return Err(BfvError::InvalidParameters(format!(
+                "variance {} must be in range [1, 16]",
+                value.variance
+            )));
+        }
+        if value.hw == 0 || value.hw as usize > degree {
+            return Err(BfvError::InvalidParameters(format!(
+                "hamming weight {} must be in range [1, {degree}]",
+                value.hw
+            )));
+        }
+        params.variance = value.variance as usize;
+        params.change_hamming_weight(value.hw as usize);
+
+        if value.special_moduli.is_empty() {
+            if value.alpha.is_some() || value.dnum.is_some() {
+                return Err(BfvError::InvalidParameters(
+                    "alpha and dnum are set without special moduli".to_string(),
+                ));
+            }
+        } else {
+            let special_moduli: &[u64; 3] = value
+                .special_moduli
+                .as_slice()
+                .try_into()
+                .map_err(|_| BfvError::InvalidParameters("alpha must equal 3".to_string()))?;
+            params.try_enable_hybrid_key_switching_with_moduli(special_moduli)?;
+
+            if value.alpha.map(|v| v as usize) != params.alpha
+                || value.dnum.map(|v| v as usize) != params.dnum
+            {
+                return Err(BfvError::InvalidParameters(
+                    "alpha or dnum inconsistent with moduli".to_string(),
+                ));
+            }
+        }
+
+        Ok(params)
+    }
+}
+
 // Poly //
 impl<'a> TryFromWithPolyContext<'a> for Poly {
     type Value = proto::Poly;
     type PolyContext = crate::PolyContext<'a>;
+    type Error = BfvError;
+
+    fn try_from_with_context(
+        poly: &Self::Value,
+        poly_ctx: &'a Self::PolyContext,
+    ) -> Result<Self, BfvError> {
+        if poly.coefficients.len() != poly_ctx.moduli_count() {
+            return deserialization_error("invalid no. of poly moduli");
+        }
 
-    fn try_from_with_context(poly: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
-        let coefficients = izip!(poly.coefficients.iter(), poly_ctx.iter_moduli_ops())
-            .flat_map(|(xi, modqi)| {
-                let values = convert_from_bytes(xi, modqi.modulus());
-                assert!(values.len() == poly_ctx.degree());
-                values
-            })
-            .collect_vec();
+        let mut coefficients = Vec::with_capacity(poly_ctx.moduli_count() * poly_ctx.degree());
+        for (xi, modqi) in izip!(poly.coefficients.iter(), poly_ctx.iter_moduli_ops()) {
+            // values are packed in `bits` of modulus and `convert_to_bytes` always appends a
+            // trailing byte for left over bits of last value
+            let bits = (64 - modqi.modulus().leading_zeros()) as usize;
+            if xi.len() != (bits * poly_ctx.degree()) / 8 + 1 {
+                return deserialization_error("invalid poly bytes length");
+            }
+
+            let values = convert_from_bytes(xi, modqi.modulus());
+            if values
+                .iter()
+                .take(poly_ctx.degree())
+                .any(|v| *v >= modqi.modulus())
+            {
+                return deserialization_error("poly coefficient exceeds modulus");
+            }
+            coefficients.extend_from_slice(&values[..poly_ctx.degree()]);
+        }
         let coefficients =
             Array2::from_shape_vec((poly_ctx.moduli_count(), poly_ctx.degree()), coefficients)
                 .unwrap();
 
-        Poly {
+        Ok(Poly {
             coefficients,
             representation: Representation::Coefficient,
-        }
+        })
     }
 }
 impl<'a> TryFromWithPolyContext<'a> for proto::Poly {
     type Value = Poly;
     type PolyContext = crate::PolyContext<'a>;
+    type Error = BfvError;
 
-    fn try_from_with_context(poly: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
-        assert!(poly.representation == Representation::Coefficient);
+    fn try_from_with_context(
+        poly: &Self::Value,
+        poly_ctx: &'a Self::PolyContext,
+    ) -> Result<Self, BfvError> {
+        check_representation(&Representation::Coefficient, &poly.representation)?;
 
         let bytes = izip!(poly.coefficients.outer_iter(), poly_ctx.iter_moduli_ops())
             .map(|(xi, modqi)| convert_to_bytes(xi.as_slice().unwrap(), modqi.modulus()))
             .collect_vec();
 
-        proto::Poly {
+        Ok(proto::Poly {
             coefficients: bytes,
-        }
+        })
     }
 }
 
@@ -60,24 +199,39 @@ impl<'a> TryFromWithPolyContext<'a> for proto::Poly {
 impl TryFromWithParameters for proto::SecretKey {
     type Value = SecretKey;
     type Parameters = BfvParameters;
+    type Error = BfvError;
 
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         let bytes = convert_ternary_to_bytes(&value.coefficients);
-        proto::SecretKey {
+        Ok(proto::SecretKey {
             coefficients: bytes,
-        }
+        })
     }
 }
 
 impl TryFromWithParameters for SecretKey {
     type Parameters = BfvParameters;
     type Value = proto::SecretKey;
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        let coefficients = convert_bytes_to_ternary(&value.coefficients, parameters.degree);
+        if coefficients.len() != parameters.degree {
+            return deserialization_error("invalid secret key bytes length");
+        }
+        if coefficients.iter().any(|c| *c > 1) {
+            return deserialization_error("secret key coefficient not ternary");
+        }
 
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
-        let coefficients =
-            convert_bytes_to_ternary(&value.coefficients, parameters.degree).into_boxed_slice();
-
-        SecretKey { coefficients }
+        Ok(SecretKey {
+            coefficients: coefficients.into_boxed_slice(),
+        })
     }
 }
 
@@ -85,8 +239,13 @@ impl TryFromWithParameters for SecretKey {
 impl TryFromWithParameters for proto::Ciphertext {
     type Value = Ciphertext;
     type Parameters = BfvParameters;
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
-        assert!(value.poly_type() == PolyType::Q);
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        crate::error::check_poly_type(&PolyType::Q, &value.poly_type)?;
         let poly_ctx = parameters.poly_ctx(&value.poly_type, value.level);
 
         let slice = {
@@ -97,7 +256,7 @@ impl TryFromWithParameters for proto::Ciphertext {
                 // if seed is present, then the ciphertext can be assumed to be fresh ciphertext with
                 // polynomial degree of <= 2 where the second polynomial is seeded. Thus we only need to
                 // serialise the first polynomial
-                assert!(value.c.len() <= 2);
+                crate::error::check_poly_count(2, value.c.len())?;
                 1
             }
         };
@@ -106,53 +265,125 @@ impl TryFromWithParameters for proto::Ciphertext {
             .iter()
             .map(|p| {
                 // Avoid converting polynomial to `Coefficient` representation to allow
-                // assert in `Poly` to fail. This also avoids adding silent NTTs of which
+                // check in `Poly` to fail. This also avoids adding silent NTTs of which
                 // user of the API isn't aware.
                 proto::Poly::try_from_with_context(p, &poly_ctx)
             })
-            .collect_vec();
+            .collect::<Result<Vec<_>, _>>()?;
 
         let seed = value.seed.as_ref().and_then(|s| Some(s.to_vec()));
 
-        proto::Ciphertext {
+        Ok(proto::Ciphertext {
             c,
             level: value.level as u32,
             seed,
-        }
+        })
     }
 }
 impl TryFromWithParameters for Ciphertext {
     type Value = proto::Ciphertext;
     type Parameters = BfvParameters;
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         let level = value.level as usize;
+        check_level_in_range(level, parameters.max_level)?;
         let poly_ctx = parameters.poly_ctx(&PolyType::Q, level);
 
         let mut c = value
             .c
             .iter()
             .map(|p_proto| Poly::try_from_with_context(p_proto, &poly_ctx))
-            .collect_vec();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let seed = value.seed.as_ref().and_then(|s| {
-            let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
-            seed.copy_from_slice(&s);
-            Some(seed)
-        });
+        let seed = value
+            .seed
+            .as_ref()
+            .map(|s| seed_from_bytes(s))
+            .transpose()?;
 
-        if seed.is_some() {
-            assert!(c.len() < 2);
+        if let Some(seed) = seed {
+            if c.len() != 1 {
+                return deserialization_error("seeded ciphertext must have exactly 1 polynomial");
+            }
 
-            let a = poly_ctx.random_with_seed(seed.unwrap());
+            let a = poly_ctx.random_with_seed(seed);
             c.push(a);
+        } else if c.len() < 2 {
+            return deserialization_error("ciphertext must have atleast 2 polynomials");
         }
 
-        Ciphertext {
+        Ok(Ciphertext {
             c,
             poly_type: PolyType::Q,
             level,
+            // noise estimate is not serialized since the sender can't be trusted with it. Receiver
+            // must set it with `set_noise_estimate` based on how the ciphertext was produced.
+            #[cfg(feature = "noise-estimate")]
+            noise: f64::INFINITY,
             seed,
-        }
+        })
+    }
+}
+
+// PublicKey //
+impl TryFromWithParameters for proto::PublicKey {
+    type Value = PublicKey;
+    type Parameters = BfvParameters;
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        let poly_ctx = parameters.poly_ctx(&PolyType::Q, value.level);
+
+        // Only c0 is serialized since c1 can be generated from seed
+        let mut c0 = value.c0.clone();
+        poly_ctx.change_representation(&mut c0, Representation::Coefficient);
+        let c0 = Some(proto::Poly::try_from_with_context(&c0, &poly_ctx)?);
+
+        Ok(proto::PublicKey {
+            c0,
+            seed: value.seed.to_vec(),
+            level: value.level as u32,
+        })
+    }
+}
+
+impl TryFromWithParameters for PublicKey {
+    type Value = proto::PublicKey;
+    type Parameters = BfvParameters;
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        let level = value.level as usize;
+        check_level_in_range(level, parameters.max_level)?;
+        let poly_ctx = parameters.poly_ctx(&PolyType::Q, level);
+
+        let c0 = match value.c0.as_ref() {
+            Some(c0) => c0,
+            None => return deserialization_error("public key c0 missing"),
+        };
+        let mut c0 = Poly::try_from_with_context(c0, &poly_ctx)?;
+        poly_ctx.change_representation(&mut c0, Representation::Evaluation);
+
+        let seed = seed_from_bytes(&value.seed)?;
+        let mut c1 = poly_ctx.random_with_seed(seed);
+        poly_ctx.change_representation(&mut c1, Representation::Evaluation);
+
+        Ok(PublicKey {
+            c0,
+            c1,
+            seed,
+            level,
+        })
     }
 }
 
@@ -160,7 +391,12 @@ impl TryFromWithParameters for Ciphertext {
 impl<'a> TryFromWithPolyContext<'a> for proto::HybridKeySwitchingKey {
     type PolyContext = PolyContext<'a>;
     type Value = HybridKeySwitchingKey;
-    fn try_from_with_context(value: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_context(
+        value: &Self::Value,
+        poly_ctx: &'a Self::PolyContext,
+    ) -> Result<Self, BfvError> {
         let c0s = value
             .c0s
             .iter()
@@ -171,7 +407,7 @@ impl<'a> TryFromWithPolyContext<'a> for proto::HybridKeySwitchingKey {
                 poly_ctx.change_representation(&mut p, Representation::Coefficient);
                 proto::Poly::try_from_with_context(&p, &poly_ctx)
             })
-            .collect_vec();
+            .collect::<Result<Vec<_>, _>>()?;
 
         let c1s = {
             if value.seed.is_none() {
@@ -183,7 +419,7 @@ impl<'a> TryFromWithPolyContext<'a> for proto::HybridKeySwitchingKey {
                         poly_ctx.change_representation(&mut p, Representation::Coefficient);
                         proto::Poly::try_from_with_context(&p, &poly_ctx)
                     })
-                    .collect_vec()
+                    .collect::<Result<Vec<_>, _>>()?
             } else {
                 vec![]
             }
@@ -191,42 +427,48 @@ impl<'a> TryFromWithPolyContext<'a> for proto::HybridKeySwitchingKey {
 
         let seed = value.seed.and_then(|s| Some(s.to_vec()));
 
-        proto::HybridKeySwitchingKey { c0s, c1s, seed }
+        Ok(proto::HybridKeySwitchingKey { c0s, c1s, seed })
     }
 }
 
 impl<'a> TryFromWithPolyContext<'a> for HybridKeySwitchingKey {
     type PolyContext = PolyContext<'a>;
     type Value = proto::HybridKeySwitchingKey;
-    fn try_from_with_context(value: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_context(
+        value: &Self::Value,
+        poly_ctx: &'a Self::PolyContext,
+    ) -> Result<Self, BfvError> {
         let c0s = value
             .c0s
             .iter()
             .map(|p| {
                 // c0s and c1s are only needed in `Evaluation` form so it safe to convert them
                 // from `Coefficient` (default form for serialization) to `Evaluation`.
-                let mut p = Poly::try_from_with_context(p, &poly_ctx);
+                let mut p = Poly::try_from_with_context(p, poly_ctx)?;
                 poly_ctx.change_representation(&mut p, Representation::Evaluation);
-                p
+                Ok(p)
             })
-            .collect_vec();
+            .collect::<Result<Vec<_>, BfvError>>()?;
 
         let (c1s, seed) = {
             if value.seed.is_none() {
-                assert!(value.c1s.len() == value.c0s.len());
+                if value.c1s.len() != value.c0s.len() {
+                    return deserialization_error("c1s and c0s length mismatch");
+                }
                 let c = value
                     .c1s
                     .iter()
                     .map(|p| {
-                        let mut p = Poly::try_from_with_context(p, &poly_ctx);
+                        let mut p = Poly::try_from_with_context(p, poly_ctx)?;
                         poly_ctx.change_representation(&mut p, Representation::Evaluation);
-                        p
+                        Ok(p)
                     })
-                    .collect_vec();
+                    .collect::<Result<Vec<_>, BfvError>>()?;
                 (c, None)
             } else {
-                let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
-                seed.copy_from_slice(value.seed());
+                let seed = seed_from_bytes(value.seed())?;
                 // `generate_c1` returns c1s in `Coefficient` representation. Convert them to `Evaluation` representation.
                 let mut c = HybridKeySwitchingKey::generate_c1(c0s.len(), poly_ctx, seed);
                 c.iter_mut().for_each(|p| {
@@ -236,11 +478,11 @@ impl<'a> TryFromWithPolyContext<'a> for HybridKeySwitchingKey {
             }
         };
 
-        HybridKeySwitchingKey {
+        Ok(HybridKeySwitchingKey {
             seed,
             c0s: c0s.into_boxed_slice(),
             c1s: c1s.into_boxed_slice(),
-        }
+        })
     }
 }
 
@@ -248,36 +490,53 @@ impl<'a> TryFromWithPolyContext<'a> for HybridKeySwitchingKey {
 impl TryFromWithParameters for proto::GaloisKey {
     type Parameters = BfvParameters;
     type Value = GaloisKey;
+    type Error = BfvError;
 
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         let ctx = parameters.poly_ctx(&PolyType::QP, value.level);
         let ksk = Some(proto::HybridKeySwitchingKey::try_from_with_context(
             &value.ksk_key,
             &ctx,
-        ));
-        proto::GaloisKey {
+        )?);
+        Ok(proto::GaloisKey {
             exponent: value.substitution.exponent as u32,
             ksk,
             level: value.level as u32,
-        }
+        })
     }
 }
 
 impl TryFromWithParameters for GaloisKey {
     type Value = proto::GaloisKey;
     type Parameters = BfvParameters;
-
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        if value.exponent & 1 == 0 {
+            return deserialization_error("galois element must be odd");
+        }
         let substitution = Substitution::new(value.exponent as usize, parameters.degree);
         let level = value.level as usize;
+        check_level_in_range(level, parameters.max_level)?;
 
         let ctx = parameters.poly_ctx(&PolyType::QP, level);
-        let ksk = HybridKeySwitchingKey::try_from_with_context(&value.ksk.as_ref().unwrap(), &ctx);
-        GaloisKey {
+        let ksk = match value.ksk.as_ref() {
+            Some(ksk) => HybridKeySwitchingKey::try_from_with_context(ksk, &ctx)?,
+            None => return deserialization_error("Rtg ksk missing"),
+        };
+        check_ksk_params(parameters, level, &ksk)?;
+
+        Ok(GaloisKey {
             substitution,
             ksk_key: ksk,
             level,
-        }
+        })
     }
 }
 
@@ -285,7 +544,12 @@ impl TryFromWithParameters for GaloisKey {
 impl TryFromWithParameters for proto::RelinearizationKey {
     type Parameters = BfvParameters;
     type Value = RelinearizationKey;
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         let level = value.level;
         let ctx = parameters.poly_ctx(&PolyType::QP, level);
 
@@ -294,27 +558,35 @@ impl TryFromWithParameters for proto::RelinearizationKey {
         // This is enforced by proto3, not something prost does.
         let ksk = Some(proto::HybridKeySwitchingKey::try_from_with_context(
             &value.ksk, &ctx,
-        ));
+        )?);
 
-        proto::RelinearizationKey {
+        Ok(proto::RelinearizationKey {
             ksk,
             level: level as u32,
-        }
+        })
     }
 }
 
 impl TryFromWithParameters for RelinearizationKey {
     type Parameters = BfvParameters;
     type Value = proto::RelinearizationKey;
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         let level = value.level as usize;
+        check_level_in_range(level, parameters.max_level)?;
+
         let ctx = parameters.poly_ctx(&PolyType::QP, level);
-        let ksk = HybridKeySwitchingKey::try_from_with_context(
-            value.ksk.as_ref().expect("Rlk missing"),
-            &ctx,
-        );
+        let ksk = match value.ksk.as_ref() {
+            Some(ksk) => HybridKeySwitchingKey::try_from_with_context(ksk, &ctx)?,
+            None => return deserialization_error("Rlk missing"),
+        };
+        check_ksk_params(parameters, level, &ksk)?;
 
-        RelinearizationKey { ksk, level }
+        Ok(RelinearizationKey { ksk, level })
     }
 }
 
@@ -322,14 +594,19 @@ impl TryFromWithParameters for RelinearizationKey {
 impl TryFromWithParameters for proto::EvaluationKey {
     type Parameters = BfvParameters;
     type Value = EvaluationKey;
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
         // since HashMap iterates over values in arbitrary order seralisation of same `EvaluationKey`
         // twice can produce different `proto::EvaluationKey`s.
         let rlks = value
             .rlks
             .iter()
             .map(|(i, k)| proto::RelinearizationKey::try_from_with_parameters(&k, parameters))
-            .collect_vec();
+            .collect::<Result<Vec<_>, _>>()?;
         let mut rot_indices = vec![];
         let rtgs = value
             .rtgs
@@ -338,38 +615,58 @@ impl TryFromWithParameters for proto::EvaluationKey {
                 rot_indices.push(i.0 as i32);
                 proto::GaloisKey::try_from_with_parameters(&k, parameters)
             })
-            .collect_vec();
-
-        proto::EvaluationKey {
+            .collect::<Result<Vec<_>, _>>()?;
+        let substitution_keys = value
+            .substitution_keys
+            .values()
+            .map(|k| proto::GaloisKey::try_from_with_parameters(k, parameters))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(proto::EvaluationKey {
             rlks,
             rtgs,
             rot_indices,
-        }
+            substitution_keys,
+        })
     }
 }
 
 impl TryFromWithParameters for EvaluationKey {
     type Parameters = BfvParameters;
     type Value = proto::EvaluationKey;
+    type Error = BfvError;
+
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, BfvError> {
+        if value.rtgs.len() != value.rot_indices.len() {
+            return deserialization_error("rtgs and rot_indices length mismatch");
+        }
 
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
         let mut rlks = HashMap::new();
-        value.rlks.iter().for_each(|v| {
-            let v = RelinearizationKey::try_from_with_parameters(v, parameters);
+        for v in value.rlks.iter() {
+            let v = RelinearizationKey::try_from_with_parameters(v, parameters)?;
             rlks.insert(v.level, v);
-        });
+        }
 
         let mut rtgs = HashMap::new();
-        value
-            .rtgs
-            .iter()
-            .zip(value.rot_indices.iter())
-            .for_each(|(gk, rot_index)| {
-                let v = GaloisKey::try_from_with_parameters(gk, parameters);
-                rtgs.insert((*rot_index as isize, v.level), v);
-            });
+        for (gk, rot_index) in value.rtgs.iter().zip(value.rot_indices.iter()) {
+            let v = GaloisKey::try_from_with_parameters(gk, parameters)?;
+            rtgs.insert((*rot_index as isize, v.level), v);
+        }
+
+        let mut substitution_keys = HashMap::new();
+        for gk in value.substitution_keys.iter() {
+            let v = GaloisKey::try_from_with_parameters(gk, parameters)?;
+            substitution_keys.insert((v.substitution.exponent, v.level), v);
+        }
 
-        EvaluationKey { rlks, rtgs }
+        Ok(EvaluationKey {
+            rlks,
+            rtgs,
+            substitution_keys,
+        })
     }
 }
 
@@ -379,6 +676,43 @@ mod tests {
     use prost::Message;
     use rand::thread_rng;
 
+    #[test]
+    fn serialize_and_deserialize_parameters() {
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let params_proto = proto::BfvParameters::from(&params);
+        let bytes = params_proto.encode_to_vec();
+        let params_back =
+            BfvParameters::try_from(&proto::BfvParameters::decode(bytes.as_slice()).unwrap())
+                .unwrap();
+        assert_eq!(params, params_back);
+
+        // parameters without hybrid key switching
+        let params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+        let params_back = BfvParameters::try_from(&proto::BfvParameters::from(&params)).unwrap();
+        assert_eq!(params, params_back);
+
+        // repeated moduli
+        let mut invalid = proto::BfvParameters::from(&params);
+        invalid.extension_moduli[0] = invalid.ciphertext_moduli[0];
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        // not a prime
+        let mut invalid = proto::BfvParameters::from(&params);
+        invalid.ciphertext_moduli[1] += 32;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        // dnum without special moduli
+        let mut invalid = proto::BfvParameters::from(&params);
+        invalid.dnum = Some(1);
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        // degree inconsistent with moduli
+        let mut invalid = proto::BfvParameters::from(&params);
+        invalid.degree = 1 << 15;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+    }
+
     #[test]
     fn serialize_and_deserialize_secret_key() {
         let mut rng = thread_rng();
@@ -386,8 +720,8 @@ mod tests {
 
         let sk = SecretKey::random_with_params(&params, &mut rng);
 
-        let sk_proto = proto::SecretKey::try_from_with_parameters(&sk, &params);
-        let sk_back = SecretKey::try_from_with_parameters(&sk_proto, &params);
+        let sk_proto = proto::SecretKey::try_from_with_parameters(&sk, &params).unwrap();
+        let sk_back = SecretKey::try_from_with_parameters(&sk_proto, &params).unwrap();
 
         assert_eq!(sk, sk_back);
     }
@@ -406,12 +740,36 @@ mod tests {
         let mut ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
         ct0.seed = None;
 
-        let ct_proto = proto::Ciphertext::try_from_with_parameters(&ct0, evaluator.params());
-        let ct_back = Ciphertext::try_from_with_parameters(&ct_proto, evaluator.params());
+        let ct_proto =
+            proto::Ciphertext::try_from_with_parameters(&ct0, evaluator.params()).unwrap();
+        let ct_back = Ciphertext::try_from_with_parameters(&ct_proto, evaluator.params()).unwrap();
+
+        // noise estimate is not serialized and is unknown after deserialization
+        #[cfg(feature = "noise-estimate")]
+        let ct_back = {
+            assert!(evaluator.check_noise_budget(&ct_back).is_err());
+            let mut ct_back = ct_back;
+            ct_back.set_noise_estimate(ct0.noise_estimate());
+            ct_back
+        };
 
         assert_eq!(ct0, ct_back);
     }
 
+    #[test]
+    fn serialize_and_deserialize_public_key() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, 1, &mut rng);
+
+        let pk_proto = proto::PublicKey::try_from_with_parameters(&pk, &params).unwrap();
+        let pk_back = PublicKey::try_from_with_parameters(&pk_proto, &params).unwrap();
+
+        assert_eq!(pk, pk_back);
+    }
+
     #[test]
     fn serialize_and_deserialize_poly() {
         let params = BfvParameters::default(3, 1 << 15);
@@ -419,10 +777,10 @@ mod tests {
 
         let mut rng = thread_rng();
         let poly = ctx.random(Representation::Coefficient, &mut rng);
-        let proto = proto::Poly::try_from_with_context(&poly, &ctx);
+        let proto = proto::Poly::try_from_with_context(&poly, &ctx).unwrap();
         let bytes = proto.encode_to_vec();
         dbg!(bytes.len());
-        let poly_back = Poly::try_from_with_context(&proto, &ctx);
+        let poly_back = Poly::try_from_with_context(&proto, &ctx).unwrap();
 
         assert_eq!(poly, poly_back);
     }
@@ -445,9 +803,9 @@ mod tests {
             &mut rng,
         );
 
-        let ksk_proto = proto::HybridKeySwitchingKey::try_from_with_context(&ksk, &qp_ctx);
+        let ksk_proto = proto::HybridKeySwitchingKey::try_from_with_context(&ksk, &qp_ctx).unwrap();
         dbg!(ksk_proto.encode_to_vec().len());
-        let ksk_back = HybridKeySwitchingKey::try_from_with_context(&ksk_proto, &qp_ctx);
+        let ksk_back = HybridKeySwitchingKey::try_from_with_context(&ksk_proto, &qp_ctx).unwrap();
 
         assert_eq!(ksk, ksk_back);
     }
@@ -461,8 +819,8 @@ mod tests {
 
         let rlk = RelinearizationKey::new(&params, &sk, 0, &mut rng);
 
-        let rlk_proto = proto::RelinearizationKey::try_from_with_parameters(&rlk, &params);
-        let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params);
+        let rlk_proto = proto::RelinearizationKey::try_from_with_parameters(&rlk, &params).unwrap();
+        let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params).unwrap();
 
         assert_eq!(rlk, rlk_back);
     }
@@ -483,9 +841,58 @@ mod tests {
             &mut rng,
         );
 
-        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params);
-        let ek_back = EvaluationKey::try_from_with_parameters(&ek_proto, &params);
+        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params).unwrap();
+        let ek_back = EvaluationKey::try_from_with_parameters(&ek_proto, &params).unwrap();
+
+        assert_eq!(ek, ek_back);
+
+        let ek = EvaluationKey::new_for_ring_packing(&params, &sk, &[0], &[0], &mut rng);
+        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params).unwrap();
+        let ek_back = EvaluationKey::try_from_with_parameters(&ek_proto, &params).unwrap();
 
         assert_eq!(ek, ek_back);
     }
+
+    #[test]
+    fn deserialize_invalid_ciphertext() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+        let ct_proto =
+            proto::Ciphertext::try_from_with_parameters(&ct0, evaluator.params()).unwrap();
+
+        // level out of range
+        let mut invalid = ct_proto.clone();
+        invalid.level = 10;
+        assert!(Ciphertext::try_from_with_parameters(&invalid, evaluator.params()).is_err());
+
+        // truncated poly
+        let mut invalid = ct_proto.clone();
+        invalid.c[0].coefficients[0].pop();
+        assert!(Ciphertext::try_from_with_parameters(&invalid, evaluator.params()).is_err());
+
+        // missing poly moduli
+        let mut invalid = ct_proto.clone();
+        invalid.c[0].coefficients.pop();
+        assert!(Ciphertext::try_from_with_parameters(&invalid, evaluator.params()).is_err());
+
+        // invalid seed
+        let mut invalid = ct_proto.clone();
+        invalid.seed.as_mut().unwrap().pop();
+        assert!(Ciphertext::try_from_with_parameters(&invalid, evaluator.params()).is_err());
+
+        // coefficient >= modulus
+        let mut invalid = ct_proto.clone();
+        invalid.c[0].coefficients[0]
+            .iter_mut()
+            .for_each(|b| *b = u8::MAX);
+        assert!(Ciphertext::try_from_with_parameters(&invalid, evaluator.params()).is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use crate::error::{check_level_range, BfvError};
+use crate::plaintext::Plaintext;
+use crate::{BfvParameters, Ciphertext, Poly, PolyType, Representation, SecretKey};
+use ndarray::s;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKey {
+    pub(crate) c0: Poly,
+    pub(crate) c1: Poly,
+    pub(crate) seed: <ChaCha8Rng as SeedableRng>::Seed,
+    pub(crate) level: usize,
+}
+
+impl PublicKey {
+    /// Generates public key (-a*s + e, a) at `level` for the secret key. `a` is generated from a seed
+    /// so that only the seed needs to be serialized.
+    ///
+    /// Since moduli chain at any level > `level` is a subset of moduli chain at `level`, public key can
+    /// be used to encrypt plaintexts at `level` or higher.
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        level: usize,
+        rng: &mut R,
+    ) -> PublicKey {
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+
+        // seed `a`
+        let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+        rng.fill_bytes(&mut seed);
+        let mut a = ctx.random_with_seed(seed);
+        ctx.change_representation(&mut a, Representation::Evaluation);
+
+        // sk*a
+        let mut sk_poly = sk.to_poly(&ctx);
+        ctx.mul_assign(&mut sk_poly, &a);
+
+        let mut e = ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+        ctx.change_representation(&mut e, Representation::Evaluation);
+
+        // e - sk*a
+        ctx.sub_assign(&mut e, &sk_poly);
+
+        PublicKey {
+            c0: e,
+            c1: a,
+            seed,
+            level,
+        }
+    }
+
+    /// Encrypts given plaintext with the public key
+    ///
+    /// Returns ciphertext (c0, c1) = (pk0*u + e0 + m, pk1*u + e1) where u is a ternary polynomial and
+    /// m is plaintext scaled by Q/t. Ciphertext is in `Coefficient` representation.
+    ///
+    /// Panics if plaintext level is smaller than public key level.
+    pub fn encrypt<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Ciphertext {
+        self.try_encrypt(params, pt, rng).unwrap()
+    }
+
+    pub fn try_encrypt<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Result<Ciphertext, BfvError> {
+        let encoding = pt.encoding.as_ref().ok_or(BfvError::EncodingMissing)?;
+        check_level_range(encoding.level, self.level, params.max_level)?;
+
+        let level = encoding.level;
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+
+        // Public key polynomials at `level` are simply residues of pk polynomials modulo
+        // subset of moduli chain at public key's level.
+        let (pk0, pk1) = self.polys_at_level(ctx.moduli_count());
+
+        let u = SecretKey::random_with_params(params, rng);
+        let u_poly = u.to_poly(&ctx);
+
+        let m = pt.scale_plaintext(params, Representation::Evaluation);
+
+        // pk0*u + e0 + m
+        let mut c0 = ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+        ctx.change_representation(&mut c0, Representation::Evaluation);
+        ctx.add_assign(&mut c0, &ctx.mul(&pk0, &u_poly));
+        ctx.add_assign(&mut c0, &m);
+
+        // pk1*u + e1
+        let mut c1 = ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+        ctx.change_representation(&mut c1, Representation::Evaluation);
+        ctx.add_assign(&mut c1, &ctx.mul(&pk1, &u_poly));
+
+        ctx.change_representation(&mut c0, Representation::Coefficient);
+        ctx.change_representation(&mut c1, Representation::Coefficient);
+
+        Ok(Ciphertext {
+            c: vec![c0, c1],
+            poly_type: PolyType::Q,
+            level,
+            seed: None,
+            #[cfg(feature = "noise-estimate")]
+            noise: params.noise_fresh_pk(),
+        })
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    fn polys_at_level(&self, moduli_count: usize) -> (Poly, Poly) {
+        if moduli_count == self.c0.coefficients.shape()[0] {
+            (self.c0.clone(), self.c1.clone())
+        } else {
+            (
+                Poly::new(
+                    self.c0
+                        .coefficients
+                        .slice(s![..moduli_count, ..])
+                        .to_owned(),
+                    Representation::Evaluation,
+                ),
+                Poly::new(
+                    self.c1
+                        .coefficients
+                        .slice(s![..moduli_count, ..])
+                        .to_owned(),
+                    Representation::Evaluation,
+                ),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, EvaluationKey, Evaluator};
+    use rand::thread_rng;
+
+    #[test]
+    fn public_key_encryption_decryption() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, 0, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        for level in 0..3 {
+            let m = evaluator
+                .params()
+                .plaintext_modulus_op
+                .random_vec(evaluator.params().degree, &mut rng);
+            let pt = evaluator.plaintext_encode(&m, Encoding::simd(level, crate::PolyCache::None));
+            let ct = evaluator.encrypt_pk(&pk, &pt, &mut rng);
+            assert_eq!(ct.level(), level);
+
+            assert!(
+                evaluator.measure_noise(&sk, &ct) as f64
+                    <= evaluator.params().noise_fresh_pk() + 1.0
+            );
+
+            let rm = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default());
+            assert_eq!(rm, m);
+        }
+    }
+
+    #[test]
+    fn public_key_ciphertexts_mul_relinearize() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, 0, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let mut m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt_pk(
+            &pk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt_pk(
+            &pk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        let ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        evaluator
+            .params()
+            .plaintext_modulus_op
+            .mul_mod_fast_vec(&mut m0, &m1);
+        let rm = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct01), Encoding::default());
+        assert_eq!(rm, m0);
+    }
+}
@@ -1,5 +1,9 @@
+use crate::error::{
+    check_level, check_poly_count, check_poly_type, check_representation, BfvError,
+};
 use crate::{
     BfvParameters, Ciphertext, HybridKeySwitchingKey, PolyType, Representation, SecretKey,
+    Workspace,
 };
 use rand::{CryptoRng, RngCore};
 
@@ -40,34 +44,66 @@ impl RelinearizationKey {
     }
 
     pub fn relinearize(&self, ct: &Ciphertext, params: &BfvParameters) -> Ciphertext {
-        assert!(ct.c.len() == 3); // otherwise invalid relinerization
-        assert!(ct.c[0].representation == Representation::Coefficient);
-        assert!(ct.level == self.level);
+        self.try_relinearize(ct, params).unwrap()
+    }
+
+    pub fn try_relinearize(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+    ) -> Result<Ciphertext, BfvError> {
+        let mut out = Ciphertext::placeholder();
+        self.try_relinearize_into(ct, params, &mut out, &mut Workspace::new())?;
+        Ok(out)
+    }
+
+    /// Same as `try_relinearize` but writes the result into `out` and takes temporary polynomials
+    /// from `ws`
+    pub fn try_relinearize_into(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        out: &mut Ciphertext,
+        ws: &mut Workspace,
+    ) -> Result<(), BfvError> {
+        check_poly_count(3, ct.c.len())?; // otherwise invalid relinerization
+        check_poly_type(&PolyType::Q, &ct.poly_type)?;
+        check_representation(&Representation::Coefficient, &ct.c[0].representation)?;
+        check_level(self.level, ct.level)?;
 
         let level = ct.level;
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
         let qp_ctx = params.poly_ctx(&PolyType::QP, level);
         let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
 
-        let (mut cs0, mut cs1) = self.ksk.switch(
+        ws.fit_polys(&mut out.c, 2, &q_ctx);
+        let [cs0, cs1] = &mut out.c[..] else {
+            unreachable!()
+        };
+        self.ksk.switch_into(
             params.hybrid_key_switching_params_at_level(self.level),
             &ct.c[2],
             &qp_ctx,
             &q_ctx,
             &specialp_ctx,
+            cs0,
+            cs1,
+            ws,
         );
-        q_ctx.change_representation(&mut cs0, Representation::Coefficient);
-        q_ctx.change_representation(&mut cs1, Representation::Coefficient);
+        q_ctx.change_representation(cs0, Representation::Coefficient);
+        q_ctx.change_representation(cs1, Representation::Coefficient);
 
-        q_ctx.add_assign(&mut cs0, &ct.c[0]);
-        q_ctx.add_assign(&mut cs1, &ct.c[1]);
+        q_ctx.add_assign(cs0, &ct.c[0]);
+        q_ctx.add_assign(cs1, &ct.c[1]);
 
-        Ciphertext {
-            c: vec![cs0, cs1],
-            poly_type: PolyType::Q,
-            level: ct.level,
-            seed: None,
+        out.poly_type = PolyType::Q;
+        out.level = ct.level;
+        out.seed = None;
+        #[cfg(feature = "noise-estimate")]
+        {
+            out.noise = params.noise_key_switch(ct.noise, ct.level);
         }
+        Ok(())
     }
 }
 
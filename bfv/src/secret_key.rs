@@ -1,7 +1,10 @@
+use crate::error::{check_level_range, check_poly_type, BfvError};
 use crate::plaintext::{Encoding, Plaintext};
 use crate::{BfvParameters, Ciphertext, PolyCache, PolyType};
 use crate::{Poly, PolyContext, Representation};
 use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::Zero;
 use rand::distributions::{Distribution, Uniform};
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
@@ -84,7 +87,7 @@ impl SecretKey {
     }
 
     /// Returns secret key polynomial for polynomial context at given level in Evaluation form
-    fn to_poly(&self, ctx: &PolyContext<'_>) -> Poly {
+    pub(crate) fn to_poly(&self, ctx: &PolyContext<'_>) -> Poly {
         let mut p = ctx.try_convert_from_i64_small(&self.coefficients, Representation::Coefficient);
         ctx.change_representation(&mut p, Representation::Evaluation);
         p
@@ -97,10 +100,17 @@ impl SecretKey {
         pt: &Plaintext,
         rng: &mut R,
     ) -> Ciphertext {
-        if pt.encoding.is_none() {
-            panic!("Plaintext encoding missing!");
-        }
-        let encoding = pt.encoding.clone().unwrap();
+        self.try_encrypt(params, pt, rng).unwrap()
+    }
+
+    pub fn try_encrypt<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Result<Ciphertext, BfvError> {
+        let encoding = pt.encoding.clone().ok_or(BfvError::EncodingMissing)?;
+        check_level_range(encoding.level, 0, params.max_level)?;
 
         let ctx = params.poly_ctx(&PolyType::Q, encoding.level);
         let mut sk_poly = self.to_poly(&ctx);
@@ -130,68 +140,88 @@ impl SecretKey {
 
         ctx.change_representation(&mut e, Representation::Coefficient);
 
-        Ciphertext {
+        Ok(Ciphertext {
             c: vec![e, a],
             poly_type: PolyType::Q,
             level: encoding.level,
             seed: Some(seed),
-        }
+            #[cfg(feature = "noise-estimate")]
+            noise: params.noise_fresh_sk(),
+        })
     }
 
     pub fn decrypt(&self, ct: &Ciphertext, params: &BfvParameters) -> Plaintext {
-        // Panic on empty ciphertext
-        assert!(ct.c.len() != 0);
-        assert!(ct.poly_type == PolyType::Q);
+        self.try_decrypt(ct, params).unwrap()
+    }
 
+    pub fn try_decrypt(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+    ) -> Result<Plaintext, BfvError> {
+        check_decryptable(ct, params)?;
         let ctx = params.poly_ctx(&ct.poly_type, ct.level);
+        let m = self.decrypt_poly(ct, &ctx);
+        Ok(Plaintext::from_decrypted_poly(&m, ct.level, params))
+    }
 
-        let mut m = ct.c[0].clone();
-        ctx.change_representation(&mut m, Representation::Evaluation);
-
-        let s = self.to_poly(&ctx);
-        let mut s_carry = s.clone();
-        for i in 1..ct.c.len() {
-            if ct.c[i].representation == Representation::Evaluation {
-                ctx.add_assign(&mut m, &ctx.mul(&s_carry, &ct.c[i]));
-            } else {
-                let mut tmp = ct.c[i].clone();
-                ctx.change_representation(&mut tmp, Representation::Evaluation);
-                ctx.mul_assign(&mut tmp, &s_carry);
-                ctx.add_assign(&mut m, &tmp);
-            }
-            ctx.mul_assign(&mut s_carry, &s);
-        }
-
-        ctx.change_representation(&mut m, Representation::Coefficient);
-        let m = ctx.scale_and_round_decryption(
+    pub fn measure_noise(&self, ct: &Ciphertext, params: &BfvParameters) -> u64 {
+        // Decrypted plaintext polynomial is independent of the encoding used for ciphertext,
+        // so it can be scaled without decoding
+        let m = self.decrypt(ct, params).m;
+        let scaled_m = Plaintext::scale_m(
             &m,
-            &params.plaintext_modulus_op,
-            params.max_bit_size_by2,
-            &params.t_ql_hat_inv_modql_divql_modt[ct.level],
-            &params.t_bql_hat_inv_modql_divql_modt[ct.level],
-            &params.t_ql_hat_inv_modql_divql_frac[ct.level],
-            &params.t_bql_hat_inv_modql_divql_frac[ct.level],
+            params,
+            &Encoding::poly(ct.level(), PolyCache::None),
+            Representation::Coefficient,
         );
-        Plaintext {
-            m,
-            encoding: None,
-            mul_poly: None,
-            add_sub_poly: None,
-        }
+
+        let ctx = params.poly_ctx(&ct.poly_type, ct.level);
+        let mut m = self.decrypt_poly(ct, &ctx);
+        ctx.sub_assign(&mut m, &scaled_m);
+
+        let mut noise = 0u64;
+        ctx.try_convert_to_biguint(&m).iter().for_each(|v| {
+            noise = std::cmp::max(noise, std::cmp::min(v.bits(), (ctx.big_q() - v).bits()))
+        });
+        noise
     }
 
-    pub fn measure_noise(&self, ct: &Ciphertext, params: &BfvParameters) -> u64 {
-        // TODO: replace default simd with encoding used for ciphertext. This will require
-        // adding encoding info to ciphertext
-        let m = self.decrypt(ct, params).decode(Encoding::default(), params);
-        let scaled_m = Plaintext::encode(&m, &params, Encoding::simd(ct.level(), PolyCache::None))
-            .scale_plaintext(&params, Representation::Evaluation);
+    /// Returns invariant noise budget of the ciphertext in bits, ie log2(Q/(2t)) minus bits in
+    /// invariant noise, where Q is ciphertext modulus at ciphertext's level. Ciphertext decrypts
+    /// correctly as long as the budget is positive. Returns 0 if decryption may fail.
+    ///
+    /// Noise is measured as ||[t * (c0 + c1*s + ... + ck*s^k)]_Q|| (as in SEAL), thus the budget
+    /// does not depend on encoding of the message and works for ciphertexts of any degree k.
+    pub fn noise_budget(&self, ct: &Ciphertext, params: &BfvParameters) -> i64 {
+        self.try_noise_budget(ct, params).unwrap()
+    }
 
+    pub fn try_noise_budget(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+    ) -> Result<i64, BfvError> {
+        check_decryptable(ct, params)?;
         let ctx = params.poly_ctx(&ct.poly_type, ct.level);
+        let m = self.decrypt_poly(ct, &ctx);
+
+        let q = ctx.big_q();
+        let mut noise = BigUint::zero();
+        ctx.try_convert_to_biguint(&m).into_iter().for_each(|v| {
+            let v = (v * params.plaintext_modulus) % &q;
+            let v = std::cmp::min(&q - &v, v);
+            noise = std::cmp::max(noise.clone(), v);
+        });
+        Ok(std::cmp::max(0, q.bits() as i64 - noise.bits() as i64 - 1))
+    }
 
+    /// Returns c0 + c1*s + ... + ck*s^k in `Coefficient` representation
+    fn decrypt_poly(&self, ct: &Ciphertext, ctx: &PolyContext<'_>) -> Poly {
         let mut m = ct.c[0].clone();
         ctx.change_representation(&mut m, Representation::Evaluation);
-        let s = self.to_poly(&ctx);
+
+        let s = self.to_poly(ctx);
         let mut s_carry = s.clone();
         for i in 1..ct.c.len() {
             if ct.c[i].representation == Representation::Evaluation {
@@ -205,20 +235,28 @@ impl SecretKey {
             ctx.mul_assign(&mut s_carry, &s);
         }
 
-        ctx.sub_assign(&mut m, &scaled_m);
         ctx.change_representation(&mut m, Representation::Coefficient);
+        m
+    }
+}
 
-        let mut noise = 0u64;
-        ctx.try_convert_to_biguint(&m).iter().for_each(|v| {
-            noise = std::cmp::max(noise, std::cmp::min(v.bits(), (ctx.big_q() - v).bits()))
+/// Checks that ciphertext can be decrypted with secret key
+fn check_decryptable(ct: &Ciphertext, params: &BfvParameters) -> Result<(), BfvError> {
+    // Fail on empty ciphertext
+    if ct.c.len() < 2 {
+        return Err(BfvError::InvalidPolyCount {
+            expected: 2,
+            found: ct.c.len(),
         });
-        noise
     }
+    check_poly_type(&PolyType::Q, &ct.poly_type)?;
+    check_level_range(ct.level, 0, params.max_level)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{EvaluationKey, Evaluator};
     use rand::{
         distributions::{Distribution, Uniform},
         thread_rng, Rng,
@@ -245,6 +283,41 @@ mod tests {
         assert_eq!(m, m2);
     }
 
+    #[test]
+    fn noise_budget_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+        let evaluator = Evaluator::new(params);
+        let params = evaluator.params();
+
+        for encoding in [Encoding::default(), Encoding::poly(0, PolyCache::None)] {
+            let m = params
+                .plaintext_modulus_op
+                .random_vec(params.degree, &mut rng);
+            let mut ct =
+                evaluator.encrypt(&sk, &evaluator.plaintext_encode(&m, encoding), &mut rng);
+
+            let fresh = evaluator.noise_budget(&sk, &ct);
+            let max = params.noise_bound(0);
+            assert!(fresh as f64 >= max - params.noise_fresh_sk() - 2.0);
+            assert!((fresh as f64) < max);
+
+            // degree 2 ciphertext
+            let product = evaluator.mul(&ct, &ct);
+            let budget = evaluator.noise_budget(&sk, &product);
+            assert!(budget > 0 && budget < fresh);
+            // multiplication consumes more than log(t) bits
+            assert!(fresh - budget > 17);
+            let budget_relin = evaluator.noise_budget(&sk, &evaluator.relinearize(&product, &ek));
+            assert!(budget_relin > 0 && budget_relin <= budget);
+
+            unsafe { evaluator.add_noise(&mut ct, max as usize + 1) };
+            assert_eq!(evaluator.noise_budget(&sk, &ct), 0);
+        }
+    }
+
     #[test]
     fn test_hamming_weight() {
         let mut rng = thread_rng();
@@ -0,0 +1,70 @@
+/// Classical security levels of [HE standard](https://homomorphicencryption.org/standard/)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityLevel {
+    Bits128,
+    Bits192,
+    Bits256,
+}
+
+impl SecurityLevel {
+    /// Returns maximum bits in QP for polynomial `degree` such that security is at least
+    /// `self`. QP includes ciphertext moduli and hybrid key switching special moduli.
+    ///
+    /// Values are taken from Table 1 of HE standard for ternary secret distribution and classical
+    /// attacks. Returns `None` if the standard does not specify the degree.
+    pub fn max_log_qp(&self, degree: usize) -> Option<usize> {
+        let bits = match degree {
+            1024 => [27, 19, 14],
+            2048 => [54, 37, 29],
+            4096 => [109, 75, 58],
+            8192 => [218, 152, 118],
+            16384 => [438, 305, 237],
+            32768 => [881, 611, 476],
+            _ => return None,
+        };
+
+        match self {
+            SecurityLevel::Bits128 => Some(bits[0]),
+            SecurityLevel::Bits192 => Some(bits[1]),
+            SecurityLevel::Bits256 => Some(bits[2]),
+        }
+    }
+
+    /// Returns highest security level satisfied by `log_qp` bits in QP for polynomial `degree`
+    pub fn estimate(degree: usize, log_qp: usize) -> Option<SecurityLevel> {
+        [
+            SecurityLevel::Bits256,
+            SecurityLevel::Bits192,
+            SecurityLevel::Bits128,
+        ]
+        .into_iter()
+        .find(|level| {
+            level
+                .max_log_qp(degree)
+                .is_some_and(|max_log_qp| log_qp <= max_log_qp)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_security_level() {
+        assert_eq!(
+            SecurityLevel::estimate(1 << 13, 218),
+            Some(SecurityLevel::Bits128)
+        );
+        assert_eq!(
+            SecurityLevel::estimate(1 << 13, 152),
+            Some(SecurityLevel::Bits192)
+        );
+        assert_eq!(
+            SecurityLevel::estimate(1 << 15, 100),
+            Some(SecurityLevel::Bits256)
+        );
+        assert_eq!(SecurityLevel::estimate(1 << 13, 219), None);
+        assert_eq!(SecurityLevel::estimate(1 << 4, 10), None);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::error::{check_level, check_level_range};
+use crate::{
+    BfvParameters, Ciphertext, Encoding, EvaluationKey, Evaluator, Modulus, PolyCache, PolyType,
+    Representation, SecretKey,
+};
+use rand::{CryptoRng, RngCore};
+
+/// Operations on SIMD encoded messages shared by `EncryptedEvaluator` and `Simulator`, so that
+/// application code can run against either of them.
+///
+/// Messages are vectors of at most N values mod t, arranged as 2 rows of N/2 slots. Same as
+/// `Evaluator`, operations panic on invalid operands (for ex, ciphertexts at different levels).
+pub trait SlotEvaluator {
+    type Ciphertext: Clone;
+
+    fn encrypt<R: CryptoRng + RngCore>(&self, m: &[u64], rng: &mut R) -> Self::Ciphertext;
+
+    fn decrypt(&self, ct: &Self::Ciphertext) -> Vec<u64>;
+
+    fn level(&self, ct: &Self::Ciphertext) -> usize;
+
+    fn add(&self, lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext;
+
+    fn sub(&self, lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext;
+
+    /// Multiplies ciphertexts and relinearizes the product
+    fn mul(&self, lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext;
+
+    fn mul_plaintext(&self, ct: &Self::Ciphertext, m: &[u64]) -> Self::Ciphertext;
+
+    fn add_plaintext(&self, ct: &Self::Ciphertext, m: &[u64]) -> Self::Ciphertext;
+
+    /// Rotates both rows left by `rotate_by` (right if negative). Rotation by 2N - 1 swaps rows.
+    fn rotate(&self, ct: &Self::Ciphertext, rotate_by: isize) -> Self::Ciphertext;
+
+    fn mod_down_next(&self, ct: &mut Self::Ciphertext);
+}
+
+/// `SlotEvaluator` that operates on ciphertexts with `Evaluator`.
+///
+/// Ciphertexts are kept in `Coefficient` representation and messages are SIMD encoded at level
+/// of the ciphertext they are used with.
+pub struct EncryptedEvaluator<'a> {
+    pub evaluator: &'a Evaluator,
+    pub sk: &'a SecretKey,
+    pub ek: &'a EvaluationKey,
+}
+
+impl<'a> EncryptedEvaluator<'a> {
+    pub fn new(
+        evaluator: &'a Evaluator,
+        sk: &'a SecretKey,
+        ek: &'a EvaluationKey,
+    ) -> EncryptedEvaluator<'a> {
+        EncryptedEvaluator { evaluator, sk, ek }
+    }
+}
+
+impl SlotEvaluator for EncryptedEvaluator<'_> {
+    type Ciphertext = Ciphertext;
+
+    fn encrypt<R: CryptoRng + RngCore>(&self, m: &[u64], rng: &mut R) -> Ciphertext {
+        let pt = self.evaluator.plaintext_encode(m, Encoding::default());
+        self.evaluator.encrypt(self.sk, &pt, rng)
+    }
+
+    fn decrypt(&self, ct: &Ciphertext) -> Vec<u64> {
+        let pt = self.evaluator.decrypt(self.sk, ct);
+        self.evaluator.plaintext_decode(&pt, Encoding::default())
+    }
+
+    fn level(&self, ct: &Ciphertext) -> usize {
+        ct.level()
+    }
+
+    fn add(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
+        self.evaluator.add(lhs, rhs)
+    }
+
+    fn sub(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
+        self.evaluator.sub(lhs, rhs)
+    }
+
+    fn mul(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
+        self.evaluator
+            .relinearize(&self.evaluator.mul(lhs, rhs), self.ek)
+    }
+
+    fn mul_plaintext(&self, ct: &Ciphertext, m: &[u64]) -> Ciphertext {
+        let pt = self
+            .evaluator
+            .plaintext_encode(m, Encoding::simd(ct.level(), PolyCache::Mul(PolyType::Q)));
+        let mut ct = ct.clone();
+        self.evaluator
+            .ciphertext_change_representation(&mut ct, Representation::Evaluation);
+        self.evaluator.mul_plaintext_assign(&mut ct, &pt);
+        self.evaluator
+            .ciphertext_change_representation(&mut ct, Representation::Coefficient);
+        ct
+    }
+
+    fn add_plaintext(&self, ct: &Ciphertext, m: &[u64]) -> Ciphertext {
+        let pt = self.evaluator.plaintext_encode(
+            m,
+            Encoding::simd(ct.level(), PolyCache::AddSub(Representation::Coefficient)),
+        );
+        self.evaluator.add_plaintext(ct, &pt)
+    }
+
+    fn rotate(&self, ct: &Ciphertext, rotate_by: isize) -> Ciphertext {
+        self.evaluator.rotate(ct, rotate_by, self.ek)
+    }
+
+    fn mod_down_next(&self, ct: &mut Ciphertext) {
+        self.evaluator.mod_down_next(ct)
+    }
+}
+
+/// Message "encrypted" by `Simulator`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedCiphertext {
+    /// Slots in the order of SIMD encoding, ie first row followed by second row
+    pub m: Vec<u64>,
+    pub level: usize,
+}
+
+/// `SlotEvaluator` that evaluates operations on messages in the clear.
+///
+/// Results match `EncryptedEvaluator` as long as ciphertexts have noise budget left (which is
+/// not simulated). Levels are tracked to catch the same misuse as `Evaluator`, for ex adding
+/// ciphertexts at different levels or key switching at the last level.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    degree: usize,
+    max_level: usize,
+    plaintext_modulus_op: Modulus,
+}
+
+impl Simulator {
+    pub fn new(params: &BfvParameters) -> Simulator {
+        Simulator {
+            degree: params.degree,
+            max_level: params.max_level,
+            plaintext_modulus_op: params.plaintext_modulus_op.clone(),
+        }
+    }
+
+    /// Pads message with zeros to N values mod t
+    fn slots(&self, m: &[u64]) -> Vec<u64> {
+        assert!(m.len() <= self.degree, "Message length exceeds degree");
+        let mut slots = m.to_vec();
+        slots.resize(self.degree, 0);
+        self.plaintext_modulus_op.reduce_vec(&mut slots);
+        slots
+    }
+
+    fn binary_op<F: Fn(&mut [u64], &[u64])>(
+        &self,
+        lhs: &SimulatedCiphertext,
+        rhs: &SimulatedCiphertext,
+        op: F,
+    ) -> SimulatedCiphertext {
+        check_level(lhs.level, rhs.level).unwrap();
+        let mut m = lhs.m.clone();
+        op(&mut m, &rhs.m);
+        SimulatedCiphertext {
+            m,
+            level: lhs.level,
+        }
+    }
+
+    /// Key switching is not supported at the last level
+    fn check_key_switching(&self, ct: &SimulatedCiphertext) {
+        check_level_range(ct.level, 0, self.max_level - 1).unwrap();
+    }
+}
+
+impl SlotEvaluator for Simulator {
+    type Ciphertext = SimulatedCiphertext;
+
+    fn encrypt<R: CryptoRng + RngCore>(&self, m: &[u64], _rng: &mut R) -> SimulatedCiphertext {
+        SimulatedCiphertext {
+            m: self.slots(m),
+            level: 0,
+        }
+    }
+
+    fn decrypt(&self, ct: &SimulatedCiphertext) -> Vec<u64> {
+        ct.m.clone()
+    }
+
+    fn level(&self, ct: &SimulatedCiphertext) -> usize {
+        ct.level
+    }
+
+    fn add(&self, lhs: &SimulatedCiphertext, rhs: &SimulatedCiphertext) -> SimulatedCiphertext {
+        self.binary_op(lhs, rhs, |a, b| {
+            self.plaintext_modulus_op.add_mod_fast_vec(a, b)
+        })
+    }
+
+    fn sub(&self, lhs: &SimulatedCiphertext, rhs: &SimulatedCiphertext) -> SimulatedCiphertext {
+        self.binary_op(lhs, rhs, |a, b| {
+            self.plaintext_modulus_op.sub_mod_fast_vec(a, b)
+        })
+    }
+
+    fn mul(&self, lhs: &SimulatedCiphertext, rhs: &SimulatedCiphertext) -> SimulatedCiphertext {
+        self.check_key_switching(lhs);
+        self.binary_op(lhs, rhs, |a, b| {
+            self.plaintext_modulus_op.mul_mod_fast_vec(a, b)
+        })
+    }
+
+    fn mul_plaintext(&self, ct: &SimulatedCiphertext, m: &[u64]) -> SimulatedCiphertext {
+        let mut ct = ct.clone();
+        self.plaintext_modulus_op
+            .mul_mod_fast_vec(&mut ct.m, &self.slots(m));
+        ct
+    }
+
+    fn add_plaintext(&self, ct: &SimulatedCiphertext, m: &[u64]) -> SimulatedCiphertext {
+        let mut ct = ct.clone();
+        self.plaintext_modulus_op
+            .add_mod_fast_vec(&mut ct.m, &self.slots(m));
+        ct
+    }
+
+    fn rotate(&self, ct: &SimulatedCiphertext, rotate_by: isize) -> SimulatedCiphertext {
+        self.check_key_switching(ct);
+        let mut ct = ct.clone();
+        let row_size = self.degree / 2;
+        if rotate_by == (2 * self.degree - 1) as isize {
+            // galois element 2N - 1 swaps rows
+            ct.m.rotate_left(row_size);
+        } else {
+            // galois element 3^i rotates rows left by i (see `rot_to_galois_element`)
+            let by = rotate_by.rem_euclid(row_size as isize) as usize;
+            ct.m.chunks_mut(row_size)
+                .for_each(|row| row.rotate_left(by));
+        }
+        ct
+    }
+
+    fn mod_down_next(&self, ct: &mut SimulatedCiphertext) {
+        check_level_range(ct.level, 0, self.max_level - 1).unwrap();
+        ct.level += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    /// Application code that runs against any backend
+    fn circuit<E: SlotEvaluator>(e: &E, x: &[u64], y: &[u64]) -> Vec<u64> {
+        let mut rng = thread_rng();
+        let cx = e.encrypt(x, &mut rng);
+        let cy = e.encrypt(y, &mut rng);
+
+        let mut z = e.mul(&cx, &cy);
+        z = e.add(&z, &e.rotate(&z, 1));
+        z = e.sub(&z, &e.rotate(&z, -3));
+        z = e.add(&z, &e.rotate(&z, 127));
+        e.mod_down_next(&mut z);
+        assert_eq!(e.level(&z), 1);
+
+        let z = e.mul_plaintext(&z, y);
+        let z = e.add_plaintext(&z, &x[..10]);
+        e.decrypt(&z)
+    }
+
+    #[test]
+    fn simulator_matches_evaluator() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 6);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(
+            &params,
+            &sk,
+            &[0],
+            &[0, 0, 0],
+            &[1, -3, (2 * params.degree - 1) as isize],
+            &mut rng,
+        );
+        let simulator = Simulator::new(&params);
+        let evaluator = Evaluator::new(params);
+        let encrypted = EncryptedEvaluator::new(&evaluator, &sk, &ek);
+
+        let modt = &evaluator.params().plaintext_modulus_op;
+        let x = modt.random_vec(evaluator.params().degree, &mut rng);
+        let y = modt.random_vec(evaluator.params().degree, &mut rng);
+        assert_eq!(circuit(&simulator, &x, &y), circuit(&encrypted, &x, &y));
+    }
+}
@@ -29,6 +29,27 @@ pub fn rot_to_galois_element(i: isize, n: usize) -> usize {
     }
 }
 
+/// Returns non-adjacent form of `value` with digits in {-1, 0, 1} in little-endian order
+pub fn naf(mut value: usize) -> Vec<i8> {
+    let mut digits = vec![];
+    while value != 0 {
+        if value & 1 == 1 {
+            // 2 - (value mod 4)
+            let d = 2 - (value & 3) as i8;
+            if d == 1 {
+                value -= 1;
+            } else {
+                value += 1;
+            }
+            digits.push(d);
+        } else {
+            digits.push(0);
+        }
+        value >>= 1;
+    }
+    digits
+}
+
 pub fn mod_inverse_biguint_u64(a: &BigUint, m: u64) -> BigUint {
     let a_dig = BigUintDig::from_bytes_le(&a.to_bytes_le());
     let m_dig = BigUintDig::from_u64(m).unwrap();
@@ -265,6 +286,23 @@ mod tests {
         dbg!(v);
     }
 
+    #[test]
+    fn naf_works() {
+        for value in 0..1024usize {
+            let digits = naf(value);
+            let mut res = 0isize;
+            digits.iter().enumerate().for_each(|(i, d)| {
+                res += (*d as isize) << i;
+            });
+            assert_eq!(res, value as isize);
+
+            // no two adjacent digits are non-zero
+            digits.windows(2).for_each(|w| {
+                assert!(w[0] == 0 || w[1] == 0);
+            });
+        }
+    }
+
     #[test]
     fn convert_to_and_from_bytes() {
         for prime_bits in [17, 43, 50, 59] {
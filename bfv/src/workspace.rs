@@ -0,0 +1,97 @@
+use crate::poly::PolyContext;
+use crate::{Poly, Representation};
+use ndarray::Array2;
+use std::mem;
+use traits::Ntt;
+
+/// Pool of coefficient buffers used by `_into` variants of operations (for ex,
+/// `Evaluator::mul_into`) for temporary polynomials.
+///
+/// Buffers are taken from the pool by shape and returned to it once the operation is done. Thus,
+/// after the first iteration, a loop of operations with the same workspace and outputs does not
+/// allocate. A workspace must not be shared across threads; use one workspace per thread instead.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    buffers: Vec<Array2<u64>>,
+    poly_vecs: Vec<Vec<Poly>>,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    /// No. of buffers currently in the pool
+    pub fn buffers_count(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Returns buffer of shape `rows` x `degree`. Values of the buffer are arbitrary.
+    pub(crate) fn take(&mut self, rows: usize, degree: usize) -> Array2<u64> {
+        match self.buffers.iter().position(|b| b.dim() == (rows, degree)) {
+            Some(index) => self.buffers.swap_remove(index),
+            None => Array2::zeros((rows, degree)),
+        }
+    }
+
+    pub(crate) fn give(&mut self, buffer: Array2<u64>) {
+        if !buffer.is_empty() && buffer.is_standard_layout() {
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// Returns polynomial of `ctx` with arbitrary coefficients
+    pub(crate) fn take_poly<T: Ntt>(
+        &mut self,
+        ctx: &PolyContext<'_, T>,
+        representation: Representation,
+    ) -> Poly {
+        Poly::new(self.take(ctx.moduli_count, ctx.degree), representation)
+    }
+
+    pub(crate) fn give_poly(&mut self, poly: Poly) {
+        self.give(poly.coefficients);
+    }
+
+    /// Swaps coefficients of `poly` with a buffer from the pool if their shape does not match
+    /// `ctx`. Coefficients are arbitrary afterwards.
+    pub(crate) fn fit_poly<T: Ntt>(&mut self, poly: &mut Poly, ctx: &PolyContext<'_, T>) {
+        if poly.coefficients.dim() != (ctx.moduli_count, ctx.degree)
+            || !poly.coefficients.is_standard_layout()
+        {
+            let buffer = mem::replace(
+                &mut poly.coefficients,
+                self.take(ctx.moduli_count, ctx.degree),
+            );
+            self.give(buffer);
+        }
+    }
+
+    /// Resizes `polys` to `count` polynomials of `ctx` with arbitrary coefficients
+    pub(crate) fn fit_polys<T: Ntt>(
+        &mut self,
+        polys: &mut Vec<Poly>,
+        count: usize,
+        ctx: &PolyContext<'_, T>,
+    ) {
+        while polys.len() > count {
+            let poly = polys.pop().unwrap();
+            self.give_poly(poly);
+        }
+        for poly in polys.iter_mut() {
+            self.fit_poly(poly, ctx);
+        }
+        while polys.len() < count {
+            polys.push(self.take_poly(ctx, Representation::Unknown));
+        }
+    }
+
+    /// Returns vector of polynomials to be resized with `fit_polys`
+    pub(crate) fn take_polys(&mut self) -> Vec<Poly> {
+        self.poly_vecs.pop().unwrap_or_default()
+    }
+
+    pub(crate) fn give_polys(&mut self, polys: Vec<Poly>) {
+        self.poly_vecs.push(polys);
+    }
+}
@@ -0,0 +1,86 @@
+//! Checks that steady-state `_into` operations do not allocate. Installs a counting global
+//! allocator, hence lives in its own test binary.
+
+// rayon allocates to schedule tasks
+#![cfg(not(feature = "parallel"))]
+
+use bfv::{BfvParameters, Ciphertext, Encoding, EvaluationKey, Evaluator, SecretKey, Workspace};
+use rand::thread_rng;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts allocations of the current thread while `COUNTING` is set
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.try_with(|c| c.get()).unwrap_or(false) {
+            ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn steady_state_ops_do_not_allocate() {
+    let mut rng = thread_rng();
+    let params = BfvParameters::default(5, 1 << 6);
+    let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+    let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+    let evaluator = Evaluator::new(params);
+
+    let m0 = evaluator
+        .params()
+        .plaintext_modulus_op
+        .random_vec(evaluator.params().degree, &mut rng);
+    let m1 = evaluator
+        .params()
+        .plaintext_modulus_op
+        .random_vec(evaluator.params().degree, &mut rng);
+    let ct0 = evaluator.encrypt(
+        &sk,
+        &evaluator.plaintext_encode(&m0, Encoding::default()),
+        &mut rng,
+    );
+    let ct1 = evaluator.encrypt(
+        &sk,
+        &evaluator.plaintext_encode(&m1, Encoding::default()),
+        &mut rng,
+    );
+
+    let mut ws = Workspace::new();
+    let mut product = Ciphertext::placeholder();
+    let mut relinearized = Ciphertext::placeholder();
+    let mut rotated = Ciphertext::placeholder();
+    for iteration in 0..3 {
+        // first iteration fills the workspace
+        COUNTING.with(|c| c.set(iteration != 0));
+        evaluator.mul_into(&ct0, &ct1, &mut product, &mut ws);
+        evaluator.relinearize_into(&product, &ek, &mut relinearized, &mut ws);
+        evaluator.rotate_into(&relinearized, 1, &ek, &mut rotated, &mut ws);
+        COUNTING.with(|c| c.set(false));
+    }
+    assert_eq!(ALLOCATIONS.with(|a| a.get()), 0);
+
+    let expected = evaluator.rotate(
+        &evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek),
+        1,
+        &ek,
+    );
+    assert_eq!(
+        evaluator.plaintext_decode(&evaluator.decrypt(&sk, &rotated), Encoding::default()),
+        evaluator.plaintext_decode(&evaluator.decrypt(&sk, &expected), Encoding::default())
+    );
+}
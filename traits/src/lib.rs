@@ -13,35 +13,45 @@ pub trait Ntt: Sync + Send + PartialEq + Clone + Debug {
 pub trait TryFromWithPolyContext<'a>: Sized {
     type Value;
     type PolyContext;
+    type Error;
 
-    fn try_from_with_context(value: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self;
+    fn try_from_with_context(
+        value: &Self::Value,
+        poly_ctx: &'a Self::PolyContext,
+    ) -> Result<Self, Self::Error>;
 }
 
 pub trait TryFromWithParameters: Sized {
     type Value;
     type Parameters;
+    type Error;
 
-    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self;
+    fn try_from_with_parameters(
+        value: &Self::Value,
+        parameters: &Self::Parameters,
+    ) -> Result<Self, Self::Error>;
 }
 
 pub trait TryEncodingWithParameters<V>: Sized {
     type Parameters;
     type Encoding;
+    type Error;
 
     fn try_encoding_with_parameters(
         value: V,
         parameters: &Self::Parameters,
         encoding: Self::Encoding,
-    ) -> Self;
+    ) -> Result<Self, Self::Error>;
 }
 
 pub trait TryDecodingWithParameters<V>: Sized {
     type Parameters;
     type Encoding;
+    type Error;
 
     fn try_decoding_with_parameters(
         value: V,
         parameters: Self::Parameters,
         encoding: Self::Encoding,
-    ) -> Self;
+    ) -> Result<Self, Self::Error>;
 }